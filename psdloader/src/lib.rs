@@ -87,12 +87,12 @@ impl <T> BinaryLoaderUtils for T where T: std::io::prelude::Read
 /// Indicates that the structure represents part of file content
 trait NativeFileContent<ReturnT: std::marker::Sized = Self>
 {
-	fn read_from_file(mut fp: std::fs::File) -> Result<(ReturnT, std::fs::File), PSDLoadingError>;
+	fn read_from_file(fp: std::fs::File) -> Result<(ReturnT, std::fs::File), PSDLoadingError>;
 }
 /// Indicates that the structure represents part of file content(reports number of bytes)
 trait UnsizedNativeFileContent<ReturnT: std::marker::Sized = Self>
 {
-	fn read_from_file(mut fp: std::fs::File) -> Result<(ReturnT, usize, std::fs::File), PSDLoadingError>;
+	fn read_from_file(fp: std::fs::File) -> Result<(ReturnT, usize, std::fs::File), PSDLoadingError>;
 }
 
 /// Binary Structures of PSD
@@ -14,3 +14,36 @@ pub const PLAYER_SIZE: f32 = 1.5;
 pub const SCREEN_SIZE: f32 = 24.0;
 pub const PLAYER_LIMIT: f32 = SCREEN_SIZE - PLAYER_SIZE;
 pub const PLAYER_VLIMIT: f32 = SCREEN_SIZE * 2.0 - PLAYER_SIZE;
+pub const PLAYER_HIT_RADIUS: f32 = 1.0;
+/// The hitbox while focusing(Slowdown is held)
+pub const PLAYER_FOCUS_HIT_RADIUS: f32 = 0.25;
+pub const BULLET_HIT_RADIUS: f32 = 0.5;
+/// Half extents of the hitboxes
+pub const ENEMY_SIZE: f32 = 1.25;
+pub const PLAYER_BULLET_SIZE: f32 = 0.25;
+
+// Rules
+pub const PLAYER_INITIAL_LIVES: u32 = 3;
+/// Secs of invulnerability after a hit
+pub const PLAYER_INVINCIBLE_SECS: f32 = 2.0;
+/// Secs before a game over screen accepts Shoot to continue
+pub const GAME_OVER_WAIT_SECS: f32 = 1.5;
+
+// Scoring
+pub const KILL_POINTS: u32 = 100;
+pub const SQUAD_CLEAR_POINTS: u32 = 1000;
+pub const GRAZE_POINTS: u32 = 10;
+/// Bullets passing within this distance from the edge of the player hitbox are grazed
+pub const GRAZE_DISTANCE: f32 = 1.5;
+pub const KILL_COMBO_GAIN: f32 = 0.1;
+pub const GRAZE_COMBO_GAIN: f32 = 0.02;
+pub const MAX_COMBO_MULTIPLIER: f32 = 8.0;
+/// Secs the combo multiplier is kept after a gain, before decaying
+pub const COMBO_HOLD_SECS: f32 = 1.0;
+pub const COMBO_DECAY_PER_SEC: f32 = 0.5;
+
+// Simulation
+/// The length of a gameplay step in game secs(the world is stepped by this regardless of the frame rate)
+pub const FIXED_STEP: f32 = 1.0 / 60.0;
+/// Steps in an update at most(the rest of a long frame is dropped instead of stalling)
+pub const MAX_STEPS_PER_UPDATE: u32 = 8;
@@ -1,23 +1,35 @@
 
 use rand;
 use rand::distributions::*;
-use structures;
 use constants::*;
+use utils::lerp;
 use GameUpdateArgs;
 
-pub struct BackgroundDatastore<'a>
+/// A background panel(offset.w holds the count of lines)
+#[derive(Clone, Copy)]
+pub struct BackgroundPanel { pub offset: [f32; 4], pub scale: [f32; 4], prev_top: f32 }
+impl BackgroundPanel
 {
-	buffer_data: &'a mut [structures::BackgroundInstance; MAX_BK_COUNT],
-	instance_data: &'a mut [u32; MAX_BK_COUNT]
+	/// Offset interpolated between the previous and the current step
+	pub fn render_offset(&self, alpha: f32) -> [f32; 4]
+	{
+		[self.offset[0], lerp(self.prev_top, self.offset[1], alpha), self.offset[2], self.offset[3]]
+	}
+}
+
+pub struct BackgroundDatastore
+{
+	panels: [BackgroundPanel; MAX_BK_COUNT],
+	instance_data: [u32; MAX_BK_COUNT]
 }
-impl <'a> BackgroundDatastore<'a>
+impl BackgroundDatastore
 {
-	pub fn new(buffer_data_ref: &'a mut [structures::BackgroundInstance; MAX_BK_COUNT], instance_data_ref: &'a mut [u32; MAX_BK_COUNT]) -> Self
+	pub fn new() -> Self
 	{
 		BackgroundDatastore
 		{
-			buffer_data: buffer_data_ref,
-			instance_data: instance_data_ref
+			panels: [BackgroundPanel { offset: [0.0; 4], scale: [0.0; 4], prev_top: 0.0 }; MAX_BK_COUNT],
+			instance_data: [0; MAX_BK_COUNT]
 		}
 	}
 	pub fn update(&mut self, update_args: &mut GameUpdateArgs, appear: bool)
@@ -35,17 +47,22 @@ impl <'a> BackgroundDatastore<'a>
 				{
 					let scale = scale_range.sample(&mut update_args.randomizer);
 					*m = 1;
-					self.buffer_data[i].offset = [left_range.sample(&mut update_args.randomizer), -20.0f32, -20.0f32,
+					self.panels[i].offset = [left_range.sample(&mut update_args.randomizer), -20.0f32, -20.0f32,
 						count_range.sample(&mut update_args.randomizer) as f32];
-					self.buffer_data[i].scale = [scale, scale, 1.0f32, 1.0f32];
+					self.panels[i].scale = [scale, scale, 1.0f32, 1.0f32];
+					self.panels[i].prev_top = self.panels[i].offset[1];
 					require_appear = false;
 				}
 			}
 			else
 			{
-				self.buffer_data[i].offset[1] += update_args.delta_time * 22.0f32;
-				*m = if self.buffer_data[i].offset[1] >= 20.0f32 { 0 } else { 1 };
+				self.panels[i].prev_top = self.panels[i].offset[1];
+				self.panels[i].offset[1] += update_args.delta_time * 22.0f32;
+				*m = if self.panels[i].offset[1] >= 20.0f32 { 0 } else { 1 };
 			}
 		}
 	}
+
+	pub fn panels(&self) -> &[BackgroundPanel; MAX_BK_COUNT] { &self.panels }
+	pub fn instance_data(&self) -> &[u32; MAX_BK_COUNT] { &self.instance_data }
 }
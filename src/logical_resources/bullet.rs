@@ -1,80 +1,144 @@
 
 use utils::*;
 use constants::*;
-use structures::*;
-use interlude::CVector4;
+use std;
 use rayon::prelude::*;
 use GameUpdateArgs;
 
-pub struct BulletDatastore<'a>
+pub struct BulletDatastore
 {
-	instance_memory_ref: &'a mut [BulletInstance; MAX_BULLETS],
+	lifetimes: Vec<f32>, availability: Vec<bool>,
 	memory_block_manager: MemoryBlockManager
 }
-impl<'a> BulletDatastore<'a>
+impl BulletDatastore
 {
-	pub fn new(im_ref: &'a mut [BulletInstance; MAX_BULLETS]) -> Self
+	pub fn new() -> Self
 	{
-		BulletDatastore { instance_memory_ref: im_ref, memory_block_manager: MemoryBlockManager::new(MAX_BULLETS as u32) }
+		BulletDatastore
+		{
+			lifetimes: vec![0.0; MAX_BULLETS], availability: vec![false; MAX_BULLETS],
+			memory_block_manager: MemoryBlockManager::new(MAX_BULLETS as u32)
+		}
 	}
 	pub fn allocate(&mut self) -> Option<u32>
 	{
 		let index = self.memory_block_manager.allocate();
-		if let Some(index) = index { self.enable_instance(index); }
+		if let Some(index) = index { self.availability[index as usize] = true; }
 		index
 	}
 	pub fn free(&mut self, index: u32)
 	{
 		self.memory_block_manager.free(index);
-		self.disable_instance(index);
+		self.availability[index as usize] = false;
 	}
 
-	fn enable_instance(&mut self, index: u32)
-	{
-		self.instance_memory_ref[index as usize].available = 1.0;
-	}
-	fn disable_instance(&mut self, index: u32)
-	{
-		self.instance_memory_ref[index as usize].available = 0.0;
-	}
+	pub fn init_lifetime(&mut self, index: u32) { self.lifetimes[index as usize] = 0.0; }
+	pub fn increase_all_lifetime(&mut self, amount: f32) { self.lifetimes.par_iter_mut().for_each(|l| *l += amount); }
 
-	pub fn init_lifetime(&mut self, index: u32) { self.instance_memory_ref[index as usize].lifetime = 0.0; }
-	pub fn increase_all_lifetime(&mut self, amount: f32) { self.instance_memory_ref.par_iter_mut().for_each(|l| l.lifetime += amount); }
+	pub fn lifetime(&self, index: usize) -> f32 { self.lifetimes[index] }
+	pub fn is_available(&self, index: usize) -> bool { self.availability[index] }
 }
 
-pub enum Bullet<'a>
+/// Motions of non-linear bullets(angles in radians, times in secs)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion
+{
+	/// The speed changes by `accel` per sec until it reaches `limit`
+	Accelerating { accel: f32, limit: f32 },
+	/// The direction turns by `angular_velocity` per sec
+	Curving { angular_velocity: f32 },
+	/// Turns toward the target(the player) by `turn_rate` per sec at most, for `secs`
+	Homing { turn_rate: f32, secs: f32 },
+	/// Sways across the direction by `amplitude`, `frequency` times per sec
+	Wave { amplitude: f32, frequency: f32 },
+	/// Changes the direction(toward the target if `angle` is None) and the speed after `delay`
+	Redirect { delay: f32, angle: Option<f32>, speed: f32 }
+}
+
+fn is_out_of_screen(t: &[f32; 4]) -> bool { t[0].abs() * 0.9 > SCREEN_SIZE || !(-1.0 <= t[1] && t[1] <= SCREEN_SIZE * 2.0 + 1.0) }
+/// Angle of the direction from a point to the target(0 = down, same as the angles of bullets)
+fn angle_to(from: &[f32; 2], target: (f32, f32)) -> f32 { (target.0 - from[0]).atan2(target.1 - from[1]) }
+
+pub enum Bullet
 {
 	Free, Garbage(u32),
-	Linear { block_index: u32, movec: [f32; 2], translation: &'a mut CVector4 }
+	Linear { block_index: u32, movec: [f32; 2], translation: [f32; 4], prev_translation: [f32; 4] },
+	/// A bullet with a motion: it goes from `origin` to `angle` at `speed`, and is displaced across the direction by waves
+	Moving
+	{
+		block_index: u32, motion: Motion, angle: f32, speed: f32, living_secs: f32, origin: [f32; 2],
+		translation: [f32; 4], prev_translation: [f32; 4]
+	}
 }
-unsafe impl<'a> Send for Bullet<'a> {}
-impl<'a> Bullet<'a>
+impl Bullet
 {
-	pub fn init_linear(block_index: u32, tref: &'a mut CVector4, from: &CVector4, angle: f32, speed: f32) -> Self
+	pub fn init_linear(block_index: u32, from: &[f32; 4], angle: f32, speed: f32) -> Self
 	{
-		*tref = *from;
 		let (s, c) = angle.sin_cos();
 
 		Bullet::Linear
 		{
-			block_index: block_index, translation: tref, 
+			block_index: block_index, translation: *from, prev_translation: *from,
 			movec: [s * speed, c * speed]
 		}
 	}
-	pub fn update(&mut self, update_args: &GameUpdateArgs)
+	pub fn init_moving(block_index: u32, from: &[f32; 4], angle: f32, speed: f32, motion: Motion) -> Self
+	{
+		Bullet::Moving
+		{
+			block_index: block_index, motion: motion, angle: angle, speed: speed, living_secs: 0.0, origin: [from[0], from[1]],
+			translation: *from, prev_translation: *from
+		}
+	}
+	/// Moves the bullet(`target` is the point homing/redirecting bullets head for)
+	pub fn update(&mut self, update_args: &GameUpdateArgs, target: (f32, f32))
 	{
 		let died_index = match self
 		{
-			&mut Bullet::Linear { block_index, movec, ref mut translation } =>
+			&mut Bullet::Linear { block_index, movec, ref mut translation, ref mut prev_translation } =>
 			{
+				*prev_translation = *translation;
 				// Linear motion
 				translation[0] += movec[0] * update_args.delta_time;
 				translation[1] += movec[1] * update_args.delta_time;
-				if translation[0].abs() * 0.9 > SCREEN_SIZE || !(-1.0 <= translation[1] && translation[1] <= SCREEN_SIZE * 2.0 + 1.0)
+				if is_out_of_screen(translation) { Some(block_index) } else { None }
+			},
+			&mut Bullet::Moving
+			{
+				block_index, motion, ref mut angle, ref mut speed, ref mut living_secs, ref mut origin,
+				ref mut translation, ref mut prev_translation
+			} =>
+			{
+				let dt = update_args.delta_time;
+				*prev_translation = *translation;
+				let mut sway = 0.0;
+				match motion
 				{
-					Some(block_index)
+					Motion::Accelerating { accel, limit } =>
+						*speed = if accel >= 0.0 { (*speed + accel * dt).min(limit) } else { (*speed + accel * dt).max(limit) },
+					Motion::Curving { angular_velocity } => *angle += angular_velocity * dt,
+					Motion::Homing { turn_rate, secs } => if *living_secs < secs
+					{
+						// the shorter way around
+						let d = angle_to(origin, target) - *angle;
+						let d = d - (d / (2.0 * std::f32::consts::PI)).round() * 2.0 * std::f32::consts::PI;
+						*angle += d.max(-turn_rate * dt).min(turn_rate * dt);
+					},
+					Motion::Wave { amplitude, frequency } =>
+						sway = amplitude * ((*living_secs + dt) * frequency * 2.0 * std::f32::consts::PI).sin(),
+					Motion::Redirect { delay, angle: new_angle, speed: new_speed } => if *living_secs <= delay && delay < *living_secs + dt
+					{
+						*angle = new_angle.unwrap_or_else(|| angle_to(origin, target));
+						*speed = new_speed;
+					}
 				}
-				else { None }
+				*living_secs += dt;
+				let (s, c) = angle.sin_cos();
+				origin[0] += s * *speed * dt;
+				origin[1] += c * *speed * dt;
+				translation[0] = origin[0] + c * sway;
+				translation[1] = origin[1] - s * sway;
+				if is_out_of_screen(translation) { Some(block_index) } else { None }
 			},
 			_ => None
 		};
@@ -82,12 +146,134 @@ impl<'a> Bullet<'a>
 		if let Some(di) = died_index { *self = Bullet::Garbage(di); }
 	}
 
+	/// Crashes into a circle(center and radius): the bullet is collected if hit
+	pub fn crash(&mut self, t_left: f32, t_top: f32, radius: f32) -> bool
+	{
+		let died_index = match self
+		{
+			&mut Bullet::Linear { block_index, ref translation, .. } | &mut Bullet::Moving { block_index, ref translation, .. } =>
+			{
+				let (xd, yd, r) = (translation[0] - t_left, translation[1] - t_top, radius + BULLET_HIT_RADIUS);
+				if xd * xd + yd * yd <= r * r { Some(block_index) } else { None }
+			},
+			_ => None
+		};
+
+		if let Some(di) = died_index { *self = Bullet::Garbage(di); true } else { false }
+	}
 	pub fn is_garbage(&self) -> bool { match self { &Bullet::Garbage(_) => true, _ => false } }
+	/// Current translation, if the bullet is alive
+	pub fn translation(&self) -> Option<&[f32; 4]>
+	{
+		match self { &Bullet::Linear { ref translation, .. } | &Bullet::Moving { ref translation, .. } => Some(translation), _ => None }
+	}
+	/// Translation interpolated between the previous and the current step, if the bullet is alive
+	pub fn render_translation(&self, alpha: f32) -> Option<[f32; 4]>
+	{
+		match self
+		{
+			&Bullet::Linear { translation: t, prev_translation: p, .. } | &Bullet::Moving { translation: t, prev_translation: p, .. } =>
+				Some([lerp(p[0], t[0], alpha), lerp(p[1], t[1], alpha), lerp(p[2], t[2], alpha), lerp(p[3], t[3], alpha)]),
+			_ => None
+		}
+	}
 }
 
+/// A bullet to fire: (from, angle, speed)
+pub type FireInfo = ([f32; 4], f32, f32);
+/// Bullets to fire, with the motion parameters shared by them
 pub enum FireRequest
 {
-	Linears(Vec<(CVector4, f32, f32)>)
+	Linears(Vec<FireInfo>),
+	/// accel, limit of the speed
+	Accelerations(Vec<FireInfo>, f32, f32),
+	/// angular velocity
+	Curves(Vec<FireInfo>, f32),
+	/// turn rate, secs
+	Homings(Vec<FireInfo>, f32, f32),
+	/// amplitude, frequency
+	Waves(Vec<FireInfo>, f32, f32),
+	/// delay, angle(None: toward the player), speed
+	Redirects(Vec<FireInfo>, f32, Option<f32>, f32)
+}
+impl FireRequest
+{
+	/// Requests the bullets with a motion(None: linear)
+	pub fn new(bullets: Vec<FireInfo>, motion: Option<Motion>) -> Self
+	{
+		match motion
+		{
+			None => FireRequest::Linears(bullets),
+			Some(Motion::Accelerating { accel, limit }) => FireRequest::Accelerations(bullets, accel, limit),
+			Some(Motion::Curving { angular_velocity }) => FireRequest::Curves(bullets, angular_velocity),
+			Some(Motion::Homing { turn_rate, secs }) => FireRequest::Homings(bullets, turn_rate, secs),
+			Some(Motion::Wave { amplitude, frequency }) => FireRequest::Waves(bullets, amplitude, frequency),
+			Some(Motion::Redirect { delay, angle, speed }) => FireRequest::Redirects(bullets, delay, angle, speed)
+		}
+	}
+	/// The bullets and their motion(None: linear)
+	pub fn bullets(&self) -> (&[FireInfo], Option<Motion>)
+	{
+		match self
+		{
+			&FireRequest::Linears(ref b) => (b, None),
+			&FireRequest::Accelerations(ref b, accel, limit) => (b, Some(Motion::Accelerating { accel: accel, limit: limit })),
+			&FireRequest::Curves(ref b, angular_velocity) => (b, Some(Motion::Curving { angular_velocity: angular_velocity })),
+			&FireRequest::Homings(ref b, turn_rate, secs) => (b, Some(Motion::Homing { turn_rate: turn_rate, secs: secs })),
+			&FireRequest::Waves(ref b, amplitude, frequency) => (b, Some(Motion::Wave { amplitude: amplitude, frequency: frequency })),
+			&FireRequest::Redirects(ref b, delay, angle, speed) => (b, Some(Motion::Redirect { delay: delay, angle: angle, speed: speed }))
+		}
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use seeded_randomizer;
+
+	fn run(motion: Motion, angle: f32, speed: f32, secs: f32, target: (f32, f32)) -> Bullet
+	{
+		let mut rng = seeded_randomizer(1);
+		let args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng };
+		let mut b = Bullet::init_moving(0, &[0.0, 10.0, 0.0, 0.0], angle, speed, motion);
+		for _ in 0 .. (secs / FIXED_STEP).round() as usize { b.update(&args, target); }
+		b
+	}
+	fn position(b: &Bullet) -> (f32, f32) { b.translation().map(|t| (t[0], t[1])).unwrap() }
+
+	#[test] fn motions()
+	{
+		// accelerates up to the limit: 1 sec at 2..6, then 6
+		let (x, y) = position(&run(Motion::Accelerating { accel: 4.0, limit: 6.0 }, 0.0, 2.0, 2.0, (0.0, 0.0)));
+		assert!(x.abs() < 1.0e-4 && (y - (10.0 + 4.0 + 6.0)).abs() < 0.1);
+		// half a circle of radius 1/pi: turned back above the start
+		let (x, y) = position(&run(Motion::Curving { angular_velocity: std::f32::consts::PI }, std::f32::consts::PI * 0.5, 1.0, 1.0, (0.0, 0.0)));
+		assert!((x - 0.0).abs() < 0.05 && (y - (10.0 - 2.0 / std::f32::consts::PI)).abs() < 0.05);
+		// waves cross the straight path every half cycle
+		let (x, y) = position(&run(Motion::Wave { amplitude: 1.0, frequency: 1.0 }, 0.0, 4.0, 0.5, (0.0, 0.0)));
+		assert!(x.abs() < 1.0e-3 && (y - 12.0).abs() < 1.0e-3);
+		assert!(position(&run(Motion::Wave { amplitude: 1.0, frequency: 1.0 }, 0.0, 4.0, 0.25, (0.0, 0.0))).0.abs() > 0.99);
+	}
+	#[test] fn homing_and_redirect_head_for_the_target()
+	{
+		// fired sideways, turned down to the target
+		let b = run(Motion::Homing { turn_rate: std::f32::consts::PI, secs: 5.0 }, std::f32::consts::PI * 0.5, 4.0, 3.0, (0.0, 30.0));
+		let (x, y) = position(&b);
+		assert!(x.abs() < 1.0 && y > 12.0);
+		// stops turning after the secs
+		let b = run(Motion::Homing { turn_rate: std::f32::consts::PI, secs: 0.0 }, std::f32::consts::PI * 0.5, 4.0, 1.0, (0.0, 30.0));
+		assert!((position(&b).1 - 10.0).abs() < 1.0e-4);
+
+		let b = run(Motion::Redirect { delay: 0.5, angle: None, speed: 10.0 }, std::f32::consts::PI, 2.0, 1.0, (5.0, 4.0));
+		let (x, y) = position(&b);
+		// went up by 1, then toward (5, 4) from (0, 9) for 0.5 secs
+		let (dx, dy) = (5.0f32, -5.0f32);
+		let l = (dx * dx + dy * dy).sqrt();
+		assert!((x - dx / l * 5.0).abs() < 0.2 && (y - (9.0 + dy / l * 5.0)).abs() < 0.2);
+		// no delay: redirected at the first step
+		let b = run(Motion::Redirect { delay: 0.0, angle: None, speed: 5.0 }, std::f32::consts::PI, 2.0, 1.0, (0.0, 30.0));
+		let (x, y) = position(&b);
+		assert!(x.abs() < 1.0e-4 && (y - 15.0).abs() < 1.0e-3);
+	}
 }
-unsafe impl Send for FireRequest {}
-unsafe impl Sync for FireRequest {}
@@ -0,0 +1,441 @@
+// Bullet Patterns: a small scripting language for enemy firing(in the spirit of BulletML), and its interpreter
+//
+//   # comment
+//   pattern <name>
+//   {
+//       fire [direction (aim|absolute|relative|sequence) <value>] [speed [absolute|relative|sequence] <value>] [motion <motion>]
+//            [{ <actions of the fired bullet> }]
+//       repeat (<count>|forever) { <actions> }   (a `forever` body must wait)
+//       wait <secs>
+//   }
+//
+// A value is a number or `random <min> <max>`. Directions are in degrees(0 = down the screen, clockwise to the left):
+// `aim` is relative to the direction to the player, `relative` to the bullet running the actions(0 for an enemy),
+// `sequence` to the previous fire(0 at first). Speeds are in units/sec(`relative`/`sequence` are added likewise).
+// Actions in a fire block are run by an invisible emitter travelling along with the fired bullet(which must be linear).
+// Motions(numbers only; see `bullet::Motion`) are linear if omitted:
+//   accelerate <accel(units/sec^2)> <speed limit>, curve <degrees/sec>, homing <degrees/sec> <secs>,
+//   wave <amplitude> <cycles/sec>, redirect <delay secs> (aim|<direction>) <speed>
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use rand;
+use rand::distributions::*;
+use constants::*;
+use super::bullet::{FireRequest, Motion};
+use GameUpdateArgs;
+
+/// The built-in patterns(used if the pattern assets are not available)
+pub const BUILTIN_PATTERNS: &'static str = include_str!("../../assets/patterns/zako.bpat");
+/// Actions run in an update at most(guards against loops which wait too little)
+const MAX_ACTIONS_PER_UPDATE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value { Constant(f32), Random(f32, f32) }
+impl Value
+{
+	fn eval(&self, update_args: &mut GameUpdateArgs) -> f32
+	{
+		match self
+		{
+			&Value::Constant(v) => v,
+			&Value::Random(min, max) => rand::distributions::Range::new(min, max).ind_sample(&mut update_args.randomizer)
+		}
+	}
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction { Aim(Value), Absolute(Value), Relative(Value), Sequence(Value) }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed { Absolute(Value), Relative(Value), Sequence(Value) }
+#[derive(Debug, PartialEq)]
+pub enum Action
+{
+	Fire { direction: Direction, speed: Speed, motion: Option<Motion>, actions: Option<Arc<Vec<Action>>> },
+	/// None repeats forever
+	Repeat(Option<u32>, Arc<Vec<Action>>),
+	Wait(Value)
+}
+pub type Pattern = Arc<Vec<Action>>;
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError { pub line: usize, pub message: String }
+fn error<T>(line: usize, message: String) -> Result<T, ParseError> { Err(ParseError { line: line, message: message }) }
+
+struct Tokens<'s> { tokens: Vec<(usize, &'s str)>, pos: usize }
+impl<'s> Tokens<'s>
+{
+	fn new(source: &'s str) -> Self
+	{
+		let mut tokens = Vec::new();
+		for (n, l) in source.lines().enumerate()
+		{
+			let l = match l.find('#') { Some(p) => &l[.. p], None => l };
+			let mut start = None;
+			for (p, c) in l.char_indices().chain(Some((l.len(), ' ')))
+			{
+				let separator = c == '{' || c == '}' || c == ';';
+				if c.is_whitespace() || separator
+				{
+					if let Some(s) = start.take() { tokens.push((n + 1, &l[s .. p])); }
+					if separator { tokens.push((n + 1, &l[p .. p + 1])); }
+				}
+				else if start.is_none() { start = Some(p); }
+			}
+		}
+		Tokens { tokens: tokens, pos: 0 }
+	}
+	fn peek(&self) -> Option<&'s str> { self.tokens.get(self.pos).map(|&(_, t)| t) }
+	fn line(&self) -> usize { self.tokens.get(self.pos).or(self.tokens.last()).map_or(0, |&(l, _)| l) }
+	fn next(&mut self) -> Result<&'s str, ParseError>
+	{
+		match self.tokens.get(self.pos) { Some(&(_, t)) => { self.pos += 1; Ok(t) }, None => error(self.line(), "unexpected end of file".to_owned()) }
+	}
+	fn expect(&mut self, token: &str) -> Result<(), ParseError>
+	{
+		let line = self.line();
+		let t = self.next()?;
+		if t == token { Ok(()) } else { error(line, format!("expected `{}`, found `{}`", token, t)) }
+	}
+	fn number(&mut self) -> Result<f32, ParseError>
+	{
+		let line = self.line();
+		let t = self.next()?;
+		t.parse().or_else(|_| error(line, format!("expected a number, found `{}`", t)))
+	}
+	fn value(&mut self) -> Result<Value, ParseError>
+	{
+		let line = self.line();
+		if self.peek() == Some("random")
+		{
+			self.pos += 1;
+			let (min, max) = (self.number()?, self.number()?);
+			if min < max { Ok(Value::Random(min, max)) } else { error(line, format!("empty random range {} .. {}", min, max)) }
+		}
+		else { self.number().map(Value::Constant) }
+	}
+	fn fire(&mut self) -> Result<Action, ParseError>
+	{
+		let (mut direction, mut speed, mut motion) = (Direction::Aim(Value::Constant(0.0)), Speed::Absolute(Value::Constant(8.0)), None);
+		loop
+		{
+			match self.peek()
+			{
+				Some("direction") =>
+				{
+					self.pos += 1;
+					let line = self.line();
+					direction = match self.next()?
+					{
+						"aim" => Direction::Aim(self.value()?),
+						"absolute" => Direction::Absolute(self.value()?),
+						"relative" => Direction::Relative(self.value()?),
+						"sequence" => Direction::Sequence(self.value()?),
+						t => return error(line, format!("unknown direction type `{}`", t))
+					};
+				},
+				Some("speed") =>
+				{
+					self.pos += 1;
+					speed = match self.peek()
+					{
+						Some("absolute") => { self.pos += 1; Speed::Absolute(self.value()?) },
+						Some("relative") => { self.pos += 1; Speed::Relative(self.value()?) },
+						Some("sequence") => { self.pos += 1; Speed::Sequence(self.value()?) },
+						_ => Speed::Absolute(self.value()?)
+					};
+				},
+				Some("motion") =>
+				{
+					self.pos += 1;
+					let line = self.line();
+					motion = Some(match self.next()?
+					{
+						"accelerate" => Motion::Accelerating { accel: self.number()?, limit: self.number()? },
+						"curve" => Motion::Curving { angular_velocity: self.number()?.to_radians() },
+						"homing" => Motion::Homing { turn_rate: self.number()?.to_radians(), secs: self.number()? },
+						"wave" => Motion::Wave { amplitude: self.number()?, frequency: self.number()? },
+						"redirect" =>
+						{
+							let delay = self.number()?;
+							let angle = if self.peek() == Some("aim") { self.pos += 1; None } else { Some(self.number()?.to_radians()) };
+							Motion::Redirect { delay: delay, angle: angle, speed: self.number()? }
+						},
+						t => return error(line, format!("unknown motion `{}`", t))
+					});
+				},
+				_ => break
+			}
+		}
+		let line = self.line();
+		let actions = if self.peek() == Some("{") { Some(Arc::new(self.block()?)) } else { None };
+		if actions.is_some() && motion.is_some() { return error(line, "actions of a fired bullet require the linear motion".to_owned()); }
+		Ok(Action::Fire { direction: direction, speed: speed, motion: motion, actions: actions })
+	}
+	fn block(&mut self) -> Result<Vec<Action>, ParseError>
+	{
+		self.expect("{")?;
+		let mut actions = Vec::new();
+		loop
+		{
+			let line = self.line();
+			match self.next()?
+			{
+				"}" => return Ok(actions),
+				";" => (),
+				"fire" => actions.push(self.fire()?),
+				"wait" => actions.push(Action::Wait(self.value()?)),
+				"repeat" =>
+				{
+					let count = if self.peek() == Some("forever") { self.pos += 1; None } else
+					{
+						let n = self.number()?;
+						if n < 0.0 || n.fract() != 0.0 { return error(line, format!("invalid repeat count {}", n)); }
+						Some(n as u32)
+					};
+					let body = self.block()?;
+					if count.is_none() && !waits(&body) { return error(line, "`repeat forever` without any wait".to_owned()); }
+					actions.push(Action::Repeat(count, Arc::new(body)));
+				},
+				t => return error(line, format!("unknown action `{}`", t))
+			}
+		}
+	}
+}
+
+/// Whether the actions wait for a while in every run(actions of fired bullets are run by others)
+fn waits(actions: &[Action]) -> bool
+{
+	actions.iter().any(|a| match a
+	{
+		&Action::Wait(Value::Constant(v)) | &Action::Wait(Value::Random(_, v)) => v > 0.0,
+		&Action::Repeat(count, ref body) => count != Some(0) && waits(body),
+		&Action::Fire { .. } => false
+	})
+}
+
+/// Named patterns parsed from a source
+pub struct PatternLibrary { patterns: HashMap<String, Pattern> }
+impl PatternLibrary
+{
+	pub fn parse(source: &str) -> Result<Self, ParseError>
+	{
+		let mut tokens = Tokens::new(source);
+		let mut patterns = HashMap::new();
+		while tokens.peek().is_some()
+		{
+			tokens.expect("pattern")?;
+			let line = tokens.line();
+			let name = tokens.next()?;
+			if patterns.insert(name.to_owned(), Arc::new(tokens.block()?)).is_some() { return error(line, format!("duplicated pattern `{}`", name)); }
+		}
+		Ok(PatternLibrary { patterns: patterns })
+	}
+	pub fn builtin() -> Self { PatternLibrary::parse(BUILTIN_PATTERNS).expect("Invalid built-in patterns") }
+	pub fn get(&self, name: &str) -> Option<&Pattern> { self.patterns.get(name) }
+}
+
+/// What a runner travels with
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Carrier
+{
+	Enemy,
+	/// the bullet of the fire request(index in the queue) is not allocated yet
+	Request(usize),
+	/// block index of the bullet
+	Bullet(u32)
+}
+
+/// Executes a pattern at a position
+pub struct PatternRunner
+{
+	stack: Vec<(Arc<Vec<Action>>, usize, Option<u32>)>, wait: f32, carrier: Carrier,
+	position: (f32, f32), velocity: Option<(f32, f32)>,
+	direction: f32, prev_direction: f32, prev_speed: f32, speed: f32
+}
+impl PatternRunner
+{
+	/// A runner at an enemy(moved by `move_to`)
+	pub fn new(pattern: &Pattern) -> Self
+	{
+		PatternRunner
+		{
+			stack: vec![(pattern.clone(), 0, Some(1))], wait: 0.0, carrier: Carrier::Enemy, position: (0.0, 0.0), velocity: None,
+			direction: 0.0, prev_direction: 0.0, prev_speed: 0.0, speed: 0.0
+		}
+	}
+	/// A runner travelling along with a fired bullet(requested at `request` in the queue)
+	fn along(actions: &Arc<Vec<Action>>, request: usize, from: (f32, f32), direction: f32, speed: f32) -> Self
+	{
+		let (s, c) = direction.to_radians().sin_cos();
+		PatternRunner
+		{
+			stack: vec![(actions.clone(), 0, Some(1))], wait: 0.0, carrier: Carrier::Request(request),
+			position: from, velocity: Some((s * speed, c * speed)),
+			direction: direction, prev_direction: direction, prev_speed: speed, speed: speed
+		}
+	}
+	pub fn move_to(&mut self, position: (f32, f32)) { self.position = position; }
+	/// Index of the fire request(in the queue passed to `update`) of the bullet which the runner is going to travel with
+	pub fn fire_request(&self) -> Option<usize> { match self.carrier { Carrier::Request(n) => Some(n), _ => None } }
+	/// Binds the runner to the allocated bullet of its fire request
+	pub fn ride(&mut self, bullet_index: u32) { self.carrier = Carrier::Bullet(bullet_index); }
+	/// Block index of the bullet which the runner travels with
+	pub fn bullet(&self) -> Option<u32> { match self.carrier { Carrier::Bullet(n) => Some(n), _ => None } }
+
+	/// Runs the actions for a step: fired bullets are requested to `fires`, runners of them are pushed to `spawns`(to be bound by `ride`).
+	/// Returns false when the pattern is finished(or the runner went out of the screen)
+	pub fn update(&mut self, update_args: &mut GameUpdateArgs, target: (f32, f32),
+		fires: &mut Vec<FireRequest>, spawns: &mut Vec<PatternRunner>) -> bool
+	{
+		if let Some((vx, vy)) = self.velocity
+		{
+			// same integration as `Bullet::update`, so that the runner stays on the bullet
+			self.position.0 += vx * update_args.delta_time;
+			self.position.1 += vy * update_args.delta_time;
+			if self.position.0.abs() * 0.9 > SCREEN_SIZE || !(-1.0 <= self.position.1 && self.position.1 <= SCREEN_SIZE * 2.0 + 1.0) { return false; }
+		}
+		self.wait -= update_args.delta_time;
+		let mut actions_run = 0;
+		while self.wait <= 0.0
+		{
+			actions_run += 1;
+			if actions_run > MAX_ACTIONS_PER_UPDATE { warn!("Bullet pattern runs too many actions without waiting"); self.wait = 0.0; break; }
+			let (block, pc) = match self.stack.last_mut()
+			{
+				None => return false,
+				Some(&mut (ref block, ref mut pc, ref mut count)) => if *pc < block.len() { *pc += 1; (block.clone(), *pc - 1) } else
+				{
+					// end of a block: repeat or return
+					let again = match count.as_mut() { None => true, Some(n) => { *n -= 1; *n > 0 } };
+					if again { *pc = 0; continue; }
+					(block.clone(), block.len())
+				}
+			};
+			if pc >= block.len() { self.stack.pop(); continue; }
+			match &block[pc]
+			{
+				&Action::Wait(ref v) => self.wait += v.eval(update_args),
+				&Action::Repeat(count, ref body) => if count != Some(0) && !body.is_empty() { self.stack.push((body.clone(), 0, count)); },
+				&Action::Fire { direction, speed, motion, ref actions } =>
+				{
+					let direction = match direction
+					{
+						Direction::Aim(v) => (target.0 - self.position.0).atan2(target.1 - self.position.1).to_degrees() + v.eval(update_args),
+						Direction::Absolute(v) => v.eval(update_args),
+						Direction::Relative(v) => self.direction + v.eval(update_args),
+						Direction::Sequence(v) => self.prev_direction + v.eval(update_args)
+					};
+					let speed = match speed
+					{
+						Speed::Absolute(v) => v.eval(update_args),
+						Speed::Relative(v) => self.speed + v.eval(update_args),
+						Speed::Sequence(v) => self.prev_speed + v.eval(update_args)
+					};
+					self.prev_direction = direction; self.prev_speed = speed;
+					fires.push(FireRequest::new(vec![([self.position.0, self.position.1, 0.0, 0.0], direction.to_radians(), speed)], motion));
+					if let &Some(ref a) = actions { spawns.push(PatternRunner::along(a, fires.len() - 1, self.position, direction, speed)); }
+				}
+			}
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use seeded_randomizer;
+
+	fn fired(requests: &[FireRequest]) -> Vec<([f32; 4], f32, f32)>
+	{
+		requests.iter().flat_map(|r| r.bullets().0.iter().cloned()).collect()
+	}
+	fn run(pattern: &Pattern, steps: usize) -> (Vec<([f32; 4], f32, f32)>, Vec<PatternRunner>)
+	{
+		let mut rng = seeded_randomizer(1);
+		let mut args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng };
+		let (mut fires, mut spawns) = (Vec::new(), Vec::new());
+		let mut runner = PatternRunner::new(pattern);
+		runner.move_to((0.0, 10.0));
+		for _ in 0 .. steps { runner.update(&mut args, (10.0, 20.0), &mut fires, &mut spawns); }
+		(fired(&fires), spawns)
+	}
+
+	#[test] fn parses_the_builtin_patterns()
+	{
+		let library = PatternLibrary::builtin();
+		assert!(library.get("zako").is_some());
+		assert_eq!(**library.get("zako").unwrap(), vec![Action::Repeat(None, Arc::new(vec![
+			Action::Fire { direction: Direction::Absolute(Value::Random(0.0, 360.0)), speed: Speed::Absolute(Value::Random(6.0, 12.0)), motion: None, actions: None },
+			Action::Wait(Value::Constant(1.0))
+		]))]);
+	}
+	#[test] fn runners_can_cross_threads()
+	{
+		fn send<T: Send>() {}
+		send::<PatternRunner>();
+	}
+	#[test] fn reports_errors_with_lines()
+	{
+		assert_eq!(PatternLibrary::parse("pattern a\n{\n\tfire direction sideways 0\n}").err().unwrap().line, 3);
+		assert_eq!(PatternLibrary::parse("pattern a { wait random 2 1 }").err().unwrap().line, 1);
+		assert!(PatternLibrary::parse("pattern a { repeat 2 { wait 1 }").is_err());
+		// loops which never wait
+		assert_eq!(PatternLibrary::parse("pattern a {\n\trepeat forever { fire wait 0 }\n}").err().unwrap().line, 2);
+		assert!(PatternLibrary::parse("pattern a { repeat forever { repeat 0 { wait 1 } fire { wait 1 } } }").is_err());
+		assert!(PatternLibrary::parse("pattern a { fire { repeat forever { fire } } }").is_err());
+		assert!(PatternLibrary::parse("pattern a { repeat forever { repeat 3 { fire wait random 0 1 } } }").is_ok());
+	}
+	#[test] fn repeats_sequences_and_waits()
+	{
+		let library = PatternLibrary::parse("pattern ring { repeat 2 { repeat 4 { fire direction sequence 90 speed sequence 1 } wait 0.5 } }").unwrap();
+		let (fires, _) = run(library.get("ring").unwrap(), 1);
+		assert_eq!(fires.len(), 4);
+		let (fires, _) = run(library.get("ring").unwrap(), 60);
+		assert_eq!(fires.len(), 8);
+		assert_eq!(fires.iter().map(|f| f.2).collect::<Vec<_>>(), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+		assert!((fires[1].1 - 180.0f32.to_radians()).abs() < 1.0e-4);
+	}
+	#[test] fn aims_at_the_target()
+	{
+		let library = PatternLibrary::parse("pattern a { fire direction aim 0 speed 10 }").unwrap();
+		let (fires, _) = run(library.get("a").unwrap(), 1);
+		// from (0, 10) to (10, 20)
+		assert!((fires[0].1 - 45.0f32.to_radians()).abs() < 1.0e-4);
+	}
+	#[test] fn sub_bullets_fire_along_the_parent()
+	{
+		let library = PatternLibrary::parse("pattern a { fire direction absolute 0 speed 6 { wait 0.5 repeat 3 { fire direction relative 90 } } }").unwrap();
+		let (fires, mut spawns) = run(library.get("a").unwrap(), 1);
+		assert_eq!((fires.len(), spawns.len()), (1, 1));
+
+		let mut rng = seeded_randomizer(1);
+		let mut args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng };
+		let (mut fires, mut nested) = (Vec::new(), Vec::new());
+		let mut child = spawns.pop().unwrap();
+		let mut steps = 1;
+		while child.update(&mut args, (0.0, 0.0), &mut fires, &mut nested) { steps += 1; }
+		// finished after firing, 0.5 secs later
+		assert!(30 <= steps && steps <= 31);
+		let fires = fired(&fires);
+		assert_eq!(fires.len(), 3);
+		// travelled downwards, and fired sideways
+		assert!((fires[0].0[1] - (10.0 + 6.0 * steps as f32 * FIXED_STEP)).abs() < 1.0e-3);
+		assert!((fires[0].1 - 90.0f32.to_radians()).abs() < 1.0e-4);
+	}
+	#[test] fn fires_with_motions()
+	{
+		let library = PatternLibrary::parse("pattern a { fire speed 4 motion redirect 0.5 aim 10; fire motion curve 90 }").unwrap();
+		let mut rng = seeded_randomizer(1);
+		let mut args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng };
+		let mut fires = Vec::new();
+		PatternRunner::new(library.get("a").unwrap()).update(&mut args, (0.0, 0.0), &mut fires, &mut Vec::new());
+		assert_eq!(fires[0].bullets().1, Some(Motion::Redirect { delay: 0.5, angle: None, speed: 10.0 }));
+		assert_eq!(fires[0].bullets().0[0].2, 4.0);
+		match &fires[1] { &FireRequest::Curves(_, w) => assert!((w - 90.0f32.to_radians()).abs() < 1.0e-6), _ => panic!("not a curve") }
+
+		assert!(PatternLibrary::parse("pattern a { fire motion wave 1 2 { wait 1 } }").is_err());
+		assert!(PatternLibrary::parse("pattern a { fire motion spiral 1 }").is_err());
+	}
+}
@@ -1,33 +1,24 @@
 
 use utils::*;
 use constants::*;
-use structures::*;
-use nalgebra::*;
-use interlude::*;
-use rand;
-use rand::distributions::*;
 use super::bullet::*;
+use super::bullet_pattern::{Pattern, PatternRunner};
 use std::rc::*;
 use utils;
 use {GameTime, GameUpdateArgs};
 
-fn store_quaternion(to: &mut CVector4, q: &Quaternion<f32>)
+pub struct EnemyDatastore
 {
-	*to = [q.i, q.j, q.k, q.w];
-}
-
-pub struct EnemyDatastore<'a>
-{
-	instance_memory_ref: &'a mut [u32; MAX_ENEMY_COUNT],
+	instance_memory: [u32; MAX_ENEMY_COUNT],
 	memory_block_manager: MemoryBlockManager
 }
-impl <'a> EnemyDatastore<'a>
+impl EnemyDatastore
 {
-	pub fn new(im_ref: &'a mut [u32; MAX_ENEMY_COUNT]) -> Self
+	pub fn new() -> Self
 	{
 		EnemyDatastore
 		{
-			instance_memory_ref: im_ref, memory_block_manager: MemoryBlockManager::new(MAX_ENEMY_COUNT as u32)
+			instance_memory: [0; MAX_ENEMY_COUNT], memory_block_manager: MemoryBlockManager::new(MAX_ENEMY_COUNT as u32)
 		}
 	}
 	pub fn allocate_block(&mut self) -> Option<u32>
@@ -43,66 +34,50 @@ impl <'a> EnemyDatastore<'a>
 	}
 	fn enable_instance(&mut self, index: u32)
 	{
-		self.instance_memory_ref[index as usize] = 1;
+		self.instance_memory[index as usize] = 1;
 	}
 	fn disable_instance(&mut self, index: u32)
 	{
-		self.instance_memory_ref[index as usize] = 0;
+		self.instance_memory[index as usize] = 0;
 	}
-}
-
-pub enum Continuous
-{
-	Cont(f32, Box<Fn((f32, f32), &mut Vec<FireRequest>) -> Continuous>), Term
+	pub fn instance_data(&self) -> &[u32; MAX_ENEMY_COUNT] { &self.instance_memory }
 }
 
 pub struct SpawnGroupRef { livings: Weak<spawn_group::EntityLivings>, local_index: usize }
-pub enum Enemy<'a>
+/// Rotations of the rezonators(in radians)
+#[derive(Clone, Copy)]
+pub struct RezonatorRotation(pub f32, pub f32);
+pub enum Enemy
 {
 	Free, Entity
 	{
-		block_index: u32, spawngroup: SpawnGroupRef, uniform_ref: &'a mut CharacterLocation, rezonator_iref: &'a mut CVector4,
-		left: f32, living_secs: GameTime, rezonator_left: u32, next: Continuous, next_raised: f32
+		block_index: u32, spawngroup: SpawnGroupRef,
+		left: f32, top: f32, prev_top: f32, living_secs: GameTime, rezonator_left: u32, rezonator_rot: RezonatorRotation,
+		pattern: PatternRunner
 	}, Garbage(u32)
 }
-unsafe impl<'a> Send for Enemy<'a> {}
-impl<'a> Enemy<'a>
+impl Enemy
 {
-	pub fn init(init_left: f32, block_index: u32, livings: &Rc<spawn_group::EntityLivings>, sglx: usize,
-		uref: &'a mut CharacterLocation, iref_rez: &'a mut CVector4) -> Self
+	pub fn init(init_left: f32, block_index: u32, livings: &Rc<spawn_group::EntityLivings>, sglx: usize, pattern: &Pattern) -> Self
 	{
-		uref.center_tf = [init_left, 0.0, 0.0, 0.0];
-		store_quaternion(&mut uref.rotq[0], UnitQuaternion::new(Vector3::new(0.0, 0.0, 0.0)).quaternion());
-		store_quaternion(&mut uref.rotq[1], UnitQuaternion::new(Vector3::new(0.0, 0.0, 0.0)).quaternion());
-		*iref_rez = [3.0, 0.0, 0.0, 0.0];
-
-		// let (frsender, uref_th) = unsafe { (fr_sender.clone(), CharacterLocationUnsafePtr(std::mem::transmute(uref))) };
 		Enemy::Entity
 		{
-			block_index: block_index, spawngroup: SpawnGroupRef { livings: Rc::downgrade(livings), local_index: sglx }, uniform_ref: uref, rezonator_iref: iref_rez,
-			left: init_left, living_secs: 0.0, rezonator_left: 3, next: Continuous::Cont(0.0, Box::new(|pos, fq|
-			{
-				fn sec1(pos: (f32, f32), fq: &mut Vec<FireRequest>) -> Continuous
-				{
-					let mut r = rand::thread_rng();
-					let ra = rand::distributions::Range::new(0.0, 360.0);
-					let rs = rand::distributions::Range::new(6.0, 12.0);
-					fq.push(FireRequest::Linears(vec![([pos.0, pos.1, 0.0, 0.0], ra.ind_sample(&mut r), rs.ind_sample(&mut r))]));
-					Continuous::Cont(1.0, Box::new(sec1))
-				}
-				sec1(pos, fq)
-			})), next_raised: 0.0
+			block_index: block_index, spawngroup: SpawnGroupRef { livings: Rc::downgrade(livings), local_index: sglx },
+			left: init_left, top: 0.0, prev_top: 0.0, living_secs: 0.0, rezonator_left: 3, rezonator_rot: RezonatorRotation(0.0, 0.0),
+			pattern: PatternRunner::new(pattern)
 		}
 	}
-	pub fn update(&mut self, update_args: &GameUpdateArgs, frequest_queue: &mut Vec<FireRequest>) -> Option<(f32, f32)>
+	/// Moves and fires(by the bullet pattern, aiming at `target`). Bullets of the pattern which run actions are pushed to `runner_spawns`
+	pub fn update(&mut self, update_args: &mut GameUpdateArgs, target: (f32, f32), frequest_queue: &mut Vec<FireRequest>,
+		runner_spawns: &mut Vec<PatternRunner>) -> Option<(f32, f32)>
 	{
 		// update values
 		let (gb_index, np) = match self
 		{
 			&mut Enemy::Entity
 			{
-				block_index, ref spawngroup, ref mut uniform_ref, ref mut rezonator_iref, ref mut living_secs,
-				rezonator_left, ref mut next, ref mut next_raised, ..
+				block_index, ref spawngroup, left, ref mut top, ref mut prev_top, ref mut living_secs,
+				ref mut rezonator_rot, ref mut pattern, ..
 			} => {
 				let current_y = if *living_secs < 0.875f32
 				{
@@ -114,7 +89,6 @@ impl<'a> Enemy<'a>
 				};
 				if current_y >= SCREEN_SIZE * 2.0 + 5.0
 				{
-					rezonator_iref[0] = 0.0;
 					if let Some(lv) = spawngroup.livings.upgrade()
 					{
 						// println!("dead... {}", spawngroup.local_index);
@@ -124,26 +98,15 @@ impl<'a> Enemy<'a>
 				}
 				else
 				{
-					uniform_ref.center_tf[1] = current_y;
-					store_quaternion(&mut uniform_ref.rotq[0], UnitQuaternion::new(Vector3::new(-1.0, 0.0, 0.75).normalize() * (260.0 * *living_secs).to_radians()).quaternion());
-					store_quaternion(&mut uniform_ref.rotq[1], UnitQuaternion::new(Vector3::new(1.0, -1.0, 0.5).normalize() * (-260.0 * *living_secs + 13.0).to_radians()).quaternion());
-					rezonator_iref[0] = rezonator_left as f32;
-					rezonator_iref[1] -= 130.0f32.to_radians() * update_args.delta_time;
-					rezonator_iref[2] += 220.0f32.to_radians() * update_args.delta_time;
+					*prev_top = *top;
+					*top = current_y;
+					rezonator_rot.0 -= 130.0f32.to_radians() * update_args.delta_time;
+					rezonator_rot.1 += 220.0f32.to_radians() * update_args.delta_time;
 					*living_secs += update_args.delta_time;
-					let newpos = (uniform_ref.center_tf[0], uniform_ref.center_tf[1]);
+					let newpos = (left, current_y);
 
-					let nx = if let &mut Continuous::Cont(n, ref f) = next
-					{
-						if *living_secs - *next_raised >= n
-						{
-							*next_raised = *living_secs;
-							Some(f(newpos, frequest_queue))
-						}
-						else { None }
-					}
-					else { None };
-					if let Some(n) = nx { *next = n; }
+					pattern.move_to(newpos);
+					pattern.update(update_args, target, frequest_queue, runner_spawns);
 					(None, Some(newpos))
 				}
 			},
@@ -159,6 +122,41 @@ impl<'a> Enemy<'a>
 	{
 		match self { &Enemy::Garbage(_) => true, _ => false }
 	}
+	pub fn position(&self) -> Option<(f32, f32)>
+	{
+		match self { &Enemy::Entity { left, top, .. } => Some((left, top)), _ => None }
+	}
+	/// Takes a hit: a rezonator is lost, and the enemy is destroyed when none is left(returns true).
+	/// A destroyed enemy dies in its spawn group, and becomes a garbage(its block is freed by the owner)
+	pub fn damage(&mut self) -> bool
+	{
+		let destroyed = match self
+		{
+			&mut Enemy::Entity { block_index, ref spawngroup, ref mut rezonator_left, .. } =>
+			{
+				*rezonator_left = rezonator_left.saturating_sub(1);
+				if *rezonator_left == 0
+				{
+					if let Some(lv) = spawngroup.livings.upgrade() { lv.zako_mut().destroy(spawngroup.local_index); }
+					Some(block_index)
+				}
+				else { None }
+			},
+			_ => None
+		};
+		if let Some(b) = destroyed { *self = Enemy::Garbage(b); true } else { false }
+	}
+	/// (block_index, center, living_secs, rezonators left, rezonator rotation) of a living enemy, for rendering
+	/// (the center is interpolated between the previous and the current step)
+	pub fn render_params(&self, alpha: f32) -> Option<(u32, (f32, f32), GameTime, u32, RezonatorRotation)>
+	{
+		match self
+		{
+			&Enemy::Entity { block_index, left, top, prev_top, living_secs, rezonator_left, rezonator_rot, .. } =>
+				Some((block_index, (left, utils::lerp(prev_top, top, alpha)), living_secs, rezonator_left, rezonator_rot)),
+			_ => None
+		}
+	}
 }
 
 /// Enemy Spawn Group
@@ -247,13 +245,20 @@ pub mod spawn_group
 			if let Term = self.1 { false } else { true }
 		}
 	}
-	pub enum LivingState { Left, Dead }
+	/// Dead: went out of the screen, Destroyed: shot down by the player
+	pub enum LivingState { Left, Dead, Destroyed }
 	pub struct ClassifiedLivingStates { list: Vec<(u32, LivingState)>, lefts: usize }
 	impl ClassifiedLivingStates
 	{
 		pub fn new() -> Self { ClassifiedLivingStates { list: Vec::new(), lefts: 0 } }
 		pub fn newlife(&mut self, index: u32) { self.list.push((index, LivingState::Left)); self.lefts += 1; }
 		pub fn die(&mut self, local_index: usize) { self.list[local_index].1 = LivingState::Dead; self.lefts -= 1; }
+		pub fn destroy(&mut self, local_index: usize) { self.die(local_index); self.list[local_index].1 = LivingState::Destroyed; }
+		/// All members were shot down
+		pub fn is_annihilated(&self) -> bool
+		{
+			!self.list.is_empty() && self.list.iter().all(|&(_, ref s)| match s { &LivingState::Destroyed => true, _ => false })
+		}
 		pub fn nextlife(&self) -> usize { self.list.len() }
 	}
 	pub struct EntityLivings
@@ -289,6 +294,7 @@ pub mod spawn_group
 			}*/
 			cont
 		}
+		pub fn is_annihilated(&self) -> bool { self.livings.zako.borrow().is_annihilated() }
 	}
 }
 pub use self::spawn_group::EnemyGroup;
@@ -353,14 +359,17 @@ impl EnemySquads
 		}
 		self.left += 1;
 	}
-	fn update_all(&mut self, update_args: &mut GameUpdateArgs, appear: &mut FnMut(f32, f32, &Rc<spawn_group::EntityLivings>, usize) -> Option<u32>)
+	/// Returns the count of squads cleared(finished with all members shot down)
+	fn update_all(&mut self, update_args: &mut GameUpdateArgs, appear: &mut FnMut(f32, f32, &Rc<spawn_group::EntityLivings>, usize) -> Option<u32>) -> u32
 	{
+		let mut cleared = 0;
 		for (n, gi) in self.objects.iter_mut().enumerate()
 		{
 			let emptiness = if let &mut Some(ref mut g) = gi
 			{
 				if !g.update(update_args, appear)
 				{
+					if g.is_annihilated() { cleared += 1; }
 					self.freelist.free(n as u32); true
 				}
 				else { false }
@@ -368,6 +377,7 @@ impl EnemySquads
 			else { false };
 			if emptiness { *gi = None; self.left -= 1; }
 		}
+		cleared
 	}
 }
 /// Top of all enemy squads
@@ -402,10 +412,11 @@ impl EnemyManager
 			squads: EnemySquads::new(), engine: ManagerEngine::begin(executions)
 		}
 	}
-	pub fn update<AppearFn>(&mut self, update_args: &mut GameUpdateArgs, mut appear: AppearFn)
+	/// Returns the count of squads cleared in this update
+	pub fn update<AppearFn>(&mut self, update_args: &mut GameUpdateArgs, mut appear: AppearFn) -> u32
 		where AppearFn: FnMut(f32, f32, &Rc<spawn_group::EntityLivings>, usize) -> Option<u32>
 	{
 		self.engine.update(update_args, &mut self.squads);
-		self.squads.update_all(update_args, &mut appear);
+		self.squads.update_all(update_args, &mut appear)
 	}
 }
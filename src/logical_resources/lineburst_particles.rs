@@ -1,32 +1,41 @@
 
+use std;
 use rand;
+use GameRandomizer;
 use utils::MemoryBlockManager;
 use constants::*;
-use structures::*;
 use rand::distributions::*;
 use std::collections::LinkedList;
 
-pub struct LineBurstParticles<'a>
+#[derive(Clone, Copy)]
+pub struct ParticleGroup { pub count: u32, pub start_point: [f32; 2] }
+#[derive(Clone, Copy)]
+pub struct Particle
+{
+	pub length: f32, pub lifestart: f32, pub lifemult: f32,
+	pub sincos: [f32; 2]
+}
+
+pub struct LineBurstParticles
 {
 	memory: MemoryBlockManager,
-	iref: &'a mut [LineBurstParticleGroup; MAX_LBPARTICLE_GROUPS],
-	uref: &'a mut [LineBurstParticle; MAX_LBPARTICLES],
-	estimated_lifetimes: LinkedList<(usize, f32)>,
-	randomizer: rand::ThreadRng
+	groups: [ParticleGroup; MAX_LBPARTICLE_GROUPS],
+	particles: Vec<Particle>,
+	estimated_lifetimes: LinkedList<(usize, f32)>
 }
-impl<'a> LineBurstParticles<'a>
+impl LineBurstParticles
 {
-	pub fn new(iref: &'a mut [LineBurstParticleGroup; MAX_LBPARTICLE_GROUPS], uref: &'a mut [LineBurstParticle; MAX_LBPARTICLES]) -> Self
+	pub fn new() -> Self
 	{
 		LineBurstParticles
 		{
 			memory: MemoryBlockManager::new(MAX_LBPARTICLE_GROUPS as u32),
-			iref: iref, uref: uref,
-			estimated_lifetimes: LinkedList::new(),
-			randomizer: rand::thread_rng()
+			groups: [ParticleGroup { count: 0, start_point: [0.0; 2] }; MAX_LBPARTICLE_GROUPS],
+			particles: vec![Particle { length: 0.0, lifestart: 0.0, lifemult: 0.0, sincos: [0.0; 2] }; MAX_LBPARTICLES],
+			estimated_lifetimes: LinkedList::new()
 		}
 	}
-	pub fn spawn(&mut self, count: u32, x: f32, y: f32, lifestart_sec: f32)
+	pub fn spawn(&mut self, randomizer: &mut GameRandomizer, count: u32, x: f32, y: f32, lifestart_sec: f32)
 	{
 		let angle_distr = rand::distributions::Range::new(0.0f32, 360.0);
 		let length_range = rand::distributions::Range::new(0.25, 2.0);
@@ -34,32 +43,37 @@ impl<'a> LineBurstParticles<'a>
 		let memindex = self.memory.allocate();
 		if let Some(mindex) = memindex
 		{
-			self.iref[mindex as usize].count = count;
-			self.iref[mindex as usize].start_point = [x, y];
+			self.groups[mindex as usize].count = count;
+			self.groups[mindex as usize].start_point = [x, y];
 			let mut estimated_lifetime = 0.0f32;
 			for n in 0 .. count
 			{
-				let (s, c) = angle_distr.ind_sample(&mut self.randomizer).to_radians().sin_cos();
-				let length = length_range.ind_sample(&mut self.randomizer);
-				let lifetime_mult = lifetime_mult_range.ind_sample(&mut self.randomizer);
+				let (s, c) = angle_distr.ind_sample(randomizer).to_radians().sin_cos();
+				let length = length_range.ind_sample(randomizer);
+				let lifetime_mult = lifetime_mult_range.ind_sample(randomizer);
 
-				self.uref[mindex as usize * MAX_LBPARTICLES_PER_GROUP + n as usize].sincos_xx = [s, c, 0.0, 0.0];
-				self.uref[mindex as usize * MAX_LBPARTICLES_PER_GROUP + n as usize].length_colrel_lifestart_lifemult = [length, 0.0, lifestart_sec, lifetime_mult];
+				self.particles[mindex as usize * MAX_LBPARTICLES_PER_GROUP + n as usize] = Particle
+				{
+					length: length, lifestart: lifestart_sec, lifemult: lifetime_mult, sincos: [s, c]
+				};
 				estimated_lifetime = estimated_lifetime.max(lifetime_mult.recip());
 			}
 			self.estimated_lifetimes.push_front((mindex as usize, lifestart_sec + estimated_lifetime));
 		}
 		else { warn!("Memory for Line Burst Particles is Full!!"); }
 	}
-	pub fn garbage_collect(mut self, current_time: f32) -> Self
+	pub fn garbage_collect(&mut self, current_time: f32)
 	{
-		let (collected, survive): (LinkedList<_>, LinkedList<_>) = self.estimated_lifetimes.into_iter().partition(|&(_, n)| current_time >= n);
+		let lifetimes = std::mem::replace(&mut self.estimated_lifetimes, LinkedList::new());
+		let (collected, survive): (LinkedList<_>, LinkedList<_>) = lifetimes.into_iter().partition(|&(_, n)| current_time >= n);
 		for (i, _) in collected.into_iter()
 		{
 			self.memory.free(i as u32);
-			self.iref[i].count = 0;
+			self.groups[i].count = 0;
 		}
 		self.estimated_lifetimes = survive;
-		self
 	}
+
+	pub fn groups(&self) -> &[ParticleGroup; MAX_LBPARTICLE_GROUPS] { &self.groups }
+	pub fn particles(&self) -> &[Particle] { &self.particles }
 }
@@ -8,3 +8,9 @@ pub mod player;
 pub use self::player::{Player, PlayerBullet};
 pub mod bullet;
 pub use self::bullet::*;
+pub mod bullet_pattern;
+pub use self::bullet_pattern::PatternLibrary;
+pub mod score;
+pub use self::score::Score;
+pub mod world;
+pub use self::world::{World, FrameInput};
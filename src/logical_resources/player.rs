@@ -1,106 +1,111 @@
 // Player Logics
 
-use nalgebra::*;
-use LogicalInputTypes;
-use interlude::*;
 use std;
 use utils::quadtree::*;
 use constants::*;
+use utils::lerp;
 use GameUpdateArgs;
+use super::FrameInput;
 
-pub struct Player<'a>
+pub struct Player
 {
-	uniform_memory: &'a mut CVector4, instance_memory: &'a mut [CVector4; 2],
-	living_secs: f32
+	left: f32, top: f32, prev_left: f32, prev_top: f32,
+	living_secs: f32,
+	lives: u32, invincible_secs: f32, focused: bool
 }
-impl<'a> Player<'a>
+impl Player
 {
-	pub fn new(uniform_ref: &'a mut CVector4, instance_ref: &'a mut [CVector4; 2]) -> Self
+	pub fn new() -> Self
 	{
-		let u_quaternion = UnitQuaternion::new(Vector3::new(0.0f32, 0.0f32, 0.0f32));
-		let quaternion_ref = u_quaternion.quaternion();
-
-		instance_ref[0] = [quaternion_ref.i, quaternion_ref.j, quaternion_ref.k, quaternion_ref.w];
-		instance_ref[1] = [quaternion_ref.i, quaternion_ref.j, quaternion_ref.k, quaternion_ref.w];
-		*uniform_ref = [0.0f32, 38.0f32, 0.0f32, 0.0f32];
-
 		Player
 		{
-			uniform_memory: uniform_ref, instance_memory: instance_ref, living_secs: 0.0f32
+			left: 0.0f32, top: 38.0f32, prev_left: 0.0f32, prev_top: 38.0f32, living_secs: 0.0f32,
+			lives: PLAYER_INITIAL_LIVES, invincible_secs: 0.0, focused: false
 		}
 	}
-	pub fn update(&mut self, update_args: &GameUpdateArgs, input: &Input<LogicalInputTypes>, movescale: f32) -> u32
+	pub fn update(&mut self, update_args: &GameUpdateArgs, input: &FrameInput, movescale: f32) -> u32
 	{
-		let u_quaternions = [
-			UnitQuaternion::new(Vector3::new(-1.0f32, 0.0f32, 0.75f32).normalize() * (260.0f32 * self.living_secs as f32).to_radians()),
-			UnitQuaternion::new(Vector3::new(1.0f32, -1.0f32, 0.5f32).normalize() * (-260.0f32 * self.living_secs as f32 + 13.0f32).to_radians())
-		];
-		let mut quaternions = u_quaternions.iter().map(|x| x.quaternion()).map(|q| [q.i, q.j, q.k, q.w]);
 		self.living_secs += update_args.delta_time;
+		self.invincible_secs = (self.invincible_secs - update_args.delta_time).max(0.0);
+		self.prev_left = self.left; self.prev_top = self.top;
+		if self.is_dead() { return bithash(self.left, self.top) as u32; }
+		self.focused = input.slowdown > 0.0;
 
-		self.uniform_memory[0] =
-			(self.uniform_memory[0] + input[LogicalInputTypes::Horizontal] * 40.0f32 * movescale * update_args.delta_time).max(-PLAYER_LIMIT).min(PLAYER_LIMIT);
-		self.uniform_memory[1] =
-			(self.uniform_memory[1] + input[LogicalInputTypes::Vertical] * 40.0f32 * movescale * update_args.delta_time).max(PLAYER_SIZE).min(PLAYER_VLIMIT);
+		self.left = (self.left + input.horizontal * 40.0f32 * movescale * update_args.delta_time).max(-PLAYER_LIMIT).min(PLAYER_LIMIT);
+		self.top = (self.top + input.vertical * 40.0f32 * movescale * update_args.delta_time).max(PLAYER_SIZE).min(PLAYER_VLIMIT);
 
-		self.instance_memory[0] = quaternions.next().unwrap();
-		self.instance_memory[1] = quaternions.next().unwrap();
-
-		bithash(self.uniform_memory[0], self.uniform_memory[1]) as u32
+		bithash(self.left, self.top) as u32
 	}
 
-	pub fn left(&self) -> f32 { self.uniform_memory[0] }
-	pub fn top(&self) -> f32 { self.uniform_memory[1] }
+	pub fn left(&self) -> f32 { self.left }
+	pub fn top(&self) -> f32 { self.top }
+	pub fn living_secs(&self) -> f32 { self.living_secs }
+	pub fn lives(&self) -> u32 { self.lives }
+	pub fn is_dead(&self) -> bool { self.lives == 0 }
+	pub fn is_invincible(&self) -> bool { self.invincible_secs > 0.0 }
+	/// Radius of the hitbox(smaller while focusing)
+	pub fn hit_radius(&self) -> f32 { if self.focused { PLAYER_FOCUS_HIT_RADIUS } else { PLAYER_HIT_RADIUS } }
+	/// Takes a hit: returns true if a life is lost(nothing happens while invincible)
+	pub fn hit(&mut self) -> bool
+	{
+		if self.is_invincible() || self.is_dead() { return false; }
+		self.lives -= 1;
+		self.invincible_secs = PLAYER_INVINCIBLE_SECS;
+		true
+	}
+	/// Whether the player is drawn(blinks while invincible)
+	pub fn is_visible(&self) -> bool
+	{
+		!self.is_dead() && (!self.is_invincible() || (self.invincible_secs * 10.0) as u32 % 2 == 0)
+	}
+	/// Position interpolated between the previous and the current step
+	pub fn render_position(&self, alpha: f32) -> (f32, f32)
+	{
+		(lerp(self.prev_left, self.left, alpha), lerp(self.prev_top, self.top, alpha))
+	}
 }
 
-pub enum PlayerBullet<'a>
+pub enum PlayerBullet
 {
-	Free, Entity { block_index: u32, offs_sincos_ref: &'a mut CVector4 }, Garbage(u32)
+	Free, Entity { block_index: u32, offs_sincos: [f32; 4], prev_offset: [f32; 2] }, Garbage(u32)
 }
-impl<'a> PlayerBullet<'a>
+impl PlayerBullet
 {
-	pub fn init(init_left: f32, init_top: f32, init_angle: f32, block_index: u32, offs_sincos_ref: &'a mut CVector4) -> Self
+	pub fn init(init_left: f32, init_top: f32, init_angle: f32, block_index: u32) -> Self
 	{
-		offs_sincos_ref[0] = init_left;
-		offs_sincos_ref[1] = init_top;
 		let (s, c) = init_angle.to_radians().sin_cos();
-		offs_sincos_ref[2] = s; offs_sincos_ref[3] = c;
 
-		PlayerBullet::Entity { block_index: block_index, offs_sincos_ref: offs_sincos_ref }
+		PlayerBullet::Entity { block_index: block_index, offs_sincos: [init_left, init_top, s, c], prev_offset: [init_left, init_top] }
 	}
 	pub fn update(&mut self, update_args: &GameUpdateArgs)
 	{
 		let died_index = match self
 		{
-			&mut PlayerBullet::Entity { block_index: block, offs_sincos_ref: ref mut offs_sincos } =>
+			&mut PlayerBullet::Entity { block_index: block, ref mut offs_sincos, ref mut prev_offset } =>
 			{
+				*prev_offset = [offs_sincos[0], offs_sincos[1]];
 				offs_sincos[0] += offs_sincos[2] * 8.0 * 14.0 * update_args.delta_time;
 				offs_sincos[1] -= offs_sincos[3] * 8.0 * 14.0 * update_args.delta_time;
 				if offs_sincos[0].abs() > SCREEN_SIZE || !(0.0 <= offs_sincos[1] && offs_sincos[1] <= SCREEN_SIZE * 2.0)
 				{
-					offs_sincos[0] = std::f32::MAX;
-					offs_sincos[1] = std::f32::MAX;
 					Some(block)
 				}
 				else { None }
 			}, _ => None
 		};
-		
+
 		if let Some(bindex) = died_index { *self = PlayerBullet::Garbage(bindex); }
 	}
 	pub fn crash(&mut self, t_left: f32, t_top: f32) -> Option<(f32, f32)>
 	{
 		let died_index = match self
 		{
-			&mut PlayerBullet::Entity { block_index: block, offs_sincos_ref: ref mut offs_sincos } =>
+			&mut PlayerBullet::Entity { block_index: block, ref offs_sincos, .. } =>
 			{
 				let (xd, yd) = ((offs_sincos[0] - t_left).abs(), (offs_sincos[1] - t_top).abs());
-				if 0.0 <= xd && xd <= 1.25 + 0.25 && 0.0 <= yd && yd <= 1.25 + 0.25
+				if 0.0 <= xd && xd <= ENEMY_SIZE + PLAYER_BULLET_SIZE && 0.0 <= yd && yd <= ENEMY_SIZE + PLAYER_BULLET_SIZE
 				{
-					let rvalue = (block, offs_sincos[0], offs_sincos[1]);
-					offs_sincos[0] = std::f32::MAX;
-					offs_sincos[1] = std::f32::MAX;
-					Some(rvalue)
+					Some((block, offs_sincos[0], offs_sincos[1]))
 				}
 				else { None }
 			},
@@ -110,4 +115,19 @@ impl<'a> PlayerBullet<'a>
 		if let Some((bindex, psx, psy)) = died_index { *self = PlayerBullet::Garbage(bindex); Some((psx, psy)) } else { None }
 	}
 	pub fn is_garbage(&self) -> bool { match self { &PlayerBullet::Garbage(_) => true, _ => false } }
+	pub fn position(&self) -> Option<(f32, f32)>
+	{
+		match self { &PlayerBullet::Entity { offs_sincos, .. } => Some((offs_sincos[0], offs_sincos[1])), _ => None }
+	}
+	/// Offset(interpolated between the previous and the current step) and direction(sin, cos) for rendering:
+	/// dead bullets are placed out of the screen
+	pub fn render_offset_sincos(&self, alpha: f32) -> [f32; 4]
+	{
+		match self
+		{
+			&PlayerBullet::Entity { offs_sincos, prev_offset, .. } =>
+				[lerp(prev_offset[0], offs_sincos[0], alpha), lerp(prev_offset[1], offs_sincos[1], alpha), offs_sincos[2], offs_sincos[3]],
+			_ => [std::f32::MAX, std::f32::MAX, 0.0, 0.0]
+		}
+	}
 }
@@ -0,0 +1,74 @@
+// Scoring: points for kills and cleared squads, grazes, and a combo multiplier decaying over time
+
+use constants::*;
+use GameTime;
+
+pub struct Score
+{
+	points: u32, kills: u32, squads_cleared: u32, grazes: u32,
+	multiplier: f32, secs_from_last_gain: GameTime
+}
+impl Score
+{
+	pub fn new() -> Self
+	{
+		Score { points: 0, kills: 0, squads_cleared: 0, grazes: 0, multiplier: 1.0, secs_from_last_gain: 0.0 }
+	}
+	/// Decays the multiplier after a while without kills or grazes
+	pub fn update(&mut self, delta_time: GameTime)
+	{
+		self.secs_from_last_gain += delta_time;
+		if self.secs_from_last_gain > COMBO_HOLD_SECS
+		{
+			self.multiplier = (self.multiplier - COMBO_DECAY_PER_SEC * delta_time).max(1.0);
+		}
+	}
+
+	fn gain(&mut self, base_points: u32, multiplier_gain: f32)
+	{
+		self.points = self.points.saturating_add((base_points as f32 * self.multiplier) as u32);
+		self.multiplier = (self.multiplier + multiplier_gain).min(MAX_COMBO_MULTIPLIER);
+		self.secs_from_last_gain = 0.0;
+	}
+	pub fn kill(&mut self) { self.kills += 1; self.gain(KILL_POINTS, KILL_COMBO_GAIN); }
+	pub fn clear_squad(&mut self) { self.squads_cleared += 1; self.gain(SQUAD_CLEAR_POINTS, 0.0); }
+	pub fn graze(&mut self) { self.grazes += 1; self.gain(GRAZE_POINTS, GRAZE_COMBO_GAIN); }
+	/// The player is hit: the combo is lost
+	pub fn miss(&mut self) { self.multiplier = 1.0; }
+
+	pub fn points(&self) -> u32 { self.points }
+	pub fn kills(&self) -> u32 { self.kills }
+	pub fn squads_cleared(&self) -> u32 { self.squads_cleared }
+	pub fn grazes(&self) -> u32 { self.grazes }
+	pub fn multiplier(&self) -> f32 { self.multiplier }
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+
+	#[test] fn combo_multiplies_and_decays()
+	{
+		let mut score = Score::new();
+		score.kill();
+		assert_eq!(score.points(), KILL_POINTS);
+		for _ in 0 .. 9 { score.kill(); }
+		assert!(score.multiplier() > 1.0);
+		let before = score.points();
+		score.kill();
+		assert!(score.points() - before > KILL_POINTS);
+
+		// kept for a while, then decays back to 1
+		let multiplier = score.multiplier();
+		score.update(COMBO_HOLD_SECS * 0.5);
+		assert_eq!(score.multiplier(), multiplier);
+		for _ in 0 .. 600 { score.update(FIXED_STEP); }
+		assert_eq!(score.multiplier(), 1.0);
+
+		score.graze();
+		assert_eq!(score.grazes(), 1);
+		score.miss();
+		assert_eq!(score.multiplier(), 1.0);
+	}
+}
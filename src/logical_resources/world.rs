@@ -0,0 +1,554 @@
+// Game World: owns the whole simulation state and steps it without touching the GPU
+// (the mapped device memory is written separately by `sync::sync_world`)
+// The simulation advances in fixed steps(`FIXED_STEP`), so that its results do not depend on the frame rate;
+// rendering interpolates positions between the last two steps.
+// Enemies fire by a bullet pattern(`bullet_pattern`); emitters of fired bullets running their own actions are kept apart, bound to the bullets.
+// Collisions are narrowed by linear quad-trees of enemies, enemy bullets and player bullets, rebuilt in every step.
+
+use std;
+use rand;
+use rand::distributions::*;
+use rayon::prelude::*;
+use utils::MemoryBlockManager;
+use utils::quadtree::LinearQuadTree;
+use constants::*;
+use {GameTime, GameUpdateArgs, GameRandomizer};
+use super::*;
+use super::enemy::*;
+use super::bullet_pattern::{Pattern, PatternLibrary, PatternRunner};
+
+/// Logical inputs of a frame(values of `LogicalInputTypes`, read from the input system or elsewhere)
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct FrameInput
+{
+	pub horizontal: f32, pub vertical: f32, pub shoot: f32, pub slowdown: f32, pub overdrive: f32
+}
+
+pub struct World
+{
+	background: BackgroundDatastore, background_next_appear: bool,
+	enemy_datastore: EnemyDatastore, enemy_entities: Vec<Enemy>, enemy_manager: EnemyManager, enemy_count: u32,
+	enemy_pattern: Pattern, pattern_runners: Vec<PatternRunner>, runner_spawns: Vec<PatternRunner>,
+	player: Player, player_bithash: u32,
+	player_bullets: Vec<PlayerBullet>, pb_memory_manager: MemoryBlockManager,
+	bullet_datastore: BulletDatastore, bullets: Vec<Bullet>, frequest_queue: Vec<FireRequest>, grazed: Vec<bool>,
+	lineburst_particles: LineBurstParticles, next_particle_spawn: Vec<(u32, f32, f32)>,
+	game_secs: GameTime, accumulator: GameTime, steps: u64, game_over_secs: GameTime,
+	shooting: bool, next_shoot: bool, secs_from_last_trigger: GameTime,
+	enemy_tree: LinearQuadTree, bullet_tree: LinearQuadTree, player_bullet_tree: LinearQuadTree,
+	score: Score
+}
+impl World
+{
+	/// A world with the built-in bullet patterns
+	pub fn new() -> Self { World::with_patterns(&PatternLibrary::builtin()) }
+	/// A world firing by the patterns(enemies fire `zako`, or the built-in one if missing)
+	pub fn with_patterns(patterns: &PatternLibrary) -> Self
+	{
+		let enemy_pattern = patterns.get("zako").cloned().unwrap_or_else(||
+		{
+			warn!("Bullet pattern `zako` is not found, the built-in one is used");
+			PatternLibrary::builtin().get("zako").unwrap().clone()
+		});
+		World
+		{
+			background: BackgroundDatastore::new(), background_next_appear: false,
+			enemy_datastore: EnemyDatastore::new(), enemy_entities: (0 .. MAX_ENEMY_COUNT).map(|_| Enemy::Free).collect(),
+			enemy_manager: EnemyManager::new(), enemy_count: 0,
+			enemy_pattern: enemy_pattern, pattern_runners: Vec::new(), runner_spawns: Vec::new(),
+			player: Player::new(), player_bithash: 0,
+			player_bullets: (0 .. MAX_PLAYER_BULLET_COUNT).map(|_| PlayerBullet::Free).collect(),
+			pb_memory_manager: MemoryBlockManager::new(MAX_PLAYER_BULLET_COUNT as u32),
+			bullet_datastore: BulletDatastore::new(), bullets: (0 .. MAX_BULLETS).map(|_| Bullet::Free).collect(),
+			frequest_queue: Vec::new(), grazed: vec![false; MAX_BULLETS],
+			lineburst_particles: LineBurstParticles::new(), next_particle_spawn: Vec::new(),
+			game_secs: 0.0, accumulator: 0.0, steps: 0, game_over_secs: 0.0,
+			shooting: false, next_shoot: false, secs_from_last_trigger: 0.0,
+			enemy_tree: LinearQuadTree::new(), bullet_tree: LinearQuadTree::new(), player_bullet_tree: LinearQuadTree::new(),
+			score: Score::new()
+		}
+	}
+
+	/// Advances the world by the real elapsed time(scaled by Slowdown/Overdrive inputs), in fixed steps
+	pub fn update(&mut self, randomizer: &mut GameRandomizer, delta_time: GameTime, input: &FrameInput)
+	{
+		let timescale = (1.0f32 + input.slowdown * 2.0f32) / (1.0f32 + input.overdrive);
+		self.accumulator += delta_time / timescale;
+		let mut steps = 0;
+		// tolerates rounding errors in summing frame times, so that the count of steps does not depend on the frame rate
+		while self.accumulator >= FIXED_STEP * (1.0 - 1.0e-3)
+		{
+			if steps >= MAX_STEPS_PER_UPDATE { self.accumulator = 0.0; break; }
+			self.accumulator -= FIXED_STEP;
+			self.step(randomizer, input);
+			steps += 1;
+		}
+	}
+	fn step(&mut self, randomizer: &mut GameRandomizer, input: &FrameInput)
+	{
+		if self.player.is_dead()
+		{
+			self.game_over_secs += FIXED_STEP;
+			// continue
+			if self.game_over_secs >= GAME_OVER_WAIT_SECS && input.shoot > 0.0 { self.restart(); }
+		}
+
+		let movescale = 1.0f32 + input.slowdown * 0.25f32;
+		let mut update_args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: randomizer };
+		self.steps += 1;
+		self.secs_from_last_trigger += update_args.delta_time;
+		self.game_secs += update_args.delta_time;
+		self.score.update(update_args.delta_time);
+		self.background.update(&mut update_args, self.background_next_appear);
+
+		let shooting = input.shoot > 0.0 && !self.player.is_dead();
+		self.update_player_shots(&update_args, shooting);
+		self.update_enemies(&mut update_args);
+		self.player_bithash = self.player.update(&update_args, input, movescale);
+		self.update_bullets(&update_args);
+
+		for &(count, x, y) in self.next_particle_spawn.iter()
+		{
+			self.lineburst_particles.spawn(update_args.randomizer, count, x, y, self.game_secs);
+		}
+		self.next_particle_spawn.clear();
+		self.lineburst_particles.garbage_collect(self.game_secs);
+		self.background_next_appear = rand::distributions::Range::new(0, 6).ind_sample(&mut update_args.randomizer) == 0;
+
+		if self.shooting && self.secs_from_last_trigger >= 0.0375
+		{
+			self.next_shoot = true;
+			self.secs_from_last_trigger -= 0.0375;
+		}
+	}
+	fn update_player_shots(&mut self, update_args: &GameUpdateArgs, new_shooting: bool)
+	{
+		self.next_shoot = if !self.shooting && new_shooting
+		{
+			// start timer
+			self.secs_from_last_trigger = update_args.delta_time;
+			self.shooting = true;
+			true
+		} else if self.shooting && !new_shooting
+		{
+			// stop timer
+			self.shooting = false;
+			false
+		} else { self.next_shoot };
+		if self.next_shoot
+		{
+			let winder_angle_abs = (self.game_secs * std::f32::consts::PI).sin() * 25.0;
+			for a in -1 .. 2
+			{
+				if let Some(bindex) = self.pb_memory_manager.allocate()
+				{
+					self.player_bullets[bindex as usize] = PlayerBullet::init(self.player.left(), self.player.top(), winder_angle_abs * a as f32, bindex);
+				}
+				else { warn!("Player Bullet Datastore is full!!"); }
+			}
+			self.next_shoot = false;
+		}
+		self.player_bullets.par_iter_mut().for_each(|e| e.update(update_args));
+		for e in self.player_bullets.iter_mut().filter(|e| e.is_garbage())
+		{
+			match e { &mut PlayerBullet::Garbage(bindex) => self.pb_memory_manager.free(bindex), _ => unreachable!() };
+			*e = PlayerBullet::Free;
+		}
+	}
+	fn update_enemies(&mut self, update_args: &mut GameUpdateArgs)
+	{
+		let cleared_squads =
+		{
+			let (datastore, entities, count, pattern) = (&mut self.enemy_datastore, &mut self.enemy_entities, &mut self.enemy_count, &self.enemy_pattern);
+			self.enemy_manager.update(update_args, |x, _, lref, manage_index| if let Some(bindex) = datastore.allocate_block()
+			{
+				entities[bindex as usize] = Enemy::init(x, bindex, lref, manage_index, pattern);
+				*count += 1;
+				Some(bindex)
+			} else { None })
+		};
+		for _ in 0 .. cleared_squads { self.score.clear_squad(); }
+		self.frequest_queue.clear();
+		let target = (self.player.left(), self.player.top());
+		for e in self.enemy_entities.iter_mut() { e.update(update_args, target, &mut self.frequest_queue, &mut self.runner_spawns); }
+		// emitters travelling with bullets(new ones start in the next step with their bullets, see `update_bullets`)
+		let (runners, frequest_queue, runner_spawns) = (std::mem::replace(&mut self.pattern_runners, Vec::new()), &mut self.frequest_queue, &mut self.runner_spawns);
+		self.pattern_runners = runners.into_iter().filter_map(|mut r| if r.update(update_args, target, frequest_queue, runner_spawns) { Some(r) } else { None }).collect();
+		self.collide_player_bullets(update_args);
+		for e in self.enemy_entities.iter_mut().filter(|e| e.is_garbage())
+		{
+			match e { &mut Enemy::Garbage(bindex) => self.enemy_datastore.free_block(bindex), _ => unreachable!() };
+			*e = Enemy::Free;
+			self.enemy_count -= 1;
+		}
+	}
+	fn update_bullets(&mut self, update_args: &GameUpdateArgs)
+	{
+		let target = (self.player.left(), self.player.top());
+		self.bullets.par_iter_mut().for_each(|e| e.update(update_args, target));
+		self.bullet_datastore.increase_all_lifetime(update_args.delta_time);
+		self.collide_player();
+		// emitters leave with their bullets
+		{
+			let bullets = &self.bullets;
+			self.pattern_runners.retain(|r| r.bullet().map_or(true, |b| !bullets[b as usize].is_garbage()));
+		}
+		for e in self.bullets.iter_mut().filter(|e| e.is_garbage())
+		{
+			match e { &mut Bullet::Garbage(i) => self.bullet_datastore.free(i), _ => unreachable!() };
+			*e = Bullet::Free;
+		}
+		let mut allocated = Vec::with_capacity(self.frequest_queue.len());
+		for f in &self.frequest_queue
+		{
+			let (vinfo, motion) = f.bullets();
+			let mut first = None;
+			for &(from, angle, speed) in vinfo
+			{
+				if let Some(bindex) = self.bullet_datastore.allocate()
+				{
+					first = first.or(Some(bindex));
+					self.bullet_datastore.init_lifetime(bindex);
+					self.grazed[bindex as usize] = false;
+					self.bullets[bindex as usize] = match motion
+					{
+						None => Bullet::init_linear(bindex, &from, angle, speed),
+						Some(m) => Bullet::init_moving(bindex, &from, angle, speed, m)
+					};
+				}
+				else { warn!("Bullet Datastore is full!!"); }
+			}
+			allocated.push(first);
+		}
+		// emitters start with their bullets(and are dropped with the bullets which could not be allocated)
+		for mut r in self.runner_spawns.drain(..)
+		{
+			if let Some(bindex) = r.fire_request().and_then(|n| allocated[n]) { r.ride(bindex); self.pattern_runners.push(r); }
+		}
+	}
+
+	/// Player bullets vs enemies: crashed bullets leave particles and damage the enemy, which explodes when destroyed
+	fn collide_player_bullets(&mut self, update_args: &mut GameUpdateArgs)
+	{
+		self.player_bullet_tree.clear();
+		for (n, (left, top)) in self.player_bullets.iter().enumerate().filter_map(|(n, b)| b.position().map(|p| (n, p)))
+		{
+			self.player_bullet_tree.insert(n as u32, left - PLAYER_BULLET_SIZE, top - PLAYER_BULLET_SIZE, left + PLAYER_BULLET_SIZE, top + PLAYER_BULLET_SIZE);
+		}
+
+		let particle_spawn_count = rand::distributions::Range::new(1, 8);
+		let (player_bullets, spawns, score) = (&mut self.player_bullets, &mut self.next_particle_spawn, &mut self.score);
+		for e in self.enemy_entities.iter_mut()
+		{
+			let (left, top) = match e.position() { Some(p) => p, None => continue };
+			self.player_bullet_tree.query(left - ENEMY_SIZE, top - ENEMY_SIZE, left + ENEMY_SIZE, top + ENEMY_SIZE, |n|
+				if e.position().is_some()
+				{
+					if let Some((psx, psy)) = player_bullets[n as usize].crash(left, top)
+					{
+						spawns.push((particle_spawn_count.ind_sample(&mut update_args.randomizer), psx, psy));
+						if e.damage()
+						{
+							spawns.push((MAX_LBPARTICLES_PER_GROUP as u32, left, top));
+							score.kill();
+						}
+					}
+				});
+		}
+	}
+	/// Enemy bullets and enemy bodies vs the player: a hit bullet is collected, and a bullet passing nearby is grazed(once)
+	/// (grazing counts while invincible). Crashing into an enemy body costs a life as well as a bullet does
+	fn collide_player(&mut self)
+	{
+		if self.player.is_dead() { return; }
+		let (left, top, radius, vulnerable) = (self.player.left(), self.player.top(), self.player.hit_radius(), !self.player.is_invincible());
+
+		self.bullet_tree.clear();
+		for (n, t) in self.bullets.iter().enumerate().filter_map(|(n, b)| b.translation().map(|t| (n, t)))
+		{
+			self.bullet_tree.insert(n as u32, t[0] - BULLET_HIT_RADIUS, t[1] - BULLET_HIT_RADIUS, t[0] + BULLET_HIT_RADIUS, t[1] + BULLET_HIT_RADIUS);
+		}
+		self.enemy_tree.clear();
+		for (n, (el, et)) in self.enemy_entities.iter().enumerate().filter_map(|(n, e)| e.position().map(|p| (n, p)))
+		{
+			self.enemy_tree.insert(n as u32, el - ENEMY_SIZE, et - ENEMY_SIZE, el + ENEMY_SIZE, et + ENEMY_SIZE);
+		}
+
+		let mut hit = false;
+		{
+			let (bullets, grazed, enemies, score) = (&mut self.bullets, &mut self.grazed, &self.enemy_entities, &mut self.score);
+			let graze_radius = radius + GRAZE_DISTANCE;
+			self.bullet_tree.query(left - graze_radius, top - graze_radius, left + graze_radius, top + graze_radius, |n|
+			{
+				let n = n as usize;
+				if vulnerable && !hit && bullets[n].crash(left, top, radius) { hit = true; }
+				else if !grazed[n]
+				{
+					if let Some(t) = bullets[n].translation()
+					{
+						let (xd, yd, r) = (t[0] - left, t[1] - top, graze_radius + BULLET_HIT_RADIUS);
+						if xd * xd + yd * yd <= r * r { grazed[n] = true; score.graze(); }
+					}
+				}
+			});
+			if vulnerable
+			{
+				self.enemy_tree.query(left - radius, top - radius, left + radius, top + radius, |n|
+					if let Some((el, et)) = enemies[n as usize].position()
+					{
+						if (el - left).abs() <= ENEMY_SIZE + radius && (et - top).abs() <= ENEMY_SIZE + radius { hit = true; }
+					});
+			}
+		}
+		if hit && self.player.hit()
+		{
+			self.score.miss();
+			self.next_particle_spawn.push((MAX_LBPARTICLES_PER_GROUP as u32, left, top));
+		}
+	}
+
+	/// Starts a new game(the step count, the time to the next step and the patterns are kept)
+	fn restart(&mut self)
+	{
+		let (accumulator, steps) = (self.accumulator, self.steps);
+		let enemy_pattern = self.enemy_pattern.clone();
+		*self = World::new();
+		self.enemy_pattern = enemy_pattern;
+		self.accumulator = accumulator;
+		self.steps = steps;
+	}
+
+	pub fn game_secs(&self) -> GameTime { self.game_secs }
+	/// All lives are lost(the game continues by Shoot after `GAME_OVER_WAIT_SECS`)
+	pub fn is_game_over(&self) -> bool { self.player.is_dead() }
+	pub fn score(&self) -> &Score { &self.score }
+	/// Count of the fixed steps so far
+	pub fn steps(&self) -> u64 { self.steps }
+	/// Position of the rendered frame between the previous(0) and the current(1) step
+	pub fn interpolation(&self) -> f32 { (self.accumulator / FIXED_STEP).max(0.0).min(1.0) }
+	/// Game secs of the rendered frame
+	pub fn render_secs(&self) -> GameTime { self.game_secs - (1.0 - self.interpolation()) * FIXED_STEP }
+	pub fn enemy_count(&self) -> u32 { self.enemy_count }
+	pub fn player_bithash(&self) -> u32 { self.player_bithash }
+	pub fn background(&self) -> &BackgroundDatastore { &self.background }
+	pub fn enemy_datastore(&self) -> &EnemyDatastore { &self.enemy_datastore }
+	pub fn enemies(&self) -> &[Enemy] { &self.enemy_entities }
+	pub fn player(&self) -> &Player { &self.player }
+	pub fn player_bullets(&self) -> &[PlayerBullet] { &self.player_bullets }
+	pub fn bullet_datastore(&self) -> &BulletDatastore { &self.bullet_datastore }
+	pub fn bullets(&self) -> &[Bullet] { &self.bullets }
+	pub fn lineburst_particles(&self) -> &LineBurstParticles { &self.lineburst_particles }
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use seeded_randomizer;
+
+	#[test] fn steps_without_gpu()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		let input = FrameInput { horizontal: 1.0, shoot: 1.0, .. FrameInput::default() };
+		for _ in 0 .. 60 * 5 { world.update(&mut rng, 1.0 / 60.0, &input); }
+
+		assert!((world.game_secs() - 5.0).abs() < 1.0e-3);
+		// the player is kept in the screen, and keeps shooting
+		assert_eq!(world.player().left(), PLAYER_LIMIT);
+		assert!(world.player_bullets().iter().any(|b| match b { &PlayerBullet::Entity { .. } => true, _ => false }));
+		// enemies appear and fire
+		assert!(world.enemies().iter().any(|e| e.render_params(1.0).is_some()));
+		assert!(world.bullets().iter().any(|b| b.translation().is_some()));
+	}
+	#[test] fn same_seed_reproduces_the_run()
+	{
+		fn run(seed: u64) -> (Vec<Option<[f32; 4]>>, Vec<Option<(f32, f32)>>)
+		{
+			let mut world = World::new();
+			let mut rng = seeded_randomizer(seed);
+			let input = FrameInput { horizontal: -0.5, shoot: 1.0, .. FrameInput::default() };
+			for _ in 0 .. 60 * 5 { world.update(&mut rng, 1.0 / 60.0, &input); }
+			(world.bullets().iter().map(|b| b.translation().cloned()).collect(),
+				world.enemies().iter().map(|e| e.render_params(1.0).map(|p| p.1)).collect())
+		}
+
+		assert_eq!(run(42), run(42));
+		assert!(run(42) != run(43));
+	}
+	#[test] fn frame_rate_does_not_change_the_results()
+	{
+		fn run(hz: u32) -> (u64, f32, Vec<Option<[f32; 4]>>, Vec<Option<(f32, f32)>>)
+		{
+			let mut world = World::new();
+			let mut rng = seeded_randomizer(3);
+			let input = FrameInput { horizontal: 0.75, vertical: -0.5, shoot: 1.0, .. FrameInput::default() };
+			for _ in 0 .. hz * 6 { world.update(&mut rng, 1.0 / hz as f32, &input); }
+			(world.steps(), world.player().left(), world.bullets().iter().map(|b| b.translation().cloned()).collect(),
+				world.enemies().iter().map(|e| e.render_params(1.0).map(|p| p.1)).collect())
+		}
+
+		let at60 = run(60);
+		assert_eq!(at60.0, 6 * 60);
+		assert!(at60 == run(30));
+		assert!(at60 == run(144));
+	}
+	fn place_bullet(world: &mut World, left: f32, top: f32)
+	{
+		let bindex = world.bullet_datastore.allocate().unwrap();
+		world.bullet_datastore.init_lifetime(bindex);
+		world.bullets[bindex as usize] = Bullet::init_linear(bindex, &[left, top, 0.0, 0.0], 0.0, 0.0);
+	}
+	#[test] fn bullets_hit_the_player()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		let (left, top) = (world.player().left(), world.player().top());
+		let at_player = |w: &World| w.bullets().iter().any(|b| b.translation().map_or(false, |t| t[0] == left && t[1] == top));
+		place_bullet(&mut world, left, top);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES - 1);
+		assert!(world.player().is_invincible());
+		assert!(!at_player(&world));
+
+		// invulnerable for a while
+		place_bullet(&mut world, left, top);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES - 1);
+		assert!(at_player(&world));
+	}
+	#[test] fn enemy_bodies_hit_the_player()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		while world.enemy_count() == 0 { world.update(&mut rng, FIXED_STEP, &FrameInput::default()); }
+		let (pleft, ptop) = (world.player().left(), world.player().top());
+		let n = world.enemies().iter().position(|e| e.position().is_some()).unwrap();
+		if let Enemy::Entity { ref mut left, ref mut top, .. } = world.enemy_entities[n] { *left = pleft + ENEMY_SIZE; *top = ptop; }
+		world.collide_player();
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES - 1);
+		// the enemy survives the crash
+		assert!(world.enemies()[n].position().is_some());
+	}
+	#[test] fn focusing_shrinks_the_hitbox()
+	{
+		let focus = FrameInput { slowdown: 1.0, .. FrameInput::default() };
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		// slowdown makes a step 3x longer in real time
+		world.update(&mut rng, FIXED_STEP * 3.0, &focus);
+		let (left, top) = (world.player().left(), world.player().top());
+		place_bullet(&mut world, left + 1.0, top);
+		world.update(&mut rng, FIXED_STEP * 3.0, &focus);
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES - 1);
+	}
+	#[test] fn game_over_and_continue()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		for _ in 0 .. PLAYER_INITIAL_LIVES * (PLAYER_INVINCIBLE_SECS / FIXED_STEP) as u32 + 8
+		{
+			if world.is_game_over() { break; }
+			let (left, top) = (world.player().left(), world.player().top());
+			place_bullet(&mut world, left, top);
+			world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		}
+		assert!(world.is_game_over());
+
+		// no shots and no continue right after the game over
+		let shoot = FrameInput { shoot: 1.0, .. FrameInput::default() };
+		world.update(&mut rng, FIXED_STEP, &shoot);
+		assert!(world.is_game_over());
+		assert!(world.player_bullets().iter().all(|b| match b { &PlayerBullet::Entity { .. } => false, _ => true }));
+		for _ in 0 .. (GAME_OVER_WAIT_SECS / FIXED_STEP) as u32 + 1 { world.update(&mut rng, FIXED_STEP, &shoot); }
+		assert!(!world.is_game_over());
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES);
+	}
+	#[test] fn enemies_are_destroyed_by_player_bullets()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		while world.enemy_count() == 0 { world.update(&mut rng, FIXED_STEP, &FrameInput::default()); }
+		let n = world.enemies().iter().position(|e| e.position().is_some()).unwrap();
+		for hit in 1 .. 4
+		{
+			let (left, top) = world.enemies()[n].position().unwrap();
+			let bindex = world.pb_memory_manager.allocate().unwrap();
+			world.player_bullets[bindex as usize] = PlayerBullet::init(left, top, 0.0, bindex);
+			world.collide_player_bullets(&mut GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng });
+			if hit < 3 { assert_eq!(world.enemies()[n].render_params(1.0).unwrap().3, 3 - hit); }
+		}
+		assert!(world.enemies()[n].is_garbage());
+		assert_eq!(world.score().kills(), 1);
+		assert_eq!(world.score().points(), KILL_POINTS);
+
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		// the block is freed(and may be reused by a new enemy)
+		assert!(world.enemies()[n].render_params(1.0).map_or(true, |p| p.3 == 3 && p.2 <= FIXED_STEP * 1.5));
+		assert!(world.lineburst_particles().groups().iter().any(|g| g.count == MAX_LBPARTICLES_PER_GROUP as u32));
+	}
+	#[test] fn bullets_near_the_player_are_grazed_once()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		let (left, top) = (world.player().left(), world.player().top());
+		place_bullet(&mut world, left + PLAYER_HIT_RADIUS + BULLET_HIT_RADIUS + GRAZE_DISTANCE * 0.5, top);
+		for _ in 0 .. 3 { world.update(&mut rng, FIXED_STEP, &FrameInput::default()); }
+		assert_eq!(world.score().grazes(), 1);
+		assert_eq!(world.score().points(), GRAZE_POINTS);
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES);
+
+		// grazing counts while invincible
+		let (left, top) = (world.player().left(), world.player().top());
+		place_bullet(&mut world, left, top);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert!(world.player().is_invincible());
+		place_bullet(&mut world, left + PLAYER_HIT_RADIUS + BULLET_HIT_RADIUS + GRAZE_DISTANCE * 0.5, top);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert_eq!(world.score().grazes(), 2);
+	}
+	#[test] fn emitters_leave_with_their_bullets()
+	{
+		let library = PatternLibrary::parse("pattern a { fire direction absolute 0 speed 0 { wait 1 repeat 4 { fire direction sequence 90 } } }").unwrap();
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		let (left, top) = (world.player().left(), world.player().top());
+		let mut fire_on_player = |world: &mut World|
+		{
+			let mut args = GameUpdateArgs { delta_time: FIXED_STEP, randomizer: &mut rng };
+			let mut runner = PatternRunner::new(library.get("a").unwrap());
+			runner.move_to((left, top));
+			runner.update(&mut args, (left, top), &mut world.frequest_queue, &mut world.runner_spawns);
+			world.update_bullets(&args);
+		};
+
+		// the bullet hits the player before the emitter fires
+		fire_on_player(&mut world);
+		assert_eq!(world.pattern_runners.len(), 1);
+		let bindex = world.pattern_runners[0].bullet().unwrap();
+		assert!(world.bullets()[bindex as usize].translation().is_some());
+		let mut rng = seeded_randomizer(1);
+		world.update(&mut rng, FIXED_STEP, &FrameInput::default());
+		assert_eq!(world.player().lives(), PLAYER_INITIAL_LIVES - 1);
+		assert!(world.pattern_runners.is_empty());
+
+		// no emitters without bullets
+		while world.bullet_datastore.allocate().is_some() {}
+		fire_on_player(&mut world);
+		assert!(world.pattern_runners.is_empty() && world.runner_spawns.is_empty());
+	}
+	#[test] fn interpolates_between_steps()
+	{
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(1);
+		let input = FrameInput { horizontal: 1.0, .. FrameInput::default() };
+		world.update(&mut rng, FIXED_STEP, &input);
+		let (prev, current) = (world.player().render_position(0.0).0, world.player().left());
+		world.update(&mut rng, FIXED_STEP * 0.5, &input);
+		assert_eq!(world.steps(), 1);
+		assert!((world.interpolation() - 0.5).abs() < 1.0e-3);
+		assert!((world.player().render_position(world.interpolation()).0 - (prev + current) * 0.5).abs() < 1.0e-3);
+	}
+}
@@ -12,12 +12,10 @@ extern crate half;
 extern crate interlude;
 extern crate postludium;
 extern crate texture_compression;
-extern crate psdloader;
 
 use interlude::*;
 use interlude::ffi::*;
 use texture_compression::*;
-use psdloader::*;
 
 mod constants;
 use constants::*;
@@ -25,9 +23,10 @@ mod structures;
 use structures::*;
 mod logical_resources;
 use logical_resources::*;
-use logical_resources::enemy::*;
 mod utils;
-use rand::distributions::*;
+mod sync;
+mod replay;
+use replay::Replay;
 use half::f16;
 use itertools::Itertools;
 
@@ -36,8 +35,6 @@ use postludium::*;
 mod smaa_extra_textures;
 use smaa_extra_textures::*;
 
-use rayon::prelude::*;
-
 use std::ops::Deref;
 use std::cell::RefCell;
 use std::sync::Arc;
@@ -49,6 +46,15 @@ pub enum LogicalInputTypes
 {
 	Horizontal, Vertical, Shoot, Slowdown, Overdrive
 }
+/// Reads the logical inputs of the frame for the world
+fn capture_input(input: &Input<LogicalInputTypes>) -> FrameInput
+{
+	FrameInput
+	{
+		horizontal: input[LogicalInputTypes::Horizontal], vertical: input[LogicalInputTypes::Vertical],
+		shoot: input[LogicalInputTypes::Shoot], slowdown: input[LogicalInputTypes::Slowdown], overdrive: input[LogicalInputTypes::Overdrive]
+	}
+}
 
 /*
 fn pack_color(canvas_size: &Size2, red: DecompressedChannelImageData, green: DecompressedChannelImageData,
@@ -91,17 +97,23 @@ fn pack_color(canvas_size: &Size2, red: DecompressedChannelImageData, green: Dec
 	color_pixels
 }
 */
-fn single(canvas_size: &Size2, v: DecompressedChannelImageData) -> Vec<u8>
+/// Loads a texture compiled by texture_compiler(see assets/Makefile), which must be of the format and the extent
+fn load_compiled_texture(path: std::path::PathBuf, format: TextureFormat, size: (usize, usize)) -> Vec<u8>
 {
-	let &Size2(cwidth, cheight) = canvas_size;
-	let mut color_pixels = vec![0u8; (cwidth * cheight) as usize];
-	for (x, y, px, py) in (0 .. v.height()).flat_map(|y| (0 .. v.width()).map(move |x| (x, y)))
-		.map(|(x, y)| (x, y, x as isize + v.offset_x(), y as isize + v.offset_y()))
-		.filter(|&(_, _, px, py)| (0 <= px && px < cwidth as isize) && (0 <= py && py < cheight as isize))
+	let texture = std::fs::File::open(&path).map_err(ContainerError::from).and_then(|mut fp| container::dds::read_dds(&mut fp))
+		.unwrap_or_else(|e| panic!("Failed to load the texture {}: {:?}(compile the assets by make)", path.display(), e));
+	if texture.format != format || texture.size != size
 	{
-		color_pixels[(px + py * cwidth as isize) as usize] = v.fetch(x, y);
+		panic!("The texture {} is {:?} {:?}, {:?} {:?} is required", path.display(), texture.format, texture.size, format, size);
 	}
-	color_pixels
+	texture.level_data(0).to_vec()
+}
+/// Compresses a texture through the on-disk cache(a failure in storing is not fatal)
+fn compress_with_cache<A: CompressionAlgorithm>(key: &str, source: &[u8], size: (usize, usize), cache_dir: &std::path::Path) -> Vec<u8>
+{
+	let (compressed, status) = compress_cached::<A>(key, source, size, cache_dir);
+	if let CacheStatus::StoreFailed(e) = status { warn!("Failed to store the compressed texture {} to the cache: {:?}", key, e); }
+	compressed
 }
 
 mod assets;
@@ -150,11 +162,32 @@ struct DevConfImageBindings<'d>
 
 /// in real secs
 pub type GameTime = f32;
+/// The random generator for all gameplay randomness(seedable, so a run can be reproduced)
+pub type GameRandomizer = rand::XorShiftRng;
+/// Makes the gameplay random generator from a seed value
+pub fn seeded_randomizer(seed: u64) -> GameRandomizer
+{
+	use rand::SeedableRng;
+	let (lo, hi) = (seed as u32, (seed >> 32) as u32);
+	// never be all zero(XorShift requires a nonzero state)
+	GameRandomizer::from_seed([lo, hi, lo ^ 0x9e3779b9, hi ^ 0x7f4a7c15])
+}
+/// The value following `name` in the command line
+fn option_value(name: &str) -> Option<String>
+{
+	std::env::args().skip_while(|a| a != name).nth(1)
+}
+/// The gameplay seed: `--seed <n>` in the command line, or taken from the clock(also if the value is invalid)
+fn startup_seed() -> u64
+{
+	option_value("--seed").and_then(|s| s.parse().map_err(|e| error!("Invalid seed value {}({:?}), taken from the clock instead", s, e)).ok())
+		.unwrap_or_else(|| { let t = time::get_time(); t.sec as u64 ^ ((t.nsec as u64) << 32) })
+}
 /// An updating util sets incl. Immutable Reference to Random Generator
 pub struct GameUpdateArgs<'a>
 {
-	/// A global random generator
-	pub randomizer: &'a mut rand::Rng,
+	/// The gameplay random generator
+	pub randomizer: &'a mut GameRandomizer,
 	/// The delta time from previous frame, in seconds
 	pub delta_time: GameTime
 }
@@ -194,22 +227,17 @@ fn main()
 		linear_sampler: &images.samplers()[0]
 	};
 
-	let playerbullet_image = PhotoshopDocument::open(engine.parse_asset("graphs.playerbullet", "psd")).unwrap();
-	let circle16_image = PhotoshopDocument::open(engine.parse_asset("graphs.circle16", "psd")).unwrap();
 	{
 		let mapped = images.map_staging_images_memory();
 		let offsets = images.staging_offsets();
-		let areatex_compressed = BC5::compress(&AREATEX_BYTES, (AREATEX_WIDTH, AREATEX_HEIGHT));
+		let texture_cache = std::env::current_dir().unwrap().join(".texture_cache");
+		let areatex_compressed = compress_with_cache::<BC5>("smaa_areatex", &AREATEX_BYTES, (AREATEX_WIDTH, AREATEX_HEIGHT), &texture_cache);
 		mapped.map_mut::<[u8; AREATEX_SIZE / 2]>(offsets[2] as usize).copy_from_slice(&areatex_compressed);
-		let searchtex_compressed = BC4::compress(&SEARCHTEX_BYTES, (SEARCHTEX_WIDTH, SEARCHTEX_HEIGHT));
+		let searchtex_compressed = compress_with_cache::<BC4>("smaa_searchtex", &SEARCHTEX_BYTES, (SEARCHTEX_WIDTH, SEARCHTEX_HEIGHT), &texture_cache);
 		mapped.map_mut::<[u8; SEARCHTEX_SIZE / 2]>(offsets[3] as usize).copy_from_slice(&searchtex_compressed);
 
-		let playerbullet_pixels = BC4::compress(&single(&Size2(playerbullet_image.width as u32, playerbullet_image.height as u32),
-			playerbullet_image.layer_raw_channel_image_data(0, PSDChannelIndices::Alpha)
-		), (playerbullet_image.width, playerbullet_image.height));
-		let circle16_pixels = BC4::compress(&single(&Size2(circle16_image.width as u32, circle16_image.height as u32),
-			circle16_image.layer_raw_channel_image_data(0, PSDChannelIndices::Alpha)
-		), (circle16_image.width, circle16_image.height));
+		let playerbullet_pixels = load_compiled_texture(engine.parse_asset("graphs.playerbullet", "dds"), TextureFormat::BC4Unorm, (16, 16));
+		let circle16_pixels = load_compiled_texture(engine.parse_asset("graphs.circle16", "dds"), TextureFormat::BC4Unorm, (16, 16));
 		mapped.range_mut(offsets[4] as usize, 16 * 16 / 2).copy_from_slice(&playerbullet_pixels);
 		mapped.range_mut(offsets[5] as usize, 16 * 16 / 2).copy_from_slice(&circle16_pixels);
 		mapped.map_mut::<[[f16; 4]; 4]>(offsets[0] as usize).copy_from_slice(&[
@@ -303,12 +331,20 @@ fn main()
 	let cputime_ms = RefCell::new(0.0f64);
 	let enemy_count = RefCell::new(0u32);
 	let player_bithash = RefCell::new(0u32);
+	let player_lives = RefCell::new(0u32);
+	let score_points = RefCell::new(0u32);
+	let score_multiplier = RefCell::new(1.0f64);
+	let score_grazes = RefCell::new(0u32);
 	let debug_info = DebugInfo::new(&engine, &[
 		DebugLine::UnsignedInt("FPS".to_owned(), &frames_per_second, None),
 		DebugLine::Float("Frame Time".to_owned(), &frame_time_ms, Some("ms".to_owned())),
 		DebugLine::Float("CPU Time".to_owned(), &cputime_ms, Some("ms".to_owned())),
 		DebugLine::UnsignedInt("Enemy Count".to_owned(), &enemy_count, None),
-		DebugLine::UnsignedInt("Player Bithash".to_owned(), &player_bithash, None)
+		DebugLine::UnsignedInt("Player Bithash".to_owned(), &player_bithash, None),
+		DebugLine::UnsignedInt("Player Lives".to_owned(), &player_lives, None),
+		DebugLine::UnsignedInt("Score".to_owned(), &score_points, None),
+		DebugLine::Float("Combo".to_owned(), &score_multiplier, Some("x".to_owned())),
+		DebugLine::UnsignedInt("Grazes".to_owned(), &score_grazes, None)
 	], &render_pass.smaa_combine, 0, &vps.entire).or_crash();
 
 	info!("Recording Rendering Commands...");
@@ -439,40 +475,18 @@ fn main()
 		}) };
 
 		let mapped_range = appdata.stg.map().or_crash();
-		let (uref_enemy, uref_bk, uref_player_center, uref_gametime, uref_particle_infos) =
-		{
-			let mapped = mapped_range.map_mut::<UniformMemory>(appdata.offset_uniform());
-			(&mut mapped.enemy_instance_data, &mut mapped.background_instance_data, &mut mapped.player_center_tf,
-				&mut mapped.gametime, &mut mapped.lineburst_particles)
-		};
-		let ref mut uref_bullet = mapped_range.map_mut::<BulletTranslations>(appdata.offset_bullet_translations()).0;
-		let (iref_enemy, iref_bk, iref_player, iref_enemy_rez, iref_player_bullet, iref_lineburst_particle_groups, iref_bullet) =
+		let uniform_memory = mapped_range.map_mut::<UniformMemory>(appdata.offset_uniform());
+		let instance_memory = mapped_range.map_mut::<InstanceMemory>(appdata.offset_instance());
+		let bullet_translations = mapped_range.map_mut::<BulletTranslations>(appdata.offset_bullet_translations());
+		// bullet patterns of enemies(the built-in ones if the asset is broken)
+		let patterns = std::fs::File::open(engine.parse_asset("patterns.zako", "bpat")).and_then(|mut fp|
 		{
-			let mapped = mapped_range.map_mut::<InstanceMemory>(appdata.offset_instance());
-			(&mut mapped.enemy_instance_mult, &mut mapped.background_instance_mult, &mut mapped.player_rotq,
-				&mut mapped.enemy_rez_instance_data, &mut mapped.player_bullet_offset_sincos, &mut mapped.lineburst_particle_groups,
-				&mut mapped.bullet_instances)
-		};
-		let mut background_datastore = BackgroundDatastore::new(uref_bk, iref_bk);
-		let mut enemy_datastore = EnemyDatastore::new(iref_enemy);
-		let mut pb_memory_manager = utils::MemoryBlockManager::new(MAX_PLAYER_BULLET_COUNT as u32);
-		let mut lineburst_particles = LineBurstParticles::new(iref_lineburst_particle_groups, uref_particle_infos);
-		let mut bullet_datastore = BulletDatastore::new(iref_bullet);
-
-		// double-buffered enemy entity list //
-		let mut enemy_entities: [Enemy; MAX_ENEMY_COUNT] = unsafe { std::mem::uninitialized() };
-		for n in 0 .. MAX_ENEMY_COUNT { enemy_entities[n] = Enemy::Free; }
-		let mut player = Player::new(uref_player_center, iref_player);
-		let mut player_bullets: [PlayerBullet; MAX_PLAYER_BULLET_COUNT] = unsafe { std::mem::uninitialized() };
-		for n in 0 .. MAX_PLAYER_BULLET_COUNT { player_bullets[n] = PlayerBullet::Free; }
-		let mut bullets: [Bullet; MAX_BULLETS] = unsafe { std::mem::uninitialized() };
-		for n in 0 .. MAX_BULLETS { bullets[n] = Bullet::Free; }
-
-		let mut mgr = EnemyManager::new();
-		// let mut eg = EnemyGroup::new(spawn_group::strategies::RandomFall(0.1, 10));
-
-		let mut frequest_queue = Vec::<FireRequest>::new();
-		let mut secs_from_last_fixed = 0.0f32;
+			let mut source = String::new();
+			std::io::Read::read_to_string(&mut fp, &mut source).map(|_| source)
+		}).map_err(|e| format!("{:?}", e)).and_then(|s| PatternLibrary::parse(&s).map_err(|e| format!("line {}: {}", e.line, e.message)))
+			.unwrap_or_else(|e| { warn!("Failed to load the bullet patterns({}), the built-in ones are used", e); PatternLibrary::builtin() });
+		let mut world = World::with_patterns(&patterns);
+
 		input_system.write().and_then(|mut isw|
 		{
 			isw.add_input(LogicalInputTypes::Horizontal, InputType::Axis(InputAxis::X));
@@ -487,18 +501,19 @@ fn main()
 			isw.add_input(LogicalInputTypes::Overdrive, InputType::Axis(InputAxis::Z));
 			Ok(())
 		}).unwrap();
-		let mut randomizer = rand::thread_rng();
-		let background_appear_rate = rand::distributions::Range::new(0, 6);
-		let mut background_next_appear = false;
+		// `--replay <file>` feeds the recorded frames instead of the input system(looped, as an attract-mode demo)
+		let replay = option_value("--replay").and_then(|p| Replay::load(std::path::Path::new(&p))
+			.map_err(|e| error!("Failed to load the replay {}({:?}), playing with the live inputs", p, e)).ok());
+		let seed = replay.as_ref().map(Replay::seed).unwrap_or_else(startup_seed);
+		info!("Gameplay Seed: {}", seed);
+		let mut randomizer = seeded_randomizer(seed);
+		let mut replay_cursor = 0;
+		// `--record <file>` saves the run on exit
+		let mut recording = option_value("--record").map(|p| (p, Replay::new(seed)));
+		let mut game_over = false;
 		let mut prev_time = time::PreciseTime::now();
-		let mut shooting = false;
-		let mut secs_from_last_trigger = 0.0;
-		let mut game_secs = 0.0;
 		let mut prev_fps_period = time::PreciseTime::now();
 		let mut fpscount = 0;
-		let mut next_shoot = false;
-		let mut next_particle_spawn = Vec::new();
-		let particle_spawn_count = rand::distributions::Range::new(1, 8);
 		loop
 		{
 			let msg = window_system.process_events_and_messages(&[&update_event]);
@@ -516,151 +531,56 @@ fn main()
 
 					// normal update
 					let cputime_start = time::PreciseTime::now();
-					input_system.write().unwrap().update();
-					let inputs = input_system.read().unwrap();
-					let timescale = (1.0f32 + inputs[LogicalInputTypes::Slowdown] * 2.0f32) / (1.0f32 + inputs[LogicalInputTypes::Overdrive]);
-					let movescale = 1.0f32 + inputs[LogicalInputTypes::Slowdown] * 0.25f32;
-					let mut update_args = GameUpdateArgs
-					{
-						delta_time: (delta_time.num_microseconds().unwrap() as f32 / 1_000_000.0) / timescale,
-						randomizer: &mut randomizer
-					};
-					secs_from_last_fixed += update_args.delta_time;
-					secs_from_last_trigger += update_args.delta_time;
-					game_secs += update_args.delta_time;
-					uref_gametime[0] = game_secs;
-					background_datastore.update(&mut update_args, background_next_appear);
-
-					let new_shooting = input_system.read().unwrap()[LogicalInputTypes::Shoot] > 0.0;
-					next_shoot = if !shooting && new_shooting
-					{
-						// start timer
-						secs_from_last_trigger = update_args.delta_time;
-						shooting = true;
-						true
-					} else if shooting && !new_shooting
-					{
-						// stop timer
-						shooting = false;
-						false
-					} else { next_shoot };
-					if next_shoot
-					{
-						let winder_angle_abs = (game_secs * std::f32::consts::PI).sin() * 25.0;
-						for a in -1 .. 2
-						{
-							let block_index = pb_memory_manager.allocate();
-							if let Some(bindex) = block_index
-							{
-								let bindex = bindex as usize;
-								player_bullets[bindex] = unsafe
-								{
-									let iref_player_bullet_ref_ptr = iref_player_bullet.as_mut_ptr();
-									PlayerBullet::init(player.left(), player.top(), winder_angle_abs * a as f32, bindex as u32,
-										&mut *iref_player_bullet_ref_ptr.offset(bindex as isize))
-								};
-							}
-							else { warn!("Player Bullet Datastore is full!!"); }
-						}
-						next_shoot = false;
-					}
-					player_bullets.par_iter_mut().for_each(|e| e.update(&update_args));
-					for e in player_bullets.iter_mut().filter(|e| e.is_garbage())
-					{
-						match e { &mut PlayerBullet::Garbage(bindex) => pb_memory_manager.free(bindex), _ => unreachable!() };
-						*e = PlayerBullet::Free;
-					}
-
-					mgr.update(&mut update_args, |x, y, lref, manage_index| if let Some(bindex) = enemy_datastore.allocate_block()
+					let (delta_secs, input) = if let Some(ref r) = replay
 					{
-						let bindexi = bindex as isize;
-						enemy_entities[bindex as usize] = unsafe
+						if replay_cursor >= r.frames().len()
 						{
-							let ref mut uref_enemy_ptr = *uref_enemy.as_mut_ptr().offset(bindexi);
-							let ref mut iref_enemy_rez_ptr = *iref_enemy_rez.as_mut_ptr().offset(bindexi);
-							Enemy::init(x, bindex, lref, manage_index, uref_enemy_ptr, iref_enemy_rez_ptr)
-						};
-						*enemy_count.borrow_mut() += 1;
-						Some(bindex)
-					} else { None });
-					frequest_queue.clear();
-					for e in enemy_entities.iter_mut()
-					{
-						let op = e.update(&update_args, &mut frequest_queue);
-						if let Some((new_left, new_top)) = op
-						{
-							for pb in player_bullets.iter_mut()
-							{
-								if let Some((psx, psy)) = pb.crash(new_left, new_top)
-								{
-									next_particle_spawn.push((particle_spawn_count.ind_sample(&mut update_args.randomizer), psx, psy));
-								}
-							}
+							info!("Replay Finished, restarting...");
+							world = World::with_patterns(&patterns);
+							game_over = false;
+							randomizer = seeded_randomizer(seed);
+							replay_cursor = 0;
 						}
+						let frame = r.frames().get(replay_cursor).cloned();
+						replay_cursor += 1;
+						frame.map(|f| (f.delta_time, f.input)).unwrap_or((0.0, FrameInput::default()))
 					}
-					for e in enemy_entities.iter_mut().filter(|e| e.is_garbage())
-					{
-						match e { &mut Enemy::Garbage(bindex) => enemy_datastore.free_block(bindex), _ => unreachable!() };
-						*e = Enemy::Free;
-						*enemy_count.borrow_mut() -= 1;
-					}
-					*player_bithash.borrow_mut() = player.update(&update_args, &*inputs, movescale);
-					// println!("PlayerBitHashBin: {:08b}", *player_bithash.borrow());
-					bullets.par_iter_mut().for_each(|e| e.update(&update_args));
-					bullet_datastore.increase_all_lifetime(update_args.delta_time);
-					for e in bullets.iter_mut().filter(|e| e.is_garbage())
-					{
-						match e { &mut Bullet::Garbage(i) => bullet_datastore.free(i), _ => unreachable!() };
-						*e = Bullet::Free;
-					}
-					for f in &frequest_queue
+					else
 					{
-						match f
+						input_system.write().unwrap().update();
+						let live_input = capture_input(&*input_system.read().unwrap());
+						let delta_secs = delta_time.num_microseconds().unwrap() as f32 / 1_000_000.0;
+						match recording
 						{
-							&FireRequest::Linears(ref vinfo) => for &(from, angle, speed) in vinfo
-							{
-								if let Some(bindex) = bullet_datastore.allocate()
-								{
-									bullets[bindex as usize] = unsafe
-									{
-										let uref_bullet_ref_ptr = uref_bullet.as_mut_ptr();
-										bullet_datastore.init_lifetime(bindex);
-										Bullet::init_linear(bindex, &mut *uref_bullet_ref_ptr.offset(bindex as isize), &from, angle, speed)
-									};
-								}
-								else { warn!("Bullet Datastore is full!!"); }
-							}
+							Some((_, ref mut r)) => (delta_secs, r.record(delta_secs, &live_input)),
+							None => (delta_secs, live_input)
 						}
-					}
-
-					if !next_particle_spawn.is_empty()
+					};
+					world.update(&mut randomizer, delta_secs, &input);
+					if world.is_game_over() != game_over
 					{
-						for &(count, x, y) in next_particle_spawn.iter()
+						game_over = world.is_game_over();
+						if game_over
 						{
-							lineburst_particles.spawn(count, x, y, game_secs);
+							let score = world.score();
+							info!("Game Over: {} points, {} kills, {} grazes(Shoot to continue)", score.points(), score.kills(), score.grazes());
 						}
-						next_particle_spawn.clear();
+						else { info!("Continued"); }
 					}
-					lineburst_particles = lineburst_particles.garbage_collect(game_secs);
+					sync::sync_world(&world, uniform_memory, instance_memory, bullet_translations);
+					*enemy_count.borrow_mut() = world.enemy_count();
+					*player_bithash.borrow_mut() = world.player_bithash();
+					*player_lives.borrow_mut() = world.player().lives();
+					*score_points.borrow_mut() = world.score().points();
+					*score_multiplier.borrow_mut() = world.score().multiplier() as f64;
+					*score_grazes.borrow_mut() = world.score().grazes();
 
-					background_next_appear = false;
 					*cputime_ms.borrow_mut() = cputime_start.to(time::PreciseTime::now()).num_microseconds().unwrap_or(0) as f64 / 1000.0f64;
 					debug_info.update();
 				},
 				_ => ()
 			}
 
-			if secs_from_last_fixed >= 1.0 / 60.0
-			{
-				// fixed update
-				background_next_appear = background_appear_rate.ind_sample(&mut randomizer) == 0;
-				secs_from_last_fixed -= 1.0 / 60.0;
-			}
-			if shooting && secs_from_last_trigger >= 0.0375
-			{
-				next_shoot = true;
-				secs_from_last_trigger -= 0.0375;
-			}
 			if prev_fps_period.to(time::PreciseTime::now()) >= time::Duration::seconds(1)
 			{
 				*frames_per_second.borrow_mut() = fpscount;
@@ -669,6 +589,16 @@ fn main()
 			}
 		}
 
+		if let Some((path, r)) = recording
+		{
+			match if r.frames().is_empty() { None } else { Some(r.save(std::path::Path::new(&path))) }
+			{
+				Some(Ok(())) => info!("Replay saved to {} ({} frames)", path, r.frames().len()),
+				Some(Err(e)) => error!("Failed to save the replay to {}: {:?}", path, e),
+				// not loadable
+				None => warn!("No frames recorded, the replay {} is not saved", path)
+			}
+		}
 		exit_flag.store(true, Ordering::Release);
 		update_observer.join()
 	};
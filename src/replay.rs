@@ -0,0 +1,133 @@
+// Input Replay: per-frame logical inputs and delta times with the gameplay seed, in a compact file
+
+use std;
+use std::io::prelude::*;
+use std::path::Path;
+use logical_resources::FrameInput;
+use GameTime;
+
+const MAGIC: [u8; 4] = *b"HGR1";
+// delta time(f32) + 5 quantized axes(i8)
+const FRAME_BYTES: usize = 4 + 5;
+
+/// An input quantized to the stored precision(1/127)
+fn quantize(v: f32) -> i8 { (v.max(-1.0).min(1.0) * 127.0).round() as i8 }
+fn dequantize(v: i8) -> f32 { v as f32 / 127.0 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayFrame { pub delta_time: GameTime, pub input: FrameInput }
+impl ReplayFrame
+{
+	fn write_to(&self, bytes: &mut Vec<u8>)
+	{
+		let i = &self.input;
+		bytes.extend_from_slice(&self.delta_time.to_bits().to_le_bytes());
+		bytes.extend([i.horizontal, i.vertical, i.shoot, i.slowdown, i.overdrive].iter().map(|&v| quantize(v) as u8));
+	}
+	fn read_from(bytes: &[u8]) -> Self
+	{
+		let a = |n: usize| dequantize(bytes[4 + n] as i8);
+		ReplayFrame
+		{
+			delta_time: f32::from_bits(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+			input: FrameInput { horizontal: a(0), vertical: a(1), shoot: a(2), slowdown: a(3), overdrive: a(4) }
+		}
+	}
+}
+
+/// A recorded run: replaying the frames on a world with the same seed reproduces it
+pub struct Replay { seed: u64, frames: Vec<ReplayFrame> }
+impl Replay
+{
+	pub fn new(seed: u64) -> Self { Replay { seed: seed, frames: Vec::new() } }
+	pub fn seed(&self) -> u64 { self.seed }
+	pub fn frames(&self) -> &[ReplayFrame] { &self.frames }
+
+	/// Appends a frame, and returns the input as stored(the world must be fed this, not the live input)
+	pub fn record(&mut self, delta_time: GameTime, input: &FrameInput) -> FrameInput
+	{
+		let stored = FrameInput
+		{
+			horizontal: dequantize(quantize(input.horizontal)), vertical: dequantize(quantize(input.vertical)),
+			shoot: dequantize(quantize(input.shoot)), slowdown: dequantize(quantize(input.slowdown)), overdrive: dequantize(quantize(input.overdrive))
+		};
+		self.frames.push(ReplayFrame { delta_time: delta_time, input: stored });
+		stored
+	}
+
+	// Layout: magic, seed(u64), frame count(u32), frames
+	pub fn to_bytes(&self) -> Vec<u8>
+	{
+		let mut bytes = Vec::with_capacity(16 + self.frames.len() * FRAME_BYTES);
+		bytes.extend_from_slice(&MAGIC);
+		bytes.extend_from_slice(&self.seed.to_le_bytes());
+		bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+		for f in &self.frames { f.write_to(&mut bytes); }
+		bytes
+	}
+	pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self>
+	{
+		let invalid = |m: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, m.to_owned());
+		if bytes.len() < 16 || bytes[.. 4] != MAGIC { return Err(invalid("not a replay file")); }
+		let mut seed = [0u8; 8]; seed.copy_from_slice(&bytes[4 .. 12]);
+		let count = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]) as usize;
+		if count == 0 { return Err(invalid("empty replay")); }
+		if bytes.len() != 16 + count * FRAME_BYTES { return Err(invalid("truncated replay file")); }
+		Ok(Replay { seed: u64::from_le_bytes(seed), frames: bytes[16 ..].chunks(FRAME_BYTES).map(ReplayFrame::read_from).collect() })
+	}
+	pub fn save(&self, path: &Path) -> std::io::Result<()>
+	{
+		std::fs::File::create(path).and_then(|mut fp| fp.write_all(&self.to_bytes()))
+	}
+	pub fn load(path: &Path) -> std::io::Result<Self>
+	{
+		let mut bytes = Vec::new();
+		std::fs::File::open(path).and_then(|mut fp| fp.read_to_end(&mut bytes))?;
+		Replay::from_bytes(&bytes)
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use logical_resources::World;
+	use seeded_randomizer;
+
+	#[test] fn roundtrip()
+	{
+		let mut replay = Replay::new(0x0123456789abcdef);
+		replay.record(1.0 / 60.0, &FrameInput { horizontal: -1.0, vertical: 0.3, shoot: 1.0, .. FrameInput::default() });
+		replay.record(1.0 / 30.0, &FrameInput { slowdown: 0.5, overdrive: 2.0, .. FrameInput::default() });
+		let bytes = replay.to_bytes();
+		assert_eq!(bytes.len(), 16 + 2 * FRAME_BYTES);
+
+		let restored = Replay::from_bytes(&bytes).unwrap();
+		assert_eq!(restored.seed(), replay.seed());
+		assert_eq!(restored.frames(), replay.frames());
+		assert_eq!(restored.frames()[1].input.overdrive, 1.0);
+		assert!(Replay::from_bytes(&bytes[.. bytes.len() - 1]).is_err());
+		assert!(Replay::from_bytes(&Replay::new(1).to_bytes()).is_err());
+	}
+	#[test] fn replaying_reproduces_the_run()
+	{
+		let mut replay = Replay::new(7);
+		let mut world = World::new();
+		let mut rng = seeded_randomizer(replay.seed());
+		for n in 0 .. 60 * 4
+		{
+			let live = FrameInput { horizontal: (n as f32 * 0.05).sin(), shoot: (n / 30 % 2) as f32, slowdown: 0.33, .. FrameInput::default() };
+			let delta_time = if n % 3 == 0 { 1.0 / 30.0 } else { 1.0 / 90.0 };
+			let input = replay.record(delta_time, &live);
+			world.update(&mut rng, delta_time, &input);
+		}
+
+		let replay = Replay::from_bytes(&replay.to_bytes()).unwrap();
+		let mut replayed = World::new();
+		let mut rng = seeded_randomizer(replay.seed());
+		for f in replay.frames() { replayed.update(&mut rng, f.delta_time, &f.input); }
+		assert_eq!(replayed.player().left(), world.player().left());
+		assert_eq!(replayed.bullets().iter().map(|b| b.translation().cloned()).collect::<Vec<_>>(),
+			world.bullets().iter().map(|b| b.translation().cloned()).collect::<Vec<_>>());
+	}
+}
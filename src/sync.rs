@@ -0,0 +1,75 @@
+// World to Device Memory Synchronization: writes the state of the simulation into the mapped staging buffer
+
+use std;
+use nalgebra::*;
+use interlude::*;
+use structures::*;
+use logical_resources::*;
+use logical_resources::enemy::RezonatorRotation;
+
+fn store_quaternion(to: &mut CVector4, q: &Quaternion<f32>)
+{
+	*to = [q.i, q.j, q.k, q.w];
+}
+/// Rotations of the two cubes of a character
+fn character_rotations(living_secs: f32) -> [CVector4; 2]
+{
+	let mut rotq = [[0.0; 4]; 2];
+	store_quaternion(&mut rotq[0], UnitQuaternion::new(Vector3::new(-1.0f32, 0.0, 0.75).normalize() * (260.0 * living_secs).to_radians()).quaternion());
+	store_quaternion(&mut rotq[1], UnitQuaternion::new(Vector3::new(1.0f32, -1.0, 0.5).normalize() * (-260.0 * living_secs + 13.0).to_radians()).quaternion());
+	rotq
+}
+
+/// Writes the world into the uniform, instance and bullet translation memory
+pub fn sync_world(world: &World, uniform: &mut UniformMemory, instance: &mut InstanceMemory, bullet_translations: &mut BulletTranslations)
+{
+	let alpha = world.interpolation();
+	uniform.gametime[0] = world.render_secs();
+
+	// Background
+	for ((d, m), (p, &s)) in uniform.background_instance_data.iter_mut().zip(instance.background_instance_mult.iter_mut())
+		.zip(world.background().panels().iter().zip(world.background().instance_data().iter()))
+	{
+		d.offset = p.render_offset(alpha); d.scale = p.scale; *m = s;
+	}
+
+	// Player
+	let (player_left, player_top) = world.player().render_position(alpha);
+	uniform.player_center_tf = if world.player().is_visible() { [player_left, player_top, 0.0, 0.0] } else { [std::f32::MAX, std::f32::MAX, 0.0, 0.0] };
+	instance.player_rotq = character_rotations(world.player().living_secs());
+	for (d, b) in instance.player_bullet_offset_sincos.iter_mut().zip(world.player_bullets().iter()) { *d = b.render_offset_sincos(alpha); }
+
+	// Enemies
+	instance.enemy_instance_mult.copy_from_slice(world.enemy_datastore().instance_data());
+	for r in instance.enemy_rez_instance_data.iter_mut() { r[0] = 0.0; }
+	for (bindex, (left, top), living_secs, rezonator_left, RezonatorRotation(r1, r2)) in world.enemies().iter().filter_map(|e| e.render_params(alpha))
+	{
+		let location = &mut uniform.enemy_instance_data[bindex as usize];
+		location.center_tf = [left, top, 0.0, 0.0];
+		location.rotq = character_rotations(living_secs);
+		instance.enemy_rez_instance_data[bindex as usize] = [rezonator_left as f32, r1, r2, 0.0];
+	}
+
+	// Bullets
+	let datastore = world.bullet_datastore();
+	for (t, b) in bullet_translations.0.iter_mut().zip(world.bullets().iter())
+	{
+		if let Some(bt) = b.render_translation(alpha) { *t = bt; }
+	}
+	for (n, bi) in instance.bullet_instances.iter_mut().enumerate()
+	{
+		bi.lifetime = datastore.lifetime(n);
+		bi.available = if datastore.is_available(n) { 1.0 } else { 0.0 };
+	}
+
+	// Line Burst Particles
+	for (d, g) in instance.lineburst_particle_groups.iter_mut().zip(world.lineburst_particles().groups().iter())
+	{
+		d.count = g.count; d.start_point = g.start_point;
+	}
+	for (d, p) in uniform.lineburst_particles.iter_mut().zip(world.lineburst_particles().particles().iter())
+	{
+		d.length_colrel_lifestart_lifemult = [p.length, 0.0, p.lifestart, p.lifemult];
+		d.sincos_xx = [p.sincos[0], p.sincos[1], 0.0, 0.0];
+	}
+}
@@ -1,3 +1,6 @@
 pub mod memblock;
 pub use self::memblock::*;
-pub mod quadtree;
\ No newline at end of file
+pub mod quadtree;
+
+/// Linear interpolation from `a`(t = 0) to `b`(t = 1)
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
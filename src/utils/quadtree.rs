@@ -1,16 +1,105 @@
 // Linear Quad-Tree
 
-use std;
+use constants::*;
 
+/// Levels under the root(the finest level divides the play field into 16x16 cells)
+pub const QUADTREE_DEPTH: u32 = 4;
+const FINEST_CELLS: u32 = 1 << QUADTREE_DEPTH;
+/// Count of cells in all levels
+pub const QUADTREE_CELLS: usize = ((1 << (2 * (QUADTREE_DEPTH + 1))) - 1) / 3;
+
+fn cell_coordinate(v: f32) -> u32 { ((v / (SCREEN_SIZE * 2.0) * FINEST_CELLS as f32).max(0.0) as u32).min(FINEST_CELLS - 1) }
+fn level_offset(level: u32) -> usize { ((1 << (2 * level)) - 1) / 3 }
+
+/// Morton code of the finest cell containing the point(clamped into the play field)
 pub fn bithash(left: f32, top: f32) -> u8
 {
 	// yx yx yx yx
-	fn bitdisc(x: u8) -> u8
+	fn bitdisc(x: u32) -> u32
 	{
 		let x = (x | (x << 2)) & 0x33;
 		(x | (x << 1)) & 0x55
 	}
-	let x = (unsafe { std::mem::transmute::<_, u32>(((left / 9.0) * 2.0).trunc() as i32) }) as u8;
-	let y = ((top * 2.0).trunc() as u32 >> 3) as u8;
-	bitdisc(x & 0x0f) | (bitdisc(y & 0x0f) << 1)
+	(bitdisc(cell_coordinate(left + SCREEN_SIZE)) | (bitdisc(cell_coordinate(top)) << 1)) as u8
+}
+/// (level, index in the level) of the smallest cell containing the region
+fn region_cell(left: f32, top: f32, right: f32, bottom: f32) -> (u32, usize)
+{
+	let (a, b) = (bithash(left, top) as u32, bithash(right, bottom) as u32);
+	// corners in different quadrants of a level go up to the parent level
+	let shift = (0 .. QUADTREE_DEPTH).rev().find(|&l| ((a ^ b) >> (l * 2)) & 3 != 0).map_or(0, |l| l + 1);
+	(QUADTREE_DEPTH - shift, (b >> (shift * 2)) as usize)
+}
+
+/// Objects(indices) bucketed into the cells of all levels by their bounding regions
+pub struct LinearQuadTree { cells: Vec<Vec<u32>> }
+impl LinearQuadTree
+{
+	pub fn new() -> Self { LinearQuadTree { cells: vec![Vec::new(); QUADTREE_CELLS] } }
+	pub fn clear(&mut self) { for c in self.cells.iter_mut() { c.clear(); } }
+	pub fn insert(&mut self, index: u32, left: f32, top: f32, right: f32, bottom: f32)
+	{
+		let (level, cell) = region_cell(left, top, right, bottom);
+		self.cells[level_offset(level) + cell].push(index);
+	}
+	/// Calls `f` with every object that may overlap the region
+	/// (objects in the cells containing the region, and in the cells contained by it)
+	pub fn query<F: FnMut(u32)>(&self, left: f32, top: f32, right: f32, bottom: f32, mut f: F)
+	{
+		let (level, cell) = region_cell(left, top, right, bottom);
+		for l in 0 .. level + 1
+		{
+			for &i in &self.cells[level_offset(l) + (cell >> (2 * (level - l)))] { f(i); }
+		}
+		for l in level + 1 .. QUADTREE_DEPTH + 1
+		{
+			let d = 2 * (l - level);
+			for c in &self.cells[level_offset(l) + (cell << d) .. level_offset(l) + ((cell + 1) << d)]
+			{
+				for &i in c { f(i); }
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use rand::{Rng, SeedableRng, XorShiftRng};
+
+	#[test] fn morton_order()
+	{
+		assert_eq!(bithash(-SCREEN_SIZE, 0.0), 0);
+		assert_eq!(bithash(SCREEN_SIZE, SCREEN_SIZE * 2.0), 0xff);
+		assert_eq!(bithash(-SCREEN_SIZE + 3.5, 0.0), 0x01);
+		assert_eq!(bithash(-SCREEN_SIZE, 3.5), 0x02);
+		// out of the play field is clamped
+		assert_eq!(bithash(-100.0, -100.0), 0);
+	}
+	#[test] fn finds_all_overlaps()
+	{
+		let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+		let regions = (0 .. 500).map(|_|
+		{
+			let (x, y, r) = (rng.gen_range(-SCREEN_SIZE, SCREEN_SIZE), rng.gen_range(0.0, SCREEN_SIZE * 2.0), rng.gen_range(0.1, 1.5));
+			(x - r, y - r, x + r, y + r)
+		}).collect::<Vec<_>>();
+		let mut tree = LinearQuadTree::new();
+		for (n, &(l, t, r, b)) in regions.iter().enumerate() { tree.insert(n as u32, l, t, r, b); }
+
+		let mut candidates = 0;
+		for &(l, t, r, b) in regions.iter().take(50)
+		{
+			let mut found = Vec::new();
+			tree.query(l, t, r, b, |i| found.push(i as usize));
+			for (n, &(l2, t2, r2, b2)) in regions.iter().enumerate()
+			{
+				if l <= r2 && l2 <= r && t <= b2 && t2 <= b { assert!(found.contains(&n)); }
+			}
+			candidates += found.len();
+		}
+		// far narrower than full scans
+		assert!(candidates < 50 * regions.len() / 4);
+	}
 }
@@ -0,0 +1,57 @@
+//! BC4/BC5 encoder timings per quality level(run with `cargo bench --bench bc4`)
+
+// Same pre-1.17 idioms as the library
+#![allow(clippy::redundant_field_names, clippy::manual_div_ceil)]
+
+extern crate texture_compression;
+use texture_compression::*;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 16;
+
+// SMAA AreaTex sized(160x560, RG) input: smooth ramps with noise, and some blocks holding the boundary values
+fn make_source(size: (usize, usize), channels: usize) -> Vec<u8>
+{
+	let mut state = 0x2545f491u32;
+	(0 .. size.0 * size.1 * channels).map(|n|
+	{
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		let (x, y) = ((n / channels) % size.0, (n / channels) / size.0);
+		if (x / 4 + y / 4) % 7 == 0 { [0, 255][(state >> 31) as usize] }
+		else { ((x + y * 3 + n % channels * 90) as u8).wrapping_add((state >> 29) as u8) }
+	}).collect()
+}
+fn as_millis(d: Duration) -> f64 { d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0 }
+fn measure<F: Fn() -> Vec<u8>>(name: &str, blocks: usize, f: F)
+{
+	f();	// warming up
+	let (mut total, mut best) = (0.0, f64::MAX);
+	for _ in 0 .. ITERATIONS
+	{
+		let start = Instant::now();
+		let r = f();
+		let elapsed = as_millis(start.elapsed());
+		assert!(!r.is_empty());
+		total += elapsed; best = best.min(elapsed);
+	}
+	let mean = total / ITERATIONS as f64;
+	println!("{:<16} mean {:>9.3} ms, best {:>9.3} ms, {:>7.1} ns/block", name, mean, best, mean * 1_000_000.0 / blocks as f64);
+}
+fn bench<A: CompressionAlgorithm>(label: &str, source: &[u8], size: (usize, usize))
+{
+	let blocks = ((size.0 + 3) / 4) * ((size.1 + 3) / 4);
+	println!("{} {}x{}(serial):", label, size.0, size.1);
+	for &(name, quality) in &[("  fast", Quality::Fast), ("  normal", Quality::Normal), ("  high", Quality::High)]
+	{
+		let options = CompressionOptions { quality: quality, .. CompressionOptions::default() };
+		let image = ImageView::new(source, size, A::source_channels());
+		measure(name, blocks, || A::compress_image(&image, &options));
+	}
+}
+
+fn main()
+{
+	let areatex = make_source((160, 560), 2);
+	bench::<BC4>("BC4", &areatex[.. 160 * 560], (160, 560));
+	bench::<BC5>("BC5", &areatex, (160, 560));
+}
@@ -0,0 +1,49 @@
+//! Serial vs Parallel encoder timings(run with `cargo bench`)
+
+extern crate texture_compression;
+use texture_compression::*;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 16;
+
+// SMAA AreaTex sized(160x560, RG) pseudo-random input
+fn make_source(size: (usize, usize), channels: usize) -> Vec<u8>
+{
+	let mut state = 0x2545f491u32;
+	(0 .. size.0 * size.1 * channels).map(|n|
+	{
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		((n % 256) as u8).wrapping_add((state >> 28) as u8)
+	}).collect()
+}
+fn as_millis(d: Duration) -> f64 { d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0 }
+fn measure<F: Fn() -> Vec<u8>>(name: &str, f: F) -> f64
+{
+	f();	// warming up
+	let (mut total, mut best) = (0.0, f64::MAX);
+	for _ in 0 .. ITERATIONS
+	{
+		let start = Instant::now();
+		let r = f();
+		let elapsed = as_millis(start.elapsed());
+		assert!(!r.is_empty());
+		total += elapsed; best = best.min(elapsed);
+	}
+	println!("{:<16} mean {:>9.3} ms, best {:>9.3} ms", name, total / ITERATIONS as f64, best);
+	total / ITERATIONS as f64
+}
+fn compare<A: CompressionAlgorithm>(label: &str, source: &[u8], size: (usize, usize))
+{
+	assert_eq!(A::compress(source, size), A::compress_parallel(source, size));
+	println!("{} {}x{}:", label, size.0, size.1);
+	let serial = measure("  serial", || A::compress(source, size));
+	let parallel = measure("  parallel", || A::compress_parallel(source, size));
+	println!("  speedup x{:.2}", serial / parallel);
+}
+
+fn main()
+{
+	let areatex = make_source((160, 560), 2);
+	compare::<BC5>("BC5", &areatex, (160, 560));
+	compare::<BC4>("BC4", &areatex[.. 160 * 560], (160, 560));
+}
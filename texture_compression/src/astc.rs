@@ -0,0 +1,513 @@
+// ASTC LDR Compression Algorithm
+// Emits a single block layout for every footprint: one partition, color endpoint mode 12(LDR RGBA direct),
+// and a 4x4 weight grid quantized to 16 levels(infilled over larger footprints). Flat blocks become void-extent blocks.
+// Bit layouts and unquantization follow the Khronos Data Format Specification, section "ASTC Compressed Texture Image Formats"
+
+use super::{CompressionAlgorithm, TextureFormat, ImageView, CompressionOptions};
+use blocks;
+
+/// Weight grid extent
+const GRID_LEN: usize = 4;
+/// Block mode: 4x4 weight grid(A = 2, B = 0), weight range 0..15(R = 4, H = 1), single plane
+const BLOCK_MODE: u32 = (1 << 9) | (2 << 5) | 0b10;
+/// Color endpoint mode: LDR RGBA, direct
+const CEM_LDR_RGBA_DIRECT: u32 = 12;
+/// Low bits of a void-extent block: LDR, without extent coordinates
+const VOID_EXTENT_HEADER: u128 = 0xFFFF_FFFF_FFFF_FDFC;
+const COLOR_OFFSET: u32 = 17;
+
+/// Integer sequence encoding ranges in ascending order: (bits, trits, quints)
+static ISE_RANGES: [(u32, u32, u32); 21] = [
+	(1, 0, 0), (0, 1, 0), (2, 0, 0), (0, 0, 1), (1, 1, 0), (3, 0, 0), (1, 0, 1), (2, 1, 0), (4, 0, 0), (2, 0, 1), (3, 1, 0),
+	(5, 0, 0), (3, 0, 1), (4, 1, 0), (6, 0, 0), (4, 0, 1), (5, 1, 0), (7, 0, 0), (5, 0, 1), (6, 1, 0), (8, 0, 0)
+];
+/// Range index of the weights(0..15)
+const WEIGHT_RANGE: usize = 8;
+fn ise_bit_count(count: u32, range: usize) -> u32
+{
+	let (bits, trits, quints) = ISE_RANGES[range];
+	count * bits + trits * (count * 8 + 4) / 5 + quints * (count * 7 + 2) / 3
+}
+/// Range of the color endpoints: the largest one fitting in the remaining bits(0..5 at least)
+fn color_range(color_count: u32, available_bits: u32) -> usize
+{
+	(4 .. ISE_RANGES.len()).rev().find(|&r| ise_bit_count(color_count, r) <= available_bits).expect("too few bits for color endpoints")
+}
+
+fn bit(v: u32, n: u32) -> u32 { (v >> n) & 1 }
+fn decode_trits(t: u32) -> [u32; 5]
+{
+	let (c, t4, t3) = if (t >> 2) & 0b111 == 0b111 { (((t >> 5) << 2) | (t & 0b11), 2, 2) }
+		else if (t >> 5) & 0b11 == 0b11 { (t & 0x1F, 2, bit(t, 7)) }
+		else { (t & 0x1F, bit(t, 7), (t >> 5) & 0b11) };
+	let (t2, t1, t0) = if c & 0b11 == 0b11 { (2, bit(c, 4), (bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1)) }
+		else if (c >> 2) & 0b11 == 0b11 { (2, 2, c & 0b11) }
+		else { (bit(c, 4), (c >> 2) & 0b11, (bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1)) };
+	[t0, t1, t2, t3, t4]
+}
+fn decode_quints(q: u32) -> [u32; 3]
+{
+	if (q >> 1) & 0b11 == 0b11 && (q >> 5) & 0b11 == 0
+	{
+		[4, 4, (bit(q, 0) << 2) | ((bit(q, 4) & !bit(q, 0) & 1) << 1) | (bit(q, 3) & !bit(q, 0) & 1)]
+	}
+	else
+	{
+		let (q2, c) = if (q >> 1) & 0b11 == 0b11 { (4, (((q >> 3) & 0b11) << 3) | ((!(q >> 5) & 0b11) << 1) | bit(q, 0)) }
+			else { ((q >> 5) & 0b11, q & 0x1F) };
+		if c & 0b111 == 0b101 { [c >> 3, 4, q2] } else { [c & 0b111, c >> 3, q2] }
+	}
+}
+
+/// Bit stream in the ASTC bit order(LSB first)
+struct BitStream { bits: u128, position: u32 }
+impl BitStream
+{
+	fn new(bits: u128) -> Self { BitStream { bits, position: 0 } }
+	fn write(&mut self, value: u32, count: u32)
+	{
+		self.bits |= ((value as u128) & ((1 << count) - 1)) << self.position;
+		self.position += count;
+	}
+	fn read(&mut self, count: u32) -> u32
+	{
+		let v = if self.position >= 128 { 0 } else { ((self.bits >> self.position) & ((1 << count) - 1)) as u32 };
+		self.position += count;
+		v
+	}
+}
+// Bit counts of the packed trit(T[1:0], T[3:2], T[4], T[6:5], T[7]) and quint(Q[2:0], Q[4:3], Q[6:5]) block parts following each value
+static TRIT_PARTS: [u32; 5] = [2, 2, 1, 2, 1];
+static QUINT_PARTS: [u32; 3] = [3, 2, 2];
+/// Integer sequence encoding of the values
+fn ise_encode(stream: &mut BitStream, values: &[u32], range: usize)
+{
+	let (bits, trits, quints) = ISE_RANGES[range];
+	if trits + quints == 0
+	{
+		for &v in values { stream.write(v, bits); }
+		return;
+	}
+	let (group, parts): (usize, &[u32]) = if trits > 0 { (5, &TRIT_PARTS) } else { (3, &QUINT_PARTS) };
+	for chunk in values.chunks(group)
+	{
+		// packed bits omitted by a partial group must be zero, so find the encoding among those without them
+		let written = parts[.. chunk.len()].iter().sum::<u32>();
+		let mut digits = [0; 5];
+		for (d, &v) in digits.iter_mut().zip(chunk) { *d = v >> bits; }
+		let packed = (0 .. 1 << parts.iter().sum::<u32>()).filter(|&p| p >> written == 0).find(|&p|
+		{
+			if trits > 0 { decode_trits(p) == digits } else { decode_quints(p)[..] == digits[.. 3] }
+		}).unwrap();
+		let mut shift = 0;
+		for (&v, &part) in chunk.iter().zip(parts)
+		{
+			stream.write(v, bits);
+			stream.write(packed >> shift, part);
+			shift += part;
+		}
+	}
+}
+fn ise_decode(stream: &mut BitStream, count: usize, range: usize) -> Vec<u32>
+{
+	let (bits, trits, quints) = ISE_RANGES[range];
+	if trits + quints == 0 { return (0 .. count).map(|_| stream.read(bits)).collect(); }
+	let (group, parts): (usize, &[u32]) = if trits > 0 { (5, &TRIT_PARTS) } else { (3, &QUINT_PARTS) };
+	let mut values = Vec::with_capacity(count);
+	while values.len() < count
+	{
+		let n = std::cmp::min(group, count - values.len());
+		let (mut low, mut packed, mut shift) = (Vec::with_capacity(n), 0, 0);
+		for &part in &parts[.. n]
+		{
+			low.push(stream.read(bits));
+			packed |= stream.read(part) << shift;
+			shift += part;
+		}
+		let digits = if trits > 0 { decode_trits(packed).to_vec() } else { decode_quints(packed).to_vec() };
+		values.extend(low.iter().zip(digits).map(|(&m, d)| (d << bits) | m));
+	}
+	values
+}
+
+/// Unquantizes a color endpoint value to 0..255(ranges from 0..5)
+fn unquantize_color(v: u32, range: usize) -> u32
+{
+	let (bits, trits, quints) = ISE_RANGES[range];
+	if trits + quints == 0
+	{
+		// bit replication
+		let mut r = 0;
+		let mut filled = 0;
+		while filled < 8 { r = (r << bits) | v; filled += bits; }
+		return r >> (filled - 8);
+	}
+	let m = v & ((1 << bits) - 1);
+	let (a, b, c, d, e, f) = (bit(m, 0), bit(m, 1), bit(m, 2), bit(m, 3), bit(m, 4), bit(m, 5));
+	let (bv, cv) = if trits > 0
+	{
+		match bits
+		{
+			1 => (0, 204),
+			2 => ((b << 8) | (b << 4) | (b << 2) | (b << 1), 93),
+			3 => ((c << 8) | (b << 7) | (c << 3) | (b << 2) | (c << 1) | b, 44),
+			4 => ((d << 8) | (c << 7) | (b << 6) | (d << 2) | (c << 1) | b, 22),
+			5 => ((e << 8) | (d << 7) | (c << 6) | (b << 5) | e, 11),
+			6 => ((f << 8) | (e << 7) | (d << 6) | (c << 5) | (b << 4), 5),
+			_ => unreachable!()
+		}
+	}
+	else
+	{
+		match bits
+		{
+			1 => (0, 113),
+			2 => ((b << 8) | (b << 3) | (b << 2), 54),
+			3 => ((c << 8) | (b << 7) | (c << 2) | (b << 1) | c, 26),
+			4 => ((d << 8) | (c << 7) | (b << 6) | (d << 1) | c, 13),
+			5 => ((e << 8) | (d << 7) | (c << 6) | (b << 5), 6),
+			_ => unreachable!()
+		}
+	};
+	let a9 = if a == 1 { 0x1FF } else { 0 };
+	let t = ((v >> bits) * cv + bv) ^ a9;
+	(a9 & 0x80) | (t >> 2)
+}
+/// Nearest quantized value for a color endpoint
+fn quantize_color(v: u8, range: usize) -> u32
+{
+	let (bits, trits, quints) = ISE_RANGES[range];
+	let levels = (1 << bits) * [1, 3][trits as usize] * [1, 5][quints as usize];
+	(0 .. levels).min_by_key(|&q| (unquantize_color(q, range) as i32 - v as i32).abs()).unwrap()
+}
+/// Unquantizes a 4-bit weight to 0..64
+fn unquantize_weight(q: u32) -> u32
+{
+	let w = (q << 2) | (q >> 2);
+	if w > 32 { w + 1 } else { w }
+}
+
+/// Bilinear infill of the weight grid over the block: returns, for each texel, (grid index, factor / 16) contributions
+fn infill_factors(extent: usize) -> Vec<Vec<(usize, u32)>>
+{
+	let d = ((1024 + extent / 2) / (extent - 1)) as u32;
+	(0 .. extent * extent).map(|n|
+	{
+		let (s, t) = ((n % extent) as u32, (n / extent) as u32);
+		let (gs, gt) = ((d * s * (GRID_LEN as u32 - 1) + 32) >> 6, (d * t * (GRID_LEN as u32 - 1) + 32) >> 6);
+		let (js, fs, jt, ft) = ((gs >> 4) as usize, gs & 0xF, (gt >> 4) as usize, gt & 0xF);
+		let w11 = (fs * ft + 8) >> 4;
+		let v0 = js + jt * GRID_LEN;
+		vec![(v0, 16 + w11 - fs - ft), (v0 + 1, fs - w11), (v0 + GRID_LEN, ft - w11), (v0 + GRID_LEN + 1, w11)]
+			.into_iter().filter(|&(_, f)| f > 0).collect()
+	}).collect()
+}
+fn infill(grid: &[u32], factors: &[Vec<(usize, u32)>]) -> Vec<u32>
+{
+	factors.iter().map(|f| (f.iter().map(|&(g, w)| grid[g] * w).sum::<u32>() + 8) >> 4).collect()
+}
+
+/// Endpoints along the principal axis of the texels
+/// Weights of the error: per channel, and per texel for the color(the alpha for alpha-weighted error)
+struct ErrorMetric { channels: [f32; 4], alpha_weighted: bool }
+impl ErrorMetric
+{
+	fn new(options: &CompressionOptions) -> Self
+	{
+		let w = &options.channel_weights;
+		ErrorMetric { channels: [w[0] as f32, w[1] as f32, w[2] as f32, w[3] as f32], alpha_weighted: options.alpha_weighted }
+	}
+	/// Whether the error differs from the plain squared error
+	fn weighted(&self) -> bool { self.alpha_weighted || self.channels != [1.0; 4] }
+	/// Channel weights of the squared error of a texel
+	fn texel(&self, t: &[u8]) -> [f32; 4]
+	{
+		let color = if self.alpha_weighted { t[3] as f32 / 255.0 } else { 1.0 };
+		[self.channels[0] * color, self.channels[1] * color, self.channels[2] * color, self.channels[3]]
+	}
+}
+
+// fitted in the space scaled by the channel weights
+// (over all texels alike even if alpha-weighted: a single line cannot keep both the alpha of transparent texels and the colors of
+// opaque ones apart, so the alpha weighting applies to the weights of the texels)
+fn principal_endpoints(texels: &[u8], metric: &ErrorMetric) -> ([f32; 4], [f32; 4])
+{
+	let scale = [metric.channels[0].sqrt(), metric.channels[1].sqrt(), metric.channels[2].sqrt(), metric.channels[3].sqrt()];
+	let points = texels.chunks(4).map(|t| [t[0] as f32 * scale[0], t[1] as f32 * scale[1], t[2] as f32 * scale[2], t[3] as f32 * scale[3]])
+		.collect::<Vec<_>>();
+	let count = points.len() as f32;
+	let mut mean = [0.0f32; 4];
+	for p in &points { for c in 0 .. 4 { mean[c] += p[c] / count; } }
+	let mut covariance = [[0.0f32; 4]; 4];
+	for p in &points
+	{
+		for i in 0 .. 4 { for j in 0 .. 4 { covariance[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]); } }
+	}
+	// power iteration from the dominant diagonal
+	let mut axis = [1.0f32; 4];
+	for _ in 0 .. 8
+	{
+		let mut next = [0.0f32; 4];
+		for (i, n) in next.iter_mut().enumerate() { *n = (0 .. 4).map(|j| covariance[i][j] * axis[j]).sum(); }
+		let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+		if length < 1.0e-6 { break; }
+		for (a, n) in axis.iter_mut().zip(next.iter()) { *a = n / length; }
+	}
+	let projections = points.iter().map(|p| (0 .. 4).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>());
+	let (lo, hi) = projections.fold((0.0f32, 0.0f32), |(lo, hi), p| (lo.min(p), hi.max(p)));
+
+	// back to the texel space(a channel without weight takes the average)
+	let average = |c: usize| texels.chunks(4).map(|t| t[c] as f32).sum::<f32>() / count;
+	let unscale = |p: f32, c: usize| if scale[c] > 0.0 { p / scale[c] } else { average(c) };
+	let endpoint = |projection: f32|
+	{
+		let mut e = [0.0f32; 4];
+		for c in 0 .. 4 { e[c] = unscale(mean[c] + axis[c] * projection, c).clamp(0.0, 255.0); }
+		e
+	};
+	(endpoint(lo), endpoint(hi))
+}
+
+/// Encodes a block from row-major RGBA texels
+fn encode_block(texels: &[u8], extent: usize, metric: &ErrorMetric) -> u128
+{
+	if texels.chunks(4).all(|t| t == &texels[.. 4])
+	{
+		let color = texels[.. 4].iter().enumerate().fold(0u128, |acc, (c, &v)| acc | ((v as u128 * 257) << (64 + 16 * c)));
+		return VOID_EXTENT_HEADER | color;
+	}
+
+	let weight_bits = ise_bit_count((GRID_LEN * GRID_LEN) as u32, WEIGHT_RANGE);
+	let range = color_range(8, 128 - COLOR_OFFSET - weight_bits);
+	let (e0, e1) = principal_endpoints(texels, metric);
+	let mut quantized = [0u32; 8];
+	for c in 0 .. 4
+	{
+		quantized[c * 2] = quantize_color(e0[c].round() as u8, range);
+		quantized[c * 2 + 1] = quantize_color(e1[c].round() as u8, range);
+	}
+	let unquantized = quantized.iter().map(|&q| unquantize_color(q, range) as f32).collect::<Vec<_>>();
+	// s1 < s0 would select blue contraction, so the endpoints are swapped(and the weights inverted) instead
+	let swapped = unquantized[1] + unquantized[3] + unquantized[5] < unquantized[0] + unquantized[2] + unquantized[4];
+	if swapped { for c in 0 .. 4 { quantized.swap(c * 2, c * 2 + 1); } }
+
+	// ideal weights of the texels(with the importance of the texels along the axis),
+	// then gathered into the grid through the infill factors
+	let axis = (0 .. 4).map(|c| unquantized[c * 2 + 1] - unquantized[c * 2]).collect::<Vec<_>>();
+	let ideal = texels.chunks(4).map(|t|
+	{
+		let k = metric.texel(t);
+		let axis_length2 = (0 .. 4).map(|c| k[c] * axis[c] * axis[c]).sum::<f32>().max(1.0e-6);
+		let w = (0 .. 4).map(|c| k[c] * (t[c] as f32 - unquantized[c * 2]) * axis[c]).sum::<f32>() / axis_length2;
+		(w.clamp(0.0, 1.0) * 64.0, if metric.weighted() { axis_length2 } else { 1.0 })
+	}).collect::<Vec<_>>();
+	let mut sums = [(0.0f32, 0.0f32); GRID_LEN * GRID_LEN];
+	for (factors, &(w, importance)) in infill_factors(extent).iter().zip(ideal.iter())
+	{
+		for &(g, f) in factors { sums[g].0 += w * f as f32 * importance; sums[g].1 += f as f32 * importance; }
+	}
+	let weights = sums.iter().map(|&(sum, total)|
+	{
+		let w = if total > 0.0 { sum / total } else { 0.0 };
+		let error = |q: u32| (unquantize_weight(q) as f32 - w).abs();
+		let q = (0 .. 16).min_by(|&a, &b| error(a).partial_cmp(&error(b)).unwrap()).unwrap();
+		if swapped { 15 - q } else { q }
+	}).collect::<Vec<_>>();
+
+	let mut header = BitStream::new(0);
+	header.write(BLOCK_MODE, 11);
+	header.write(0, 2);
+	header.write(CEM_LDR_RGBA_DIRECT, 4);
+	ise_encode(&mut header, &quantized, range);
+	let mut weight_stream = BitStream::new(0);
+	ise_encode(&mut weight_stream, &weights, WEIGHT_RANGE);
+	header.bits | weight_stream.bits.reverse_bits()
+}
+/// Decodes a block emitted by `encode_block` into row-major RGBA texels(LDR, unorm8 decode mode)
+fn decode_block(block: u128, extent: usize, texels: &mut [u8])
+{
+	if block & 0x1FF == VOID_EXTENT_HEADER & 0x1FF
+	{
+		for t in texels.chunks_mut(4)
+		{
+			for (c, v) in t.iter_mut().enumerate() { *v = (block >> (64 + 16 * c + 8)) as u8; }
+		}
+		return;
+	}
+	let mut stream = BitStream::new(block);
+	let (mode, partitions, cem) = (stream.read(11), stream.read(2), stream.read(4));
+	assert!(mode == BLOCK_MODE && partitions == 0 && cem == CEM_LDR_RGBA_DIRECT, "unsupported ASTC block layout");
+
+	let weight_bits = ise_bit_count((GRID_LEN * GRID_LEN) as u32, WEIGHT_RANGE);
+	let range = color_range(8, 128 - COLOR_OFFSET - weight_bits);
+	let v = ise_decode(&mut stream, 8, range).into_iter().map(|q| unquantize_color(q, range)).collect::<Vec<_>>();
+	let (e0, e1) = if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] { ([v[0], v[2], v[4], v[6]], [v[1], v[3], v[5], v[7]]) }
+		else
+		{
+			let blue_contract = |r: u32, g: u32, b: u32, a: u32| [(r + b) >> 1, (g + b) >> 1, b, a];
+			(blue_contract(v[1], v[3], v[5], v[7]), blue_contract(v[0], v[2], v[4], v[6]))
+		};
+	let grid = ise_decode(&mut BitStream::new(block.reverse_bits()), GRID_LEN * GRID_LEN, WEIGHT_RANGE)
+		.into_iter().map(unquantize_weight).collect::<Vec<_>>();
+	for (t, w) in texels.chunks_mut(4).zip(infill(&grid, &infill_factors(extent)))
+	{
+		for c in 0 .. 4 { t[c] = (((e0[c] * 257 * (64 - w) + e1[c] * 257 * w + 32) >> 6) >> 8) as u8; }
+	}
+}
+
+// (the encoder has a single principal-axis fit, so the quality does not change the output)
+fn compress_with<A: CompressionAlgorithm>(image: &ImageView, options: &CompressionOptions) -> Vec<u8>
+{
+	let (source, size) = (image.pack(&options.swizzle), image.size);
+	assert!(size.0 > 0 && size.1 > 0);					// at least a texel
+	let metric = ErrorMetric::new(options);
+
+	let extent = A::format().block_extent();
+	let encoder = |bx, by, dest: &mut [u8]|
+	{
+		dest.copy_from_slice(&encode_block(&blocks::gather_block(&source, size, 4, (bx, by), extent), extent.0, &metric).to_le_bytes())
+	};
+	if options.parallel { blocks::compress_blocks_parallel(size, extent, 16, encoder) }
+	else { blocks::compress_blocks(size, extent, 16, encoder) }
+}
+fn decompress_with<A: CompressionAlgorithm>(compressed: &[u8], size: (usize, usize)) -> Vec<u8>
+{
+	let extent = A::format().block_extent();
+	blocks::decompress_blocks(compressed, size, extent, 16, 4, |block, texels|
+	{
+		let mut bytes = [0u8; 16];
+		bytes.copy_from_slice(block);
+		decode_block(u128::from_le_bytes(bytes), extent.0, texels)
+	})
+}
+
+/// ASTC 4x4 LDR(8 bpp): 4-channel source
+pub enum ASTC4x4 {}
+/// ASTC 6x6 LDR(3.56 bpp): 4-channel source
+pub enum ASTC6x6 {}
+/// ASTC 8x8 LDR(2 bpp): 4-channel source
+pub enum ASTC8x8 {}
+macro_rules! ImplAstcAlgorithm
+{
+	($t: ty, $format: expr) =>
+	{
+		impl CompressionAlgorithm for $t
+		{
+			fn source_channels() -> usize { 4 }
+			fn format() -> TextureFormat { $format }
+			fn version() -> u32 { 1 }
+			fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8> { compress_with::<Self>(image, options) }
+			fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8> { decompress_with::<Self>(compressed, size) }
+		}
+	}
+}
+ImplAstcAlgorithm!(ASTC4x4, TextureFormat::ASTC4x4Unorm);
+ImplAstcAlgorithm!(ASTC6x6, TextureFormat::ASTC6x6Unorm);
+ImplAstcAlgorithm!(ASTC8x8, TextureFormat::ASTC8x8Unorm);
+
+#[cfg(test)]
+mod testings
+{
+	use {CompressionAlgorithm, CompressionOptions, ImageView};
+	use metrics::{psnr, rmse};
+
+	fn default_metric() -> super::ErrorMetric { super::ErrorMetric::new(&CompressionOptions::default()) }
+
+	#[test] fn integer_sequence_roundtrip()
+	{
+		for range in 0 .. super::ISE_RANGES.len()
+		{
+			let (bits, trits, quints) = super::ISE_RANGES[range];
+			let levels = (1 << bits) * [1, 3][trits as usize] * [1, 5][quints as usize];
+			for count in 1 .. 12
+			{
+				let values = (0 .. count).map(|n| (n * 7 + range as u32) % levels).collect::<Vec<_>>();
+				let mut stream = super::BitStream::new(0);
+				super::ise_encode(&mut stream, &values, range);
+				assert_eq!(stream.position, super::ise_bit_count(count, range));
+				assert_eq!(super::ise_decode(&mut super::BitStream::new(stream.bits), count as usize, range), values);
+			}
+		}
+	}
+	#[test] fn unquantization_matches_spec_tables()
+	{
+		// color range 0..47(trits, 4 bits): spot values from the specification's unquantization table
+		let range = super::color_range(8, 128 - super::COLOR_OFFSET - 64);
+		assert_eq!(super::ISE_RANGES[range], (4, 1, 0));
+		let table = (0 .. 48).map(|q| super::unquantize_color(q, range)).collect::<Vec<_>>();
+		assert_eq!(&table[.. 4], &[0, 255, 16, 239]);
+		assert_eq!((0 .. 48).filter(|&q| super::unquantize_color(q, range) == 0).count(), 1);
+		assert_eq!((0 .. 16).map(super::unquantize_weight).collect::<Vec<_>>(),
+			vec![0, 4, 8, 12, 17, 21, 25, 29, 35, 39, 43, 47, 52, 56, 60, 64]);
+	}
+	#[test] fn block_layout_is_bit_exact()
+	{
+		let texels = (0 .. 16).flat_map(|n| vec![(n * 16) as u8, 255 - (n * 16) as u8, 64, 255]).collect::<Vec<_>>();
+		let block = super::encode_block(&texels, 4, &default_metric());
+		assert_eq!(block & 0x7FF, super::BLOCK_MODE as u128);
+		assert_eq!((block >> 11) & 0b11, 0);
+		assert_eq!((block >> 13) & 0xF, 12);
+		// color(45 bits) and weight(64 bits) streams don't overlap
+		assert_eq!((block >> (17 + 45)) & 0b11, 0);
+		let flat = [10u8, 20, 30, 40].iter().cycle().take(64).cloned().collect::<Vec<_>>();
+		let void_extent = super::encode_block(&flat, 4, &default_metric());
+		assert_eq!(void_extent, 0x2828_1E1E_1414_0A0A_FFFF_FFFF_FFFF_FDFC);
+		let mut decoded = [0u8; 64];
+		super::decode_block(void_extent, 4, &mut decoded);
+		assert_eq!(&decoded[..], &flat[..]);
+
+		// color values 0, 15, 15, 0, 5, 5, 15, 15 with every trit zero(unquantized to 0, 142, 142, 0, 223, 223, 142, 142),
+		// texel n has weight n(unquantized to 0, 21 and 35 at 0, 5 and 8)
+		// (expected texels worked out by hand with the unquantization and interpolation formulas of the specification)
+		super::decode_block(0x084C_2A6E_195D_3B7F_1E78_A501_E781_8242, 4, &mut decoded);
+		assert_eq!(&decoded[.. 4], &[0, 142, 223, 142]);
+		assert_eq!(&decoded[5 * 4 .. 6 * 4], &[46, 95, 223, 142]);
+		assert_eq!(&decoded[8 * 4 .. 9 * 4], &[77, 64, 223, 142]);
+		assert_eq!(&decoded[15 * 4 ..], &[142, 0, 223, 142]);
+	}
+	#[test] fn footprints_roundtrip_within_tolerance()
+	{
+		fn check<A: CompressionAlgorithm>(size: (usize, usize), min_psnr: f64)
+		{
+			let source = (0 .. size.0 * size.1 * 4).map(|n|
+			{
+				let (x, y, c) = ((n / 4) % size.0, (n / 4) / size.0, n % 4);
+				if c == 3 { 255 - (y * 4) as u8 } else { (x * 100 / size.0 + y * 4 + c * 30) as u8 }
+			}).collect::<Vec<_>>();
+			let compressed = A::compress(&source, size);
+			assert_eq!(compressed.len(), A::format().level_length(size));
+			assert_eq!(A::compress_parallel(&source, size), compressed);
+			for p in psnr(&source, &A::decompress(&compressed, size), size, 4) { assert!(p > min_psnr, "{:?}: PSNR {}", A::format(), p); }
+		}
+		check::<super::ASTC4x4>((21, 14), 35.0);
+		check::<super::ASTC6x6>((21, 14), 32.0);
+		check::<super::ASTC8x8>((21, 14), 30.0);
+	}
+	#[test] fn error_weighting_options()
+	{
+		// opaque gradient with noisy colors under the transparent texels
+		let size = (16, 16);
+		let rgba = (0 .. size.0 * size.1).flat_map(|n|
+		{
+			let (x, y) = (n % size.0, n / size.0);
+			if (x + y) % 3 == 0 { vec![(n * 7919 % 256) as u8, (n * 104729 % 256) as u8, (n * 31 % 256) as u8, 0] }
+			else { vec![(x * 12) as u8, (y * 12) as u8, 128, 255] }
+		}).collect::<Vec<_>>();
+		let image = ImageView::new(&rgba[..], size, 4);
+		let decode = |options: &CompressionOptions| super::ASTC8x8::decompress(&super::ASTC8x8::compress_image(&image, options), size);
+		let opaque_error = |decoded: &[u8]| rgba.chunks(4).zip(decoded.chunks(4)).filter(|&(s, _)| s[3] == 255)
+			.map(|(s, d)| (0 .. 3).map(|c| (s[c] as i32 - d[c] as i32).pow(2)).sum::<i32>()).sum::<i32>();
+		let weighted = decode(&CompressionOptions { alpha_weighted: true, .. CompressionOptions::default() });
+		assert!(opaque_error(&weighted) < opaque_error(&decode(&CompressionOptions::default())));
+
+		// a heavier channel is fitted at the expense of the others
+		let noisy = (0 .. size.0 * size.1 * 4).map(|n| ((n * 7919 + n / 64 * 31) % 256) as u8).collect::<Vec<_>>();
+		let channel_error = |weights: [u32; 4]|
+		{
+			let options = CompressionOptions { channel_weights: weights, .. CompressionOptions::default() };
+			rmse(&noisy, &super::ASTC4x4::decompress(&super::ASTC4x4::compress_image(&ImageView::new(&noisy[..], size, 4), &options), size), size, 4)
+		};
+		let (uniform, green) = (channel_error([1, 1, 1, 1]), channel_error([1, 16, 1, 1]));
+		assert!(green[1] < uniform[1], "{:?} {:?}", uniform, green);
+	}
+}
@@ -0,0 +1,116 @@
+// Atlas and Array Compression: each entry is compressed on its own, so no block straddles two entries
+// (edge blocks of an entry are padded by repeating its own border, never with texels of a neighbor)
+
+use super::{CompressionAlgorithm, TextureFormat, ImageView, CompressionOptions};
+
+/// Rectangle of an entry in an atlas, in texels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasRegion
+{
+	pub origin: (usize, usize),
+	pub size: (usize, usize)
+}
+
+/// An entry in the compressed batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressedEntry
+{
+	/// Extent of the entry, in texels
+	pub size: (usize, usize),
+	/// Byte offset of the entry in the batch(always a multiple of the block size)
+	pub offset: usize,
+	/// Byte length of the entry
+	pub length: usize
+}
+
+/// Compressed entries concatenated in the given order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedBatch
+{
+	pub format: TextureFormat,
+	pub entries: Vec<CompressedEntry>,
+	pub data: Vec<u8>
+}
+impl CompressedBatch
+{
+	/// Bytes of the entry
+	pub fn entry_data(&self, index: usize) -> &[u8]
+	{
+		let e = &self.entries[index];
+		&self.data[e.offset .. e.offset + e.length]
+	}
+	/// Extent shared by every entry, if any: the data is then laid out as the layers of an array texture
+	pub fn layer_size(&self) -> Option<(usize, usize)>
+	{
+		let size = self.entries.first().map(|e| e.size);
+		if self.entries.iter().all(|e| Some(e.size) == size) { size } else { None }
+	}
+}
+
+fn compress_entries<Algorithm: CompressionAlgorithm>(images: &[ImageView], options: &CompressionOptions) -> CompressedBatch
+{
+	let mut entries = Vec::with_capacity(images.len());
+	let mut data = Vec::new();
+	for image in images
+	{
+		let bytes = Algorithm::compress_image(image, options);
+		entries.push(CompressedEntry { size: image.size, offset: data.len(), length: bytes.len() });
+		data.extend_from_slice(&bytes);
+	}
+	CompressedBatch { format: Algorithm::format(), entries, data }
+}
+/// Compresses the regions of an atlas separately, in the given order
+pub fn compress_atlas<Algorithm: CompressionAlgorithm>(atlas: &ImageView, regions: &[AtlasRegion], options: &CompressionOptions) -> CompressedBatch
+{
+	let views = regions.iter().map(|r| atlas.region(r.origin, r.size)).collect::<Vec<_>>();
+	compress_entries::<Algorithm>(&views, options)
+}
+/// Compresses the layers of an array texture(all of the same extent)
+pub fn compress_layers<Algorithm: CompressionAlgorithm>(layers: &[ImageView], options: &CompressionOptions) -> CompressedBatch
+{
+	assert!(layers.iter().all(|l| l.size == layers[0].size), "layers of an array texture must share the extent");
+	compress_entries::<Algorithm>(layers, options)
+}
+
+#[cfg(test)]
+mod testings
+{
+	use {CompressionAlgorithm, ImageView, CompressionOptions, BC4};
+	use super::AtlasRegion;
+
+	// two 6x4 sprites side by side, each of two values: the second begins in the middle of a block column
+	fn sprites() -> Vec<u8> { (0 .. 12 * 4).map(|n| if n % 12 < 6 { 60 } else if n / 12 % 2 == 0 { 200 } else { 255 }).collect() }
+
+	#[test] fn entries_do_not_bleed()
+	{
+		let atlas = sprites();
+		let image = ImageView::new(&atlas[..], (12, 4), 1);
+		let regions = [AtlasRegion { origin: (0, 0), size: (6, 4) }, AtlasRegion { origin: (6, 0), size: (6, 4) }];
+		let batch = super::compress_atlas::<BC4>(&image, &regions, &CompressionOptions::default());
+		assert_eq!(batch.entries.iter().map(|e| (e.offset, e.length)).collect::<Vec<_>>(), vec![(0, 16), (16, 16)]);
+		assert_eq!(batch.layer_size(), Some((6, 4)));
+		// BC4 reproduces a block of two values exactly
+		assert!(BC4::decompress(batch.entry_data(0), (6, 4)).iter().all(|&v| v == 60));
+		assert_eq!(BC4::decompress(batch.entry_data(1), (6, 4)), image.region((6, 0), (6, 4)).pack(&[0]));
+		// as a single image, the middle block straddles both sprites: its encoding depends on the second one
+		// (this block is still exact, since the 6-step codec of BC4 has 60, 200 and the explicit 255)
+		let changed = atlas.iter().map(|&v| if v == 200 { 180 } else { v }).collect::<Vec<_>>();
+		let (whole, whole_changed) = (BC4::compress(&atlas, (12, 4)), BC4::compress(&changed, (12, 4)));
+		assert_ne!(whole[8 .. 16], whole_changed[8 .. 16]);
+		let entries_changed = super::compress_atlas::<BC4>(&ImageView::new(&changed[..], (12, 4), 1), &regions, &CompressionOptions::default());
+		assert_eq!(entries_changed.entry_data(0), batch.entry_data(0));
+	}
+	#[test] fn layers_are_laid_out_in_order()
+	{
+		let layers = (0 .. 3).map(|n| (0 .. 16 * 16).map(|i| (i * (n + 1)) as u8).collect::<Vec<_>>()).collect::<Vec<_>>();
+		let views = layers.iter().map(|l| ImageView::new(&l[..], (16, 16), 1)).collect::<Vec<_>>();
+		let batch = super::compress_layers::<BC4>(&views, &CompressionOptions::default());
+		assert_eq!(batch.entries.iter().map(|e| e.offset).collect::<Vec<_>>(), vec![0, 128, 256]);
+		for (n, l) in layers.iter().enumerate() { assert_eq!(batch.entry_data(n), &BC4::compress(l, (16, 16))[..]); }
+	}
+	#[test] #[should_panic] fn layers_must_share_the_extent()
+	{
+		let (a, b) = ([0u8; 16], [0u8; 32]);
+		super::compress_layers::<BC4>(&[ImageView::new(&a[..], (4, 4), 1), ImageView::new(&b[..], (8, 4), 1)], &CompressionOptions::default());
+	}
+}
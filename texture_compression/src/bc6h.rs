@@ -0,0 +1,374 @@
+// BC6H Compression Algorithm(HDR RGB half-float, unsigned and signed)
+// Emits mode 11 only: one region, 10-bit endpoints and 4-bit indices. Endpoints are fitted in the integer domain
+// the hardware interpolates in, so the palette is linear there(and roughly logarithmic in the half-float values).
+// Unquantization and interpolation follow the Direct3D 11.3 functional specification, section "BC6H Format"
+
+use std;
+use half::f16;
+use super::{CompressionAlgorithm, TextureFormat, ImageView, CompressionOptions, Quality};
+use blocks;
+
+/// Mode 11 in its 5-bit mode field
+const MODE_11: u128 = 0x03;
+const ENDPOINT_BITS: u32 = 10;
+/// Interpolation weights of the 4-bit indices
+static WEIGHTS: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Half-float bits into the interpolation domain(inverse of `finish_unquantize`).
+/// NaNs become zero, infinities the largest finite value, and negative values zero in the unsigned format
+fn to_integer(h: u16, signed: bool) -> i32
+{
+	let magnitude = if h & 0x7FFF > 0x7C00 { 0 } else { std::cmp::min(h & 0x7FFF, 0x7BFF) as i32 };
+	let negative = h & 0x8000 != 0;
+	if signed
+	{
+		let v = (magnitude * 32 + 30) / 31;
+		if negative { -v } else { v }
+	}
+	else if negative { 0 } else { (magnitude * 64 + 30) / 31 }
+}
+/// Interpolated value back into half-float bits
+fn finish_unquantize(v: i32, signed: bool) -> u16
+{
+	if signed
+	{
+		let magnitude = ((v.abs() * 31) >> 5) as u16;
+		if v < 0 { 0x8000 | magnitude } else { magnitude }
+	}
+	else { ((v * 31) >> 6) as u16 }
+}
+/// 10-bit endpoint into the interpolation domain
+fn unquantize(q: i32, signed: bool) -> i32
+{
+	if signed
+	{
+		let m = q.abs();
+		let v = if m == 0 { 0 } else if m >= (1 << (ENDPOINT_BITS - 1)) - 1 { 0x7FFF } else { ((m << 15) + 0x4000) >> (ENDPOINT_BITS - 1) };
+		if q < 0 { -v } else { v }
+	}
+	else if q == 0 { 0 }
+	else if q == (1 << ENDPOINT_BITS) - 1 { 0xFFFF }
+	else { ((q << 16) + 0x8000) >> ENDPOINT_BITS }
+}
+/// Nearest 10-bit endpoint(the signed range is kept symmetric)
+fn quantize(v: i32, signed: bool) -> i32
+{
+	let (lo, hi) = if signed { (-511, 511) } else { (0, 1023) };
+	let guess = ((v - 32) as f32 / 64.0).round() as i32;
+	(guess - 1 .. guess + 2).map(|q| q.max(lo).min(hi)).min_by_key(|&q| (unquantize(q, signed) - v).abs()).unwrap()
+}
+fn interpolate(a: i32, b: i32, w: i32) -> i32 { (a * (64 - w) + b * w + 32) >> 6 }
+fn sign_extend(v: u32, bits: u32) -> i32 { ((v << (32 - bits)) as i32) >> (32 - bits) }
+
+/// Quantized endpoints with the indices and squared error they produce
+struct Fit { endpoints: [[i32; 3]; 2], indices: [usize; 16], error: i64 }
+impl Fit
+{
+	fn new(texels: &[i32], endpoints: [[i32; 3]; 2], signed: bool) -> Self
+	{
+		let mut palette = [[0i32; 3]; 16];
+		for (p, &w) in palette.iter_mut().zip(WEIGHTS.iter())
+		{
+			for c in 0 .. 3 { p[c] = interpolate(unquantize(endpoints[0][c], signed), unquantize(endpoints[1][c], signed), w); }
+		}
+		let mut fit = Fit { endpoints, indices: [0; 16], error: 0 };
+		for (index, t) in fit.indices.iter_mut().zip(texels.chunks(3))
+		{
+			let error = |p: &[i32; 3]| (0 .. 3).map(|c| ((p[c] - t[c]) as i64).pow(2)).sum::<i64>();
+			let (i, e) = palette.iter().map(error).enumerate().min_by_key(|&(_, e)| e).unwrap();
+			*index = i;
+			fit.error += e;
+		}
+		fit
+	}
+	/// Least-squares endpoints for the current indices(None if every texel uses the same weight)
+	fn refined_endpoints(&self, texels: &[i32], signed: bool) -> Option<[[i32; 3]; 2]>
+	{
+		let (mut aa, mut ab, mut bb) = (0.0f64, 0.0f64, 0.0f64);
+		let (mut ax, mut bx) = ([0.0f64; 3], [0.0f64; 3]);
+		for (&index, t) in self.indices.iter().zip(texels.chunks(3))
+		{
+			let w = WEIGHTS[index] as f64 / 64.0;
+			aa += (1.0 - w) * (1.0 - w); ab += (1.0 - w) * w; bb += w * w;
+			for c in 0 .. 3 { ax[c] += (1.0 - w) * t[c] as f64; bx[c] += w * t[c] as f64; }
+		}
+		let det = aa * bb - ab * ab;
+		if det.abs() < 1.0e-9 { return None; }
+		let mut endpoints = [[0i32; 3]; 2];
+		for c in 0 .. 3
+		{
+			endpoints[0][c] = quantize(((ax[c] * bb - bx[c] * ab) / det).round() as i32, signed);
+			endpoints[1][c] = quantize(((bx[c] * aa - ax[c] * ab) / det).round() as i32, signed);
+		}
+		Some(endpoints)
+	}
+}
+
+/// Endpoints along the principal axis of the texels(3 channels, interpolation domain)
+fn principal_endpoints(texels: &[i32]) -> ([f32; 3], [f32; 3])
+{
+	let count = (texels.len() / 3) as f32;
+	let mut mean = [0.0f32; 3];
+	for t in texels.chunks(3) { for c in 0 .. 3 { mean[c] += t[c] as f32 / count; } }
+	let mut covariance = [[0.0f32; 3]; 3];
+	for t in texels.chunks(3)
+	{
+		for i in 0 .. 3 { for j in 0 .. 3 { covariance[i][j] += (t[i] as f32 - mean[i]) * (t[j] as f32 - mean[j]); } }
+	}
+	// power iteration from the row of the largest variance((1, 1, 1) would miss anti-correlated channels)
+	let dominant = (0 .. 3).max_by(|&a, &b| covariance[a][a].partial_cmp(&covariance[b][b]).unwrap()).unwrap();
+	// (normalized up front: products of the raw covariances overflow f32 in bright, high-contrast blocks)
+	let row_length = covariance[dominant].iter().map(|v| v * v).sum::<f32>().sqrt().max(1.0e-6);
+	let mut axis = [0.0f32; 3];
+	for (a, v) in axis.iter_mut().zip(covariance[dominant].iter()) { *a = v / row_length; }
+	for _ in 0 .. 8
+	{
+		let mut next = [0.0f32; 3];
+		for (i, n) in next.iter_mut().enumerate() { *n = (0 .. 3).map(|j| covariance[i][j] * axis[j]).sum(); }
+		let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+		if length < 1.0e-6 { break; }
+		for (a, n) in axis.iter_mut().zip(next.iter()) { *a = n / length; }
+	}
+	let projections = texels.chunks(3).map(|t| (0 .. 3).map(|c| (t[c] as f32 - mean[c]) * axis[c]).sum::<f32>());
+	let (lo, hi) = projections.fold((0.0f32, 0.0f32), |(lo, hi), p| (lo.min(p), hi.max(p)));
+	let mut endpoints = ([0.0f32; 3], [0.0f32; 3]);
+	for c in 0 .. 3
+	{
+		endpoints.0[c] = mean[c] + axis[c] * lo;
+		endpoints.1[c] = mean[c] + axis[c] * hi;
+	}
+	endpoints
+}
+
+/// Encodes a block from row-major RGB texels in the interpolation domain
+fn encode_block(texels: &[i32], signed: bool, quality: Quality) -> u128
+{
+	let (e0, e1) = principal_endpoints(texels);
+	let mut endpoints = [[0i32; 3]; 2];
+	for c in 0 .. 3
+	{
+		endpoints[0][c] = quantize(e0[c].round() as i32, signed);
+		endpoints[1][c] = quantize(e1[c].round() as i32, signed);
+	}
+	let mut best = Fit::new(texels, endpoints, signed);
+	let iterations = match quality { Quality::Fast => 0, Quality::Normal => 2, Quality::High => 8 };
+	for _ in 0 .. iterations
+	{
+		let candidate = match best.refined_endpoints(texels, signed) { Some(e) => Fit::new(texels, e, signed), None => break };
+		if candidate.error >= best.error { break; }
+		best = candidate;
+	}
+	if quality == Quality::High
+	{
+		// nudges each endpoint component by one step while the error decreases
+		for (e, c) in (0 .. 2).flat_map(|e| (0 .. 3).map(move |c| (e, c)))
+		{
+			for &d in &[-1, 1]
+			{
+				loop
+				{
+					let mut endpoints = best.endpoints;
+					endpoints[e][c] = quantize(unquantize(endpoints[e][c] + d, signed), signed);
+					if endpoints[e][c] == best.endpoints[e][c] { break; }
+					let candidate = Fit::new(texels, endpoints, signed);
+					if candidate.error >= best.error { break; }
+					best = candidate;
+				}
+			}
+		}
+	}
+
+	// the anchor(first) index has its most significant bit implied to be zero
+	if best.indices[0] >= 8
+	{
+		best.endpoints.swap(0, 1);
+		for i in best.indices.iter_mut() { *i = 15 - *i; }
+	}
+	let mut block = MODE_11;
+	let mut position = 5;
+	for &v in best.endpoints.iter().flat_map(|e| e.iter())
+	{
+		block |= ((v as u32 & ((1 << ENDPOINT_BITS) - 1)) as u128) << position;
+		position += ENDPOINT_BITS;
+	}
+	for (n, &i) in best.indices.iter().enumerate()
+	{
+		block |= (i as u128) << position;
+		position += if n == 0 { 3 } else { 4 };
+	}
+	block
+}
+/// Decodes a mode 11 block into row-major RGB half-float bits
+fn decode_block(block: u128, signed: bool) -> [[u16; 3]; 16]
+{
+	assert_eq!(block & 0x1F, MODE_11, "unsupported BC6H block mode");
+	let field = |position: u32, bits: u32| ((block >> position) as u32) & ((1 << bits) - 1);
+	let mut endpoints = [[0i32; 3]; 2];
+	for (n, v) in endpoints.iter_mut().flat_map(|e| e.iter_mut()).enumerate()
+	{
+		let bits = field(5 + n as u32 * ENDPOINT_BITS, ENDPOINT_BITS);
+		*v = unquantize(if signed { sign_extend(bits, ENDPOINT_BITS) } else { bits as i32 }, signed);
+	}
+	let mut texels = [[0u16; 3]; 16];
+	let mut position = 5 + 6 * ENDPOINT_BITS;
+	for (n, t) in texels.iter_mut().enumerate()
+	{
+		let bits = if n == 0 { 3 } else { 4 };
+		let w = WEIGHTS[field(position, bits) as usize];
+		position += bits;
+		for c in 0 .. 3 { t[c] = finish_unquantize(interpolate(endpoints[0][c], endpoints[1][c], w), signed); }
+	}
+	texels
+}
+
+fn compress_with(image: &ImageView, options: &CompressionOptions, signed: bool) -> Vec<u8>
+{
+	let size = image.size;
+	assert!(size.0 > 0 && size.1 > 0);					// at least a texel
+	let source = image.pack_f32(&options.swizzle[.. 3]).into_iter().map(|v| to_integer(f16::from_f32(v).as_bits(), signed)).collect::<Vec<_>>();
+
+	let encoder = |bx, by, dest: &mut [u8]|
+	{
+		let block = encode_block(&blocks::gather_block(&source, size, 3, (bx, by), (4, 4)), signed, options.quality);
+		dest.copy_from_slice(&block.to_le_bytes())
+	};
+	if options.parallel { blocks::compress_blocks_parallel(size, (4, 4), 16, encoder) }
+	else { blocks::compress_blocks(size, (4, 4), 16, encoder) }
+}
+/// Decodes into interleaved RGB half-float bits(little-endian bytes per channel)
+fn decompress_bytes(compressed: &[u8], size: (usize, usize), signed: bool) -> Vec<u8>
+{
+	blocks::decompress_blocks(compressed, size, (4, 4), 16, 6, |block, texels|
+	{
+		let mut bytes = [0u8; 16];
+		bytes.copy_from_slice(block);
+		let decoded = decode_block(u128::from_le_bytes(bytes), signed);
+		for (d, t) in texels.chunks_mut(2).zip(decoded.iter().flat_map(|t| t.iter())) { d.copy_from_slice(&t.to_le_bytes()); }
+	})
+}
+
+/// BC6H unsigned half-float(8 bpp): 3-channel source, negative values are clamped to zero
+pub enum BC6HUF16 {}
+/// BC6H signed half-float(8 bpp): 3-channel source
+pub enum BC6HSF16 {}
+macro_rules! ImplBc6hAlgorithm
+{
+	($t: ty, $format: expr, $signed: expr) =>
+	{
+		impl CompressionAlgorithm for $t
+		{
+			fn source_channels() -> usize { 3 }
+			fn format() -> TextureFormat { $format }
+			fn version() -> u32 { 1 }
+			fn float_source() -> bool { true }
+			fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8> { compress_with(image, options, $signed) }
+			/// 8-bit view of the decoded values(clamped to 0.0 ..= 1.0): `decompress_f16` keeps the HDR range
+			fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8>
+			{
+				Self::decompress_f16(compressed, size).into_iter().map(|v| (f32::from(v).clamp(0.0, 1.0) * 255.0 + 0.5) as u8).collect()
+			}
+		}
+		impl $t
+		{
+			/// Decodes compressed blocks into interleaved RGB half-floats
+			pub fn decompress_f16(compressed: &[u8], size: (usize, usize)) -> Vec<f16>
+			{
+				decompress_bytes(compressed, size, $signed).chunks(2).map(|b| f16::from_bits(u16::from_le_bytes([b[0], b[1]]))).collect()
+			}
+		}
+	}
+}
+ImplBc6hAlgorithm!(BC6HUF16, TextureFormat::BC6HUfloat, false);
+ImplBc6hAlgorithm!(BC6HSF16, TextureFormat::BC6HSfloat, true);
+
+#[cfg(test)]
+mod testings
+{
+	use {CompressionAlgorithm, ImageView, CompressionOptions, Quality};
+	use half::f16;
+	use metrics::psnr;
+
+	#[test] fn endpoint_unquantization_matches_spec()
+	{
+		assert_eq!((super::unquantize(0, false), super::unquantize(1, false), super::unquantize(1023, false)), (0, 96, 0xFFFF));
+		assert_eq!((super::unquantize(-511, true), super::unquantize(1, true), super::unquantize(511, true)), (-0x7FFF, 96, 0x7FFF));
+		assert_eq!(super::finish_unquantize(0xFFFF, false), 0x7BFF);
+		assert_eq!(super::finish_unquantize(-0x7FFF, true), 0xFBFF);
+		assert_eq!(super::sign_extend(0x3FF, 10), -1);
+		// every finite half value survives the trip into the interpolation domain
+		for h in (0 .. 0x7C00u16).chain(0x8000 .. 0xFC00)
+		{
+			assert_eq!(super::finish_unquantize(super::to_integer(h, true), true), if h == 0x8000 { 0 } else { h });
+			if h < 0x8000 { assert_eq!(super::finish_unquantize(super::to_integer(h, false), false), h); }
+		}
+	}
+	#[test] fn block_layout_is_bit_exact()
+	{
+		let texels = (0 .. 16).flat_map(|n| vec![n * 2000, 65535 - n * 2000, 30000]).collect::<Vec<_>>();
+		let block = super::encode_block(&texels, false, Quality::Normal);
+		assert_eq!(block & 0x1F, 0x03);
+		let decoded = super::decode_block(block, false);
+		for (t, s) in decoded.iter().zip(texels.chunks(3))
+		{
+			for c in 0 .. 3 { assert!((super::to_integer(t[c], false) - s[c]).abs() <= 640, "{:?} vs {:?}", t, s); }
+		}
+		assert_eq!(super::encode_block(&[0; 48], false, Quality::Normal), 0x03);
+	}
+	#[test] fn blocks_decode_as_specified()
+	{
+		// mode 11, endpoints (0, 512, 1023)-(1023, 0, 100), texel n has index n
+		// (expected halves worked out by hand with the unquantization, interpolation and finish formulas of the specification)
+		let decoded = super::decode_block(0xFEDC_BA98_7654_3210_3200_1FFF_FF00_0003, false);
+		assert_eq!(decoded[0], [0x0000, 0x3E0F, 0x7BFF]);
+		assert_eq!(decoded[5], [10416, 10674, 22350]);
+		assert_eq!(decoded[15], [0x7BFF, 0x0000, 0x0C2B]);
+		// black and white halves: endpoints (0, 0, 0)-(1023, 1023, 1023), indices 0 and 15
+		let texels = (0 .. 16).flat_map(|n| vec![if n < 8 { 0 } else { 0xFFFF }; 3]).collect::<Vec<_>>();
+		assert_eq!(super::encode_block(&texels, false, Quality::Normal), 0xFFFF_FFFF_0000_0001_FFFF_FFF8_0000_0003);
+	}
+	#[test] fn hdr_gradients_roundtrip()
+	{
+		fn image_of<F: Fn(f32) -> [f32; 3]>(size: (usize, usize), f: F) -> Vec<f16>
+		{
+			(0 .. size.0 * size.1).flat_map(|n| f((n % size.0) as f32).to_vec()).map(f16::from_f32).collect()
+		}
+		fn max_relative_error(source: &[f16], decoded: &[f16]) -> f32
+		{
+			source.iter().zip(decoded.iter()).map(|(&s, &d)|
+			{
+				let (s, d) = (f32::from(s), f32::from(d));
+				(s - d).abs() / s.abs().max(0.25)
+			}).fold(0.0, f32::max)
+		}
+		// horizontal ramps beyond 1.0(exponential ones span about 2 stops over a block; mode 11 fits a single line per block)
+		let size = (13, 10);
+		let unsigned = image_of(size, |x| [(x * 0.2).exp2() * 0.5, (x * 0.15).exp2(), 4.0 - x * 0.1]);
+		let image = ImageView::new(&unsigned[..], size, 3);
+		let compressed = super::BC6HUF16::compress_image(&image, &CompressionOptions::default());
+		assert_eq!(compressed.len(), super::BC6HUF16::format().level_length(size));
+		let parallel = CompressionOptions { parallel: true, .. CompressionOptions::default() };
+		assert_eq!(super::BC6HUF16::compress_image(&image, &parallel), compressed);
+		let error = max_relative_error(&unsigned, &super::BC6HUF16::decompress_f16(&compressed, size));
+		assert!(error < 0.05, "{}", error);
+
+		// (the domain is sign-magnitude and roughly logarithmic: a ramp through zero bends, so zero falls between blocks here)
+		let signed = image_of(size, |x| [(x - 3.5) * 0.5, -(x * 0.25).exp2(), 1.0 + x * 0.05]);
+		let image = ImageView::new(&signed[..], size, 3);
+		let errors = [Quality::Fast, Quality::High].iter().map(|&q|
+		{
+			let options = CompressionOptions { quality: q, .. CompressionOptions::default() };
+			max_relative_error(&signed, &super::BC6HSF16::decompress_f16(&super::BC6HSF16::compress_image(&image, &options), size))
+		}).collect::<Vec<_>>();
+		assert!(errors[1] <= errors[0] && errors[1] < 0.1, "{:?}", errors);
+		// the unsigned format clamps negative values
+		let clamped = super::BC6HUF16::decompress_f16(&super::BC6HUF16::compress_image(&image, &CompressionOptions::default()), size);
+		assert!(clamped.iter().all(|&v| f32::from(v) >= 0.0));
+	}
+	#[test] fn ldr_sources_decode_to_8bit()
+	{
+		let size = (15, 9);
+		let source = (0 .. size.0 * size.1 * 3).map(|n| ((n / 3) % size.0 * 100 / size.0 + (n / 3) / size.0 * 4 + n % 3 * 30) as u8).collect::<Vec<_>>();
+		let decoded = super::BC6HUF16::decompress(&super::BC6HUF16::compress(&source, size), size);
+		for p in psnr(&source, &decoded, size, 3) { assert!(p > 30.0, "PSNR {}", p); }
+	}
+}
@@ -0,0 +1,336 @@
+//! Texture Compiler: compresses PNG/PSD/raw images into DDS, KTX, KTX2 or raw blocks at build time
+//!
+//! texture_compiler -a <algorithm> [options] <input> <output>
+//!   -a, --algorithm  bc4 | bc5 | bc6h-uf16 | bc6h-sf16 | etc2-rgb | etc2-rgba | eac-r11 | eac-rg11 | astc4x4 | astc6x6 | astc8x8
+//!   -f, --format     dds | ktx | ktx2 | raw(default: from the output extension, raw if unknown)
+//!   -m, --mipmaps    box | kaiser(generates the full mipmap chain)
+//!   -q, --quality    fast | normal | high
+//!   -s, --swizzle    source channels fed into the encoder, e.g. `a` or `ra`(r, g, b, a or 0-3)
+//!   -j, --parallel   encodes on the thread pool
+//!   -w, --weights    error weights of the encoder channels, e.g. `3,6,1,1`, or `perceptual`(ETC2 color)
+//!   --alpha-weighted scales the color error by the alpha(colors of transparent texels are ignored)
+//!   --loose-extremes lets BC4/BC5 trade exact 0 and 255 for less error overall
+//!   --size WxH, --channels N  extent and channel count of raw input
+//!   --layer N        reads the layer of PSD input instead of the combined image
+//!
+//! Built with the `cli` feature(and `psd` for PSD input).
+
+extern crate texture_compression;
+extern crate png;
+#[cfg(feature = "psd")] extern crate psdloader;
+
+use texture_compression::*;
+use texture_compression::container::{dds, ktx};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Output container
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat { Dds, Ktx, Ktx2, Raw }
+
+struct Arguments
+{
+	algorithm: String, format: Option<OutputFormat>, mip_filter: Option<MipFilter>,
+	quality: Quality, swizzle: Option<Vec<usize>>, parallel: bool,
+	weights: Option<[u32; 4]>, alpha_weighted: bool, exact_extremes: bool,
+	raw_size: Option<(usize, usize)>, raw_channels: Option<usize>, layer: Option<usize>,
+	input: PathBuf, output: PathBuf
+}
+
+/// Decoded source image(interleaved 8-bit)
+struct SourceImage { data: Vec<u8>, size: (usize, usize), channels: usize }
+
+fn parse_arguments<I: Iterator<Item = String>>(mut args: I) -> Result<Arguments, String>
+{
+	let (mut algorithm, mut format, mut mip_filter, mut quality, mut swizzle, mut parallel) = (None, None, None, Quality::Normal, None, false);
+	let (mut weights, mut alpha_weighted, mut exact_extremes) = (None, false, true);
+	let (mut raw_size, mut raw_channels, mut layer) = (None, None, None);
+	let mut paths = Vec::new();
+	while let Some(a) = args.next()
+	{
+		let mut value = || args.next().ok_or_else(|| format!("{} requires a value", a));
+		match a.as_str()
+		{
+			"-a" | "--algorithm" => algorithm = Some(value()?),
+			"-f" | "--format" => format = Some(parse_output_format(&value()?)?),
+			"-m" | "--mipmaps" => mip_filter = Some(match value()?.as_str()
+			{
+				"box" => MipFilter::Box, "kaiser" => MipFilter::Kaiser,
+				v => return Err(format!("unknown mipmap filter: {}", v))
+			}),
+			"-q" | "--quality" => quality = match value()?.as_str()
+			{
+				"fast" => Quality::Fast, "normal" => Quality::Normal, "high" => Quality::High,
+				v => return Err(format!("unknown quality: {}", v))
+			},
+			"-s" | "--swizzle" => swizzle = Some(parse_swizzle(&value()?)?),
+			"-j" | "--parallel" => parallel = true,
+			"-w" | "--weights" => weights = Some(parse_weights(&value()?)?),
+			"--alpha-weighted" => alpha_weighted = true,
+			"--loose-extremes" => exact_extremes = false,
+			"--size" =>
+			{
+				let v = value()?;
+				let mut wh = v.split('x').map(|n| n.parse::<usize>());
+				raw_size = match (wh.next(), wh.next(), wh.next())
+				{
+					(Some(Ok(w)), Some(Ok(h)), None) => Some((w, h)),
+					_ => return Err(format!("invalid size: {}(expected WxH)", v))
+				};
+			},
+			"--channels" => raw_channels = Some(value()?.parse().map_err(|e| format!("invalid channel count: {}", e))?),
+			"--layer" => layer = Some(value()?.parse().map_err(|e| format!("invalid layer index: {}", e))?),
+			_ if a.starts_with('-') => return Err(format!("unknown option: {}", a)),
+			_ => paths.push(PathBuf::from(a))
+		}
+	}
+	if paths.len() != 2 { return Err("an input and an output path are required".to_owned()); }
+	let output = paths.pop().unwrap();
+	let input = paths.pop().unwrap();
+	Ok(Arguments
+	{
+		algorithm: algorithm.ok_or("an algorithm is required(-a)")?, format, mip_filter, quality, swizzle, parallel,
+		weights, alpha_weighted, exact_extremes, raw_size, raw_channels, layer, input, output
+	})
+}
+fn parse_output_format(v: &str) -> Result<OutputFormat, String>
+{
+	match v
+	{
+		"dds" => Ok(OutputFormat::Dds), "ktx" => Ok(OutputFormat::Ktx), "ktx2" => Ok(OutputFormat::Ktx2), "raw" => Ok(OutputFormat::Raw),
+		_ => Err(format!("unknown output format: {}", v))
+	}
+}
+fn parse_swizzle(v: &str) -> Result<Vec<usize>, String>
+{
+	v.chars().map(|c| match c
+	{
+		'r' | 'x' | '0' => Ok(0), 'g' | 'y' | '1' => Ok(1), 'b' | 'z' | '2' => Ok(2), 'a' | 'w' | '3' => Ok(3),
+		_ => Err(format!("invalid swizzle channel: {}", c))
+	}).collect()
+}
+fn parse_weights(v: &str) -> Result<[u32; 4], String>
+{
+	if v == "perceptual" { return Ok(CompressionOptions::PERCEPTUAL_WEIGHTS); }
+	let values = v.split(',').map(|w| w.parse::<u32>().map_err(|e| format!("invalid weight {}: {}", w, e))).collect::<Result<Vec<_>, _>>()?;
+	if values.is_empty() || values.len() > 4 { return Err(format!("1 to 4 weights are expected: {}", v)); }
+	let mut weights = [1; 4];
+	weights[.. values.len()].copy_from_slice(&values);
+	Ok(weights)
+}
+fn extension_of(path: &Path) -> String
+{
+	path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).unwrap_or_default()
+}
+
+fn load_png(path: &Path) -> Result<SourceImage, String>
+{
+	let fp = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+	// the default transformations expand palettes/low bit depths and strip 16-bit samples to 8-bit
+	let (info, mut reader) = png::Decoder::new(fp).read_info().map_err(|e| format!("{}: {:?}", path.display(), e))?;
+	let mut data = vec![0u8; info.buffer_size()];
+	reader.next_frame(&mut data).map_err(|e| format!("{}: {:?}", path.display(), e))?;
+	let channels = reader.output_color_type().0.samples();
+	Ok(SourceImage { data, size: (info.width as usize, info.height as usize), channels })
+}
+#[cfg(feature = "psd")]
+fn load_psd(path: &Path, layer: Option<usize>) -> Result<SourceImage, String>
+{
+	use psdloader::{PhotoshopDocument, PSDChannelIndices};
+
+	let psd = PhotoshopDocument::open(path).map_err(|e| format!("{}: {:?}", path.display(), e))?;
+	let size = (psd.width, psd.height);
+	match layer
+	{
+		Some(index) =>
+		{
+			// layer channels are placed on the canvas at their content rect(uncovered texels are transparent black)
+			let mut data = vec![0u8; size.0 * size.1 * 4];
+			let channel_ids = [PSDChannelIndices::Red, PSDChannelIndices::Green, PSDChannelIndices::Blue, PSDChannelIndices::Alpha];
+			for (c, &id) in channel_ids.iter().enumerate()
+			{
+				let v = psd.layer_raw_channel_image_data(index, id);
+				for (x, y) in (0 .. v.height()).flat_map(|y| (0 .. v.width()).map(move |x| (x, y)))
+				{
+					let (px, py) = (x as isize + v.offset_x(), y as isize + v.offset_y());
+					if (0 <= px && px < size.0 as isize) && (0 <= py && py < size.1 as isize)
+					{
+						data[(px as usize + py as usize * size.0) * 4 + c] = v.fetch(x, y);
+					}
+				}
+			}
+			Ok(SourceImage { data, size, channels: 4 })
+		},
+		None =>
+		{
+			let planes = psd.combined_raw_image_data();
+			let data = (0 .. size.1).flat_map(|y| (0 .. size.0).map(move |x| (x, y)))
+				.flat_map(|(x, y)| (0 .. planes.channels).map(|c| planes.fetch(x, y, c)).collect::<Vec<_>>()).collect();
+			Ok(SourceImage { data, size, channels: planes.channels })
+		}
+	}
+}
+#[cfg(not(feature = "psd"))]
+fn load_psd(path: &Path, _: Option<usize>) -> Result<SourceImage, String>
+{
+	Err(format!("{}: PSD input requires the `psd` feature", path.display()))
+}
+fn load_raw(path: &Path, size: Option<(usize, usize)>, channels: Option<usize>) -> Result<SourceImage, String>
+{
+	let (size, channels) = match (size, channels)
+	{
+		(Some(s), Some(c)) => (s, c),
+		_ => return Err(format!("{}: raw input requires --size and --channels", path.display()))
+	};
+	let mut data = Vec::new();
+	std::fs::File::open(path).and_then(|mut fp| fp.read_to_end(&mut data)).map_err(|e| format!("{}: {}", path.display(), e))?;
+	if size.0.checked_mul(size.1).and_then(|n| n.checked_mul(channels)) != Some(data.len())
+	{
+		return Err(format!("{}: {} bytes for {}x{}x{}", path.display(), data.len(), size.0, size.1, channels));
+	}
+	Ok(SourceImage { data, size, channels })
+}
+
+fn compress<A: CompressionAlgorithm>(source: &SourceImage, args: &Arguments) -> Result<CompressedTexture, String>
+{
+	let mut options = CompressionOptions
+	{
+		quality: args.quality, parallel: args.parallel,
+		channel_weights: args.weights.unwrap_or([1; 4]), alpha_weighted: args.alpha_weighted, exact_extremes: args.exact_extremes,
+		.. CompressionOptions::default()
+	};
+	if let Some(ref s) = args.swizzle
+	{
+		if s.len() != A::source_channels() { return Err(format!("the algorithm reads {} channels, but the swizzle has {}", A::source_channels(), s.len())); }
+		options.swizzle[.. s.len()].copy_from_slice(s);
+	}
+	if let Some(&c) = options.swizzle[.. A::source_channels()].iter().find(|&&c| c >= source.channels)
+	{
+		return Err(format!("the source has {} channels(channel {} is read: use --swizzle)", source.channels, c));
+	}
+	let image = ImageView::new(&source.data[..], source.size, source.channels);
+	Ok(CompressedTexture::compress_image::<A>(&image, &options, args.mip_filter))
+}
+fn run(args: &Arguments) -> Result<(), String>
+{
+	let source = match extension_of(&args.input).as_str()
+	{
+		"png" => load_png(&args.input),
+		"psd" => load_psd(&args.input, args.layer),
+		_ => load_raw(&args.input, args.raw_size, args.raw_channels)
+	}?;
+	if source.size.0 == 0 || source.size.1 == 0 || source.channels == 0
+	{
+		return Err(format!("{}: empty image({}x{}, {} channels)", args.input.display(), source.size.0, source.size.1, source.channels));
+	}
+	let texture = match args.algorithm.as_str()
+	{
+		"bc4" => compress::<BC4>(&source, args), "bc5" => compress::<BC5>(&source, args),
+		"bc6h-uf16" => compress::<BC6HUF16>(&source, args), "bc6h-sf16" => compress::<BC6HSF16>(&source, args),
+		"etc2-rgb" => compress::<ETC2RGB>(&source, args), "etc2-rgba" => compress::<ETC2RGBA8>(&source, args),
+		"eac-r11" => compress::<EACR11>(&source, args), "eac-rg11" => compress::<EACRG11>(&source, args),
+		"astc4x4" => compress::<ASTC4x4>(&source, args), "astc6x6" => compress::<ASTC6x6>(&source, args),
+		"astc8x8" => compress::<ASTC8x8>(&source, args),
+		a => Err(format!("unknown algorithm: {}", a))
+	}?;
+	let format = args.format.unwrap_or_else(|| parse_output_format(&extension_of(&args.output)).unwrap_or(OutputFormat::Raw));
+
+	// serialized in memory first: a rejected format leaves no partial file behind
+	let mut bytes = Vec::new();
+	match format
+	{
+		OutputFormat::Dds => dds::write_dds(&mut bytes, &texture),
+		OutputFormat::Ktx => ktx::write_ktx(&mut bytes, &texture),
+		OutputFormat::Ktx2 => ktx::write_ktx2(&mut bytes, &texture),
+		OutputFormat::Raw => bytes.write_all(&texture.data)
+	}.and_then(|()| std::fs::write(&args.output, &bytes)).map_err(|e| format!("{}: {}", args.output.display(), e))
+}
+
+fn main()
+{
+	let result = parse_arguments(std::env::args().skip(1)).and_then(|args| run(&args));
+	if let Err(e) = result
+	{
+		eprintln!("texture_compiler: {}", e);
+		std::process::exit(1);
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+
+	fn arguments(line: &str) -> Result<Arguments, String> { parse_arguments(line.split_whitespace().map(|s| s.to_owned())) }
+	fn temp_path(name: &str) -> PathBuf
+	{
+		let dir = std::env::temp_dir().join(format!("texture_compiler_test_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		dir.join(name)
+	}
+
+	#[test] fn parses_arguments()
+	{
+		let args = arguments("-a bc5 -m kaiser -q high -s ra -j -w 3,6 --loose-extremes in.png out.ktx2").unwrap();
+		assert_eq!(args.algorithm, "bc5");
+		assert_eq!((args.format, args.mip_filter, args.quality), (None, Some(MipFilter::Kaiser), Quality::High));
+		assert_eq!((args.swizzle, args.parallel, args.weights, args.exact_extremes), (Some(vec![0, 3]), true, Some([3, 6, 1, 1]), false));
+		assert_eq!((args.input, args.output), (PathBuf::from("in.png"), PathBuf::from("out.ktx2")));
+		let args = arguments("in.raw --size 24x12 --channels 2 -f dds -a bc4 out").unwrap();
+		assert_eq!((args.format, args.raw_size, args.raw_channels), (Some(OutputFormat::Dds), Some((24, 12)), Some(2)));
+		assert_eq!(arguments("-a bc4 -w perceptual in out").unwrap().weights, Some(CompressionOptions::PERCEPTUAL_WEIGHTS));
+	}
+	#[test] fn rejects_invalid_arguments()
+	{
+		assert!(arguments("in.png out.dds").is_err());
+		assert!(arguments("-a bc4 in.png").is_err());
+		assert!(arguments("-a bc4 in.png out.dds extra").is_err());
+		assert!(arguments("-a bc4 --verbose in.png out.dds").is_err());
+		assert!(arguments("-a bc4 in.png out.dds -m").is_err());
+		assert!(arguments("-a bc4 -f tga in.png out.dds").is_err());
+		assert!(arguments("-a bc4 -s rgbk in.png out.dds").is_err());
+		assert!(arguments("-a bc4 -w 1,2,3,4,5 in.png out.dds").is_err());
+		assert!(arguments("-a bc4 --size 16 --channels 1 in.raw out.dds").is_err());
+		assert!(arguments("-a bc4 --size 16x16x2 --channels 1 in.raw out.dds").is_err());
+	}
+	#[test] fn compiles_raw_input()
+	{
+		let source = (0 .. 24 * 12 * 2).map(|n| (n * 7 % 256) as u8).collect::<Vec<_>>();
+		let (input, output) = (temp_path("raw_input.raw"), temp_path("raw_output.dds"));
+		std::fs::write(&input, &source).unwrap();
+		let line = format!("-a bc5 -m box --size 24x12 --channels 2 {} {}", input.display(), output.display());
+		run(&arguments(&line).unwrap()).unwrap();
+		let texture = dds::read_dds(&mut std::fs::File::open(&output).unwrap()).unwrap();
+		assert_eq!(texture, CompressedTexture::compress::<BC5>(&source, (24, 12), Some(MipFilter::Box)));
+
+		// extents which do not match the data, or no texels
+		for &size in &["24x13", "0x12", "0x0"]
+		{
+			let line = format!("-a bc5 --size {} --channels 2 {} {}", size, input.display(), output.display());
+			assert!(run(&arguments(&line).unwrap()).is_err());
+		}
+		// BC5 reads the second channel
+		let line = format!("-a bc5 --size 24x24 --channels 1 {} {}", input.display(), output.display());
+		assert!(run(&arguments(&line).unwrap()).is_err());
+		std::fs::write(&input, []).unwrap();
+		let line = format!("-a bc4 --size 0x0 --channels 1 {} {}", input.display(), output.display());
+		assert!(run(&arguments(&line).unwrap()).is_err());
+		for p in &[input, output] { std::fs::remove_file(p).unwrap(); }
+	}
+	#[test] fn compiles_png_input()
+	{
+		let source = (0 .. 20 * 8 * 4).map(|n| (n * 5 % 256) as u8).collect::<Vec<_>>();
+		let (input, output) = (temp_path("png_input.png"), temp_path("png_output.ktx"));
+		{
+			use png::HasParameters;
+			let mut encoder = png::Encoder::new(std::fs::File::create(&input).unwrap(), 20, 8);
+			encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+			encoder.write_header().unwrap().write_image_data(&source).unwrap();
+		}
+		run(&arguments(&format!("-a bc4 -s a {} {}", input.display(), output.display())).unwrap()).unwrap();
+		let texture = ktx::read_ktx(&mut std::fs::File::open(&output).unwrap()).unwrap();
+		let options = CompressionOptions { swizzle: [3, 1, 2, 3], .. CompressionOptions::default() };
+		assert_eq!(texture, CompressedTexture::compress_image::<BC4>(&ImageView::new(&source[..], (20, 8), 4), &options, None));
+		for p in &[input, output] { std::fs::remove_file(p).unwrap(); }
+	}
+}
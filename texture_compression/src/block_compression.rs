@@ -17,84 +17,43 @@
 //-------------------------------------------------------------------------------------
 
 use std;
-use super::CompressionAlgorithm;
+use super::{CompressionAlgorithm, TextureFormat, ImageView, CompressionOptions, Quality};
+use blocks;
 
 const BLOCK_LEN: usize = 4;
-// const BLOCK_SIZE: usize = BLOCK_LEN * BLOCK_LEN;
+const BLOCK_SIZE: usize = BLOCK_LEN * BLOCK_LEN;
 
-/// Common Adapter Operation for Block Processing
-trait BlockAdapter<'a> : std::marker::Sized
-{
-	fn at(&self, x: usize, y: usize) -> f32;
-	fn iter(&'a self) -> BlockRefIterator<'a, Self>;
-}
+/// Texels of a block normalized to 0.0 ..= 1.0, row-major. Kept in a fixed array so that the per-texel loops
+/// neither allocate nor re-fetch texels from the source, and can be unrolled/vectorized
+type BlockTexels = [f32; BLOCK_SIZE];
 
-/// Adapter for Block Processing(for Unsigned Normalized Float)
-struct BlockRefAdapter<'a>
-{
-	slice_ref: &'a [u8], offset: (usize, usize), stride: usize
-}
-impl<'a> BlockAdapter<'a> for BlockRefAdapter<'a>
-{
-	fn at(&self, x: usize, y: usize) -> f32 { self.slice_ref[(self.offset.0 + x) + (self.offset.1 + y) * self.stride] as f32 / 255.0 }
-	fn iter(&'a self) -> BlockRefIterator<'a, Self> { BlockRefIterator { adapter: self, current: (0, 0) } }
-}
-impl<'a> std::iter::IntoIterator for BlockRefAdapter<'a>
+/// Clamps a texel coordinate into the image, so that edge blocks of images whose size is not a multiple of 4 repeat the last texel
+fn clamp_coord(offset: (usize, usize), x: usize, y: usize, size: (usize, usize)) -> (usize, usize)
 {
-	type Item = f32;
-	type IntoIter = BlockRefIntoIter<'a, Self>;
-	fn into_iter(self) -> Self::IntoIter { BlockRefIntoIter { adapter: self, current: (0, 0), ph: std::marker::PhantomData } }
+	(std::cmp::min(offset.0 + x, size.0 - 1), std::cmp::min(offset.1 + y, size.1 - 1))
 }
-/// Adapter for Block Processing(for double packed Unsigned Normalized Float)
-struct BlockRefAdapter2<'a>
+/// Loads a channel of the block at `offset` from an interleaved 8-bit image
+fn load_block(source: &[u8], size: (usize, usize), channels: usize, channel: usize, offset: (usize, usize)) -> BlockTexels
 {
-	slice_ref: &'a [u8], offset: (usize, usize), stride: usize, swizzle: usize
-}
-impl<'a> BlockAdapter<'a> for BlockRefAdapter2<'a>
-{
-	fn at(&self, x: usize, y: usize) -> f32 { self.slice_ref[((self.offset.0 + x) + (self.offset.1 + y) * self.stride) * 2 + self.swizzle] as f32 / 255.0 }
-	fn iter(&'a self) -> BlockRefIterator<'a, Self> { BlockRefIterator { adapter: self, current: (0, 0) } }
-}
-impl<'a> std::iter::IntoIterator for BlockRefAdapter2<'a>
-{
-	type Item = f32;
-	type IntoIter = BlockRefIntoIter<'a, Self>;
-	fn into_iter(self) -> Self::IntoIter { BlockRefIntoIter { adapter: self, current: (0, 0), ph: std::marker::PhantomData } }
-}
-/// For Iteration
-struct BlockRefIntoIter<'a, AdapterT: BlockAdapter<'a>> { adapter: AdapterT, current: (usize, usize), ph: std::marker::PhantomData<&'a usize> }
-impl<'a, AdapterT: BlockAdapter<'a>> std::iter::Iterator for BlockRefIntoIter<'a, AdapterT>
-{
-	type Item = f32;
-	fn next(&mut self) -> Option<Self::Item>
+	let mut texels = [0.0f32; BLOCK_SIZE];
+	for (n, t) in texels.iter_mut().enumerate()
 	{
-		if self.current.0 >= 4 || self.current.1 >= 4 { None } /* invalid */
-		else
-		{
-			let v = self.adapter.at(self.current.0, self.current.1);
-			self.current = if self.current.0 == 3 { (0, self.current.1 + 1) } else { (self.current.0 + 1, self.current.1) };
-			Some(v)
-		}
+		let (px, py) = clamp_coord(offset, n % BLOCK_LEN, n / BLOCK_LEN, size);
+		*t = source[(px + py * size.0) * channels + channel] as f32 / 255.0;
 	}
+	texels
 }
-struct BlockRefIterator<'a, AdapterT: BlockAdapter<'a> + 'a> { adapter: &'a AdapterT, current: (usize, usize) }
-impl<'a, AdapterT: BlockAdapter<'a> + 'a> std::iter::Iterator for BlockRefIterator<'a, AdapterT>
+/// Column-major copy of the block(the order the Newton's method accumulates derivatives in)
+fn transpose(texels: &BlockTexels) -> BlockTexels
 {
-	type Item = f32;
-	fn next(&mut self) -> Option<Self::Item>
-	{
-		if self.current.0 >= 4 || self.current.1 >= 4 { None } /* invalid */
-		else
-		{
-			let v = self.adapter.at(self.current.0, self.current.1);
-			self.current = if self.current.0 == 3 { (0, self.current.1 + 1) } else { (self.current.0 + 1, self.current.1) };
-			Some(v)
-		}
-	}
+	let mut columns = [0.0f32; BLOCK_SIZE];
+	for (n, c) in columns.iter_mut().enumerate() { *c = texels[(n % BLOCK_LEN) * BLOCK_LEN + n / BLOCK_LEN]; }
+	columns
 }
 
 // returns (pX, pY)
-fn optimize_alpha_u<'a, PointRef: BlockAdapter<'a>>(points: &'a PointRef, steps: usize) -> (f32, f32)
+#[allow(clippy::eq_op)]
+fn optimize_alpha_u(points: &BlockTexels, steps: usize, iterations: usize) -> (f32, f32)
 {
 	static C6: [f32; 6] = [5.0 / 5.0, 4.0 / 5.0, 3.0 / 5.0, 2.0 / 5.0, 1.0 / 5.0, 0.0 / 5.0];
 	static D6: [f32; 6] = [0.0 / 5.0, 1.0 / 5.0, 2.0 / 5.0, 3.0 / 5.0, 4.0 / 5.0, 5.0 / 5.0];
@@ -109,11 +68,11 @@ fn optimize_alpha_u<'a, PointRef: BlockAdapter<'a>>(points: &'a PointRef, steps:
 	// Find Min and Max points, as starting point
 	let (mut minv, mut maxv) = if steps == 8
 	{
-		points.iter().fold((MAX_VALUE, MIN_VALUE), |(mx, mn), x| (mx.min(x), mn.max(x)))
+		points.iter().fold((MAX_VALUE, MIN_VALUE), |(mx, mn), &x| (mx.min(x), mn.max(x)))
 	}
 	else
 	{
-		points.iter().fold((MAX_VALUE, MIN_VALUE), |(mx, mn), x| (
+		points.iter().fold((MAX_VALUE, MIN_VALUE), |(mx, mn), &x| (
 			if x < mx && x > MIN_VALUE { x } else { mx },
 			if x > mn && x < MAX_VALUE { x } else { mn }
 		))
@@ -121,23 +80,22 @@ fn optimize_alpha_u<'a, PointRef: BlockAdapter<'a>>(points: &'a PointRef, steps:
 	maxv = if steps == 6 && minv == maxv { MAX_VALUE } else { maxv };
 
 	// Use Newton's Method to find local minima of sum-of-squares error
+	let columns = transpose(points);
 	let f_steps = steps - 1;
-	for _ in 0 .. 8
+	let mut p_steps = [MIN_VALUE; 8];
+	p_steps[7] = MAX_VALUE;		// the 6-step codec's fixed boundary values(overwritten in the 8-step one)
+	for _ in 0 .. iterations
 	{
 		let diff = maxv - minv;
 		if diff < (1.0f32 / 256.0f32) { break; }
 		let f_scale = f_steps as f32 / diff;
 
 		// Calculate new steps
-		let mut p_steps: Vec<f32> = (0 .. steps).map(|n| c[n] * minv + d[n] * maxv).collect();
-		if steps == 6
-		{
-			p_steps.push(MIN_VALUE); p_steps.push(MAX_VALUE);
-		}
+		for (n, p) in p_steps[.. steps].iter_mut().enumerate() { *p = c[n] * minv + d[n] * maxv; }
 
 		// Evaluate function, and derivatives
 		let (mut dx, mut dy, mut d2x, mut d2y) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
-		for p in (0 .. BLOCK_LEN).flat_map(|x| (0 .. BLOCK_LEN).map(move |y| (x, y))).map(|(x, y)| points.at(x, y))
+		for &p in columns.iter()
 		{
 			let f_dot = (p - minv) * f_scale;
 			let i_step = if f_dot <= 0.0
@@ -169,34 +127,62 @@ fn optimize_alpha_u<'a, PointRef: BlockAdapter<'a>>(points: &'a PointRef, steps:
 		if dx * dx < 1.0 / 64.0 && dy * dy < 1.0 / 64.0 { break; }
 	}
 
-	(minv.max(MIN_VALUE).min(MAX_VALUE), maxv.max(MIN_VALUE).min(MAX_VALUE))
+	(minv.clamp(MIN_VALUE, MAX_VALUE), maxv.clamp(MIN_VALUE, MAX_VALUE))
 }
 
 // returns (endpoint0, endpoint1)
-fn find_endpoints_bc4u<'a, TexelRef: BlockAdapter<'a>>(texels: &'a TexelRef) -> (u8, u8)
+fn find_endpoints_bc4u(texels: &BlockTexels, quality: Quality, exact_extremes: bool) -> (u8, u8)
 {
 	// The boundary of codec for signed/unsigned format
 	const MIN_NORM: f32 = 0.0;
 	const MAX_NORM: f32 = 1.0;
 
 	// Find max.min of input texels
-	let (block_max, block_min) = texels.iter().fold((texels.at(0, 0), texels.at(0, 0)), |(mx, mn), x| (mx.max(x), mn.min(x)));
+	let (block_max, block_min) = texels.iter().fold((texels[0], texels[0]), |(mx, mn), &x| (mx.max(x), mn.min(x)));
 
-	// If there are boundary values in input texels, Should use 4 block-coded to guarantee
-	// the exact code of the boundary values
-	let using_4_block_codec = MIN_NORM == block_min || MAX_NORM == block_max;
+	// The 6-step codec(r0 <= r1) has the exact codes of the boundary values, the 8-step one(r0 > r1) finer steps between the endpoints
+	let has_boundary = MIN_NORM == block_min || MAX_NORM == block_max;
+	let iterations = if quality == Quality::Fast { 0 } else { 8 };
+	let six_step = || refine_endpoints_bc4u(fit_endpoints_bc4u(texels, 6, iterations), texels, quality);
+	let eight_step = || refine_endpoints_bc4u(fit_endpoints_bc4u(texels, 8, iterations), texels, quality);
+	if !has_boundary { return eight_step(); }
+	if exact_extremes { return six_step(); }
 
-	// Using optimize
-	if using_4_block_codec
-	{
-		let (start, end) = optimize_alpha_u(texels, 8);
-		((start * 255.0) as u8, (end * 255.0) as u8)
-	}
-	else
+	// Otherwise the codec with the least error wins
+	let (a, b) = (six_step(), eight_step());
+	if quantization_error(b.0, b.1, texels) < quantization_error(a.0, a.1, texels) { b } else { a }
+}
+// Optimized endpoints in the order selecting the codec of `steps`
+fn fit_endpoints_bc4u(texels: &BlockTexels, steps: usize, iterations: usize) -> (u8, u8)
+{
+	let (start, end) = optimize_alpha_u(texels, steps, iterations);
+	let (a, b) = ((start * 255.0 + 0.5) as u8, (end * 255.0 + 0.5) as u8);
+	let (lo, hi) = (a.min(b), a.max(b));
+	if steps == 6 { (lo, hi) } else { (hi, lo) }
+}
+// Searches the neighborhood of the endpoints on High quality, keeping their order(the codec selection)
+fn refine_endpoints_bc4u((r0, r1): (u8, u8), texels: &BlockTexels, quality: Quality) -> (u8, u8)
+{
+	if quality != Quality::High { return (r0, r1); }
+
+	let mut best = ((r0, r1), quantization_error(r0, r1, texels));
+	for d0 in -1 .. 2
 	{
-		let (start, end) = optimize_alpha_u(texels, 6);
-		((start * 255.0) as u8, (end * 255.0) as u8)
+		for d1 in -1 .. 2
+		{
+			let (c0, c1) = (r0 as i32 + d0, r1 as i32 + d1);
+			if !(0 ..= 255).contains(&c0) || !(0 ..= 255).contains(&c1) || (c0 <= c1) != (r0 <= r1) { continue; }
+			let error = quantization_error(c0 as u8, c1 as u8, texels);
+			if error < best.1 { best = ((c0 as u8, c1 as u8), error); }
+		}
 	}
+	best.0
+}
+// Sum of squared errors of the texels encoded with the endpoints
+fn quantization_error(r0: u8, r1: u8, texels: &BlockTexels) -> f32
+{
+	let gradients = bc4_gradients(r0, r1);
+	texels.iter().map(|&p| gradients.iter().fold(f32::MAX, |e, &g| e.min((g - p) * (g - p)))).sum()
 }
 fn bc4_decode_from_index(r0: u8, r1: u8, index: usize) -> f32
 {
@@ -215,19 +201,28 @@ fn bc4_decode_from_index(r0: u8, r1: u8, index: usize) -> f32
 		}
 	}
 }
+/// Decoded values of the 8 indices
+fn bc4_gradients(r0: u8, r1: u8) -> [f32; 8]
+{
+	let mut gradients = [0.0f32; 8];
+	for (n, g) in gradients.iter_mut().enumerate() { *g = bc4_decode_from_index(r0, r1, n); }
+	gradients
+}
 // returns indices
-fn find_closest_unorm<'a, TexelRef: BlockAdapter<'a>>(r0: u8, r1: u8, texels: &'a TexelRef) -> Vec<u8>
+fn find_closest_unorm(r0: u8, r1: u8, texels: &BlockTexels) -> [u8; BLOCK_SIZE]
 {
-	let gradients = (0 .. 8).map(|n| bc4_decode_from_index(r0, r1, n)).collect::<Vec<_>>();
-	(0 .. BLOCK_LEN).flat_map(|y| (0 .. BLOCK_LEN).map(move |x| (x, y))).map(|(x, y)| texels.at(x, y)).map(|p|
+	let gradients = bc4_gradients(r0, r1);
+	let mut indices = [0u8; BLOCK_SIZE];
+	for (index, &p) in indices.iter_mut().zip(texels.iter())
 	{
 		let (best_index, _) = gradients.iter().enumerate().fold((0, 100000.0f32), |(bi, bd), (i, &g)|
 		{
 			let current_delta = (g - p).abs();
 			if current_delta < bd { (i, current_delta) } else { (bi, bd) }
 		});
-		best_index as u8
-	}).collect()
+		*index = best_index as u8;
+	}
+	indices
 }
 fn v8_to_u64_encode(src: &[u8]) -> u64
 {
@@ -239,82 +234,213 @@ fn v8_to_u64_encode(src: &[u8]) -> u64
 	})
 }
 
-#[repr(C)] struct CompressedBlockData
+/// BC4 block: two endpoints and 16 3-bit indices(packed by `v8_to_u64_encode`)
+struct CompressedBlockData
 {
-	r0: u8, r1: u8, indices: [u8; 6]
+	r0: u8, r1: u8, indices: u64
 }
-#[repr(C)] struct CompressedBlockData2
+impl CompressedBlockData
+{
+	/// Serialized form: r0, r1, then the 48 index bits in little-endian order(independent of the host)
+	fn write_bytes(&self, dest: &mut [u8])
+	{
+		dest[0] = self.r0;
+		dest[1] = self.r1;
+		for (n, b) in dest[2 .. 8].iter_mut().enumerate() { *b = (self.indices >> (n * 8)) as u8; }
+	}
+	fn read_bytes(src: &[u8]) -> Self
+	{
+		let indices = src[2 .. 8].iter().rev().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+		CompressedBlockData { r0: src[0], r1: src[1], indices }
+	}
+}
+/// BC5 block: the red block followed by the green block
+struct CompressedBlockData2
 {
 	r: CompressedBlockData, g: CompressedBlockData
 }
+impl CompressedBlockData2
+{
+	fn write_bytes(&self, dest: &mut [u8])
+	{
+		self.r.write_bytes(&mut dest[.. 8]);
+		self.g.write_bytes(&mut dest[8 .. 16]);
+	}
+}
 
-fn encode_block_single(src: &[u8], pitch: usize, bx: usize, by: usize) -> CompressedBlockData
+fn encode_block_channel(texels: &BlockTexels, options: &CompressionOptions) -> CompressedBlockData
 {
-	let block_normalized = BlockRefAdapter { slice_ref: src, stride: pitch, offset: (bx, by) };
-	let (r0, r1) = find_endpoints_bc4u(&block_normalized);
-	let indices_t = find_closest_unorm(r0, r1, &block_normalized);
-	let indices = v8_to_u64_encode(&indices_t);
-	let mut cb = CompressedBlockData { r0: r0, r1: r1, indices: [0; 6] };
-	cb.indices.copy_from_slice(&unsafe { std::mem::transmute::<_, [u8; 8]>(indices) }[..6]);
-	cb
+	let (r0, r1) = find_endpoints_bc4u(texels, options.quality, options.exact_extremes);
+	CompressedBlockData { r0, r1, indices: v8_to_u64_encode(&find_closest_unorm(r0, r1, texels)) }
 }
-fn encode_block_double(src: &[u8], pitch: usize, bx: usize, by: usize) -> CompressedBlockData2
+fn encode_block_single(src: &[u8], size: (usize, usize), bx: usize, by: usize, options: &CompressionOptions) -> CompressedBlockData
 {
-	let block_normalized_r = BlockRefAdapter2 { slice_ref: src, stride: pitch, offset: (bx, by), swizzle: 0 };
-	let block_normalized_g = BlockRefAdapter2 { slice_ref: src, stride: pitch, offset: (bx, by), swizzle: 1 };
-	let (r0, r1) = find_endpoints_bc4u(&block_normalized_r);
-	let (g0, g1) = find_endpoints_bc4u(&block_normalized_g);
-	let (indices_r, indices_g) = (
-		v8_to_u64_encode(&find_closest_unorm(r0, r1, &block_normalized_r)),
-		v8_to_u64_encode(&find_closest_unorm(g0, g1, &block_normalized_g))
-	);
-	let mut cbs = CompressedBlockData2
+	encode_block_channel(&load_block(src, size, 1, 0, (bx, by)), options)
+}
+fn encode_block_double(src: &[u8], size: (usize, usize), bx: usize, by: usize, options: &CompressionOptions) -> CompressedBlockData2
+{
+	CompressedBlockData2
 	{
-		r: CompressedBlockData { r0: r0, r1: r1, indices: [0; 6] },
-		g: CompressedBlockData { r0: g0, r1: g1, indices: [0; 6] }
-	};
-	cbs.r.indices.copy_from_slice(&unsafe { std::mem::transmute::<_, [u8; 8]>(indices_r) }[..6]);
-	cbs.g.indices.copy_from_slice(&unsafe { std::mem::transmute::<_, [u8; 8]>(indices_g) }[..6]);
-	cbs
+		r: encode_block_channel(&load_block(src, size, 2, 0, (bx, by)), options),
+		g: encode_block_channel(&load_block(src, size, 2, 1, (bx, by)), options)
+	}
 }
 
+// Decoding(for measuring quality): texels of a BC4 block in row-major order
+fn decode_block_single(block: &[u8]) -> [u8; 16]
+{
+	let block = CompressedBlockData::read_bytes(block);
+	let mut texels = [0u8; 16];
+	for (n, t) in texels.iter_mut().enumerate()
+	{
+		*t = (bc4_decode_from_index(block.r0, block.r1, ((block.indices >> (n * 3)) & 0b111) as usize) * 255.0 + 0.5) as u8;
+	}
+	texels
+}
+
+/// Number of blocks required to cover the image(edge blocks are padded by clamping)
+pub fn block_count(size: (usize, usize)) -> (usize, usize) { blocks::block_count(size, (BLOCK_LEN, BLOCK_LEN)) }
+
 pub enum BC4 {}
 pub enum BC5 {}
 impl CompressionAlgorithm for BC4
 {
-	fn compress(source: &[u8], size: (usize, usize)) -> Vec<u8>
+	fn source_channels() -> usize { 1 }
+	fn format() -> TextureFormat { TextureFormat::BC4Unorm }
+	fn version() -> u32 { 2 }
+	fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8>
 	{
-		assert_eq!(size.0 * size.1, source.len());		// size matching
-		assert!(size.0 % 4 == 0 && size.1 % 4 == 0);	// alignment matching
+		let (source, size) = (image.pack(&options.swizzle[.. 1]), image.size);
+		assert!(size.0 > 0 && size.1 > 0);				// at least a texel
 
-		let compressed_blocks = (0 .. size.1 / 4).map(|y| y * 4).flat_map(|y| (0 .. size.0 / 4).map(|x| x * 4).map(move |x| (x, y))).map(|(bx, by)|
-		{
-			encode_block_single(&source, size.0, bx, by)
-		}).collect::<Vec<_>>();
-		unsafe { std::slice::from_raw_parts(compressed_blocks.as_ptr() as *const u8, compressed_blocks.len() * 8) }.into()
+		let encoder = |bx, by, dest: &mut [u8]| encode_block_single(&source, size, bx, by, options).write_bytes(dest);
+		if options.parallel { blocks::compress_blocks_parallel(size, (BLOCK_LEN, BLOCK_LEN), 8, encoder) }
+		else { blocks::compress_blocks(size, (BLOCK_LEN, BLOCK_LEN), 8, encoder) }
+	}
+	fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8>
+	{
+		blocks::decompress_blocks(compressed, size, (BLOCK_LEN, BLOCK_LEN), 8, 1, |block, texels| texels.copy_from_slice(&decode_block_single(block)))
 	}
 }
 impl CompressionAlgorithm for BC5
 {
-	fn compress(source: &[u8], size: (usize, usize)) -> Vec<u8>
+	fn source_channels() -> usize { 2 }
+	fn format() -> TextureFormat { TextureFormat::BC5Unorm }
+	fn version() -> u32 { 2 }
+	fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8>
 	{
-		assert_eq!(size.0 * size.1 * 2, source.len());		// size matching
-		assert!(size.0 % 4 == 0 && size.1 % 4 == 0);		// alignment matching
+		let (source, size) = (image.pack(&options.swizzle[.. 2]), image.size);
+		assert!(size.0 > 0 && size.1 > 0);					// at least a texel
 
-		let compressed_blocks = (0 .. size.1 / 4).map(|y| y * 4).flat_map(|y| (0 .. size.0 / 4).map(|x| x * 4).map(move |x| (x, y))).map(|(bx, by)|
+		let encoder = |bx, by, dest: &mut [u8]| encode_block_double(&source, size, bx, by, options).write_bytes(dest);
+		if options.parallel { blocks::compress_blocks_parallel(size, (BLOCK_LEN, BLOCK_LEN), 16, encoder) }
+		else { blocks::compress_blocks(size, (BLOCK_LEN, BLOCK_LEN), 16, encoder) }
+	}
+	fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8>
+	{
+		blocks::decompress_blocks(compressed, size, (BLOCK_LEN, BLOCK_LEN), 16, 2, |block, texels|
 		{
-			encode_block_double(&source, size.0, bx, by)
-		}).collect::<Vec<_>>();
-		unsafe { std::slice::from_raw_parts(compressed_blocks.as_ptr() as *const u8, compressed_blocks.len() * 16) }.into()
+			let (r, g) = (decode_block_single(&block[.. 8]), decode_block_single(&block[8 ..]));
+			for (n, t) in texels.chunks_mut(2).enumerate() { t[0] = r[n]; t[1] = g[n]; }
+		})
 	}
 }
 
 #[cfg(test)]
 mod testings
 {
+	use CompressionAlgorithm;
+
 	#[test] fn v8_to_u64_encoding()
 	{
 		assert_eq!(super::v8_to_u64_encode(&[0, 1, 2, 3, 4, 5]), 0b101100011010001000);
 		assert_eq!(super::v8_to_u64_encode(&[7, 5, 1, 3, 2, 3, 4, 6]), 0b110100011010011001101111);
 	}
+	#[test] fn block_serialization_is_little_endian()
+	{
+		let block = super::CompressedBlockData { r0: 0x12, r1: 0x34, indices: 0x0000_FEDC_BA98_7654 };
+		let mut bytes = [0u8; 8];
+		block.write_bytes(&mut bytes);
+		assert_eq!(bytes, [0x12, 0x34, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE]);
+		let read = super::CompressedBlockData::read_bytes(&bytes);
+		assert_eq!((read.r0, read.r1, read.indices), (0x12, 0x34, 0x0000_FEDC_BA98_7654));
+	}
+	#[test] fn known_blocks_encode_to_pinned_bytes()
+	{
+		let ramp = (0 .. 16).map(|n| (n * 16) as u8).collect::<Vec<_>>();
+		assert_eq!(super::BC4::compress(&ramp, (4, 4)), vec![0x15, 0xE9, 0x06, 0x24, 0x6D, 0x24, 0xDB, 0x26]);
+		let ramp2 = (0 .. 16).flat_map(|n| vec![(n * 16) as u8, 200 - (n * 9) as u8]).collect::<Vec<_>>();
+		assert_eq!(super::BC5::compress(&ramp2, (4, 4)), vec![
+			0x15, 0xE9, 0x06, 0x24, 0x6D, 0x24, 0xDB, 0x26,
+			0xC5, 0x44, 0x80, 0xB4, 0x91, 0xAD, 0xFD, 0x27
+		]);
+	}
+	#[test] fn unaligned_sizes_pad_edge_blocks()
+	{
+		assert_eq!(super::BC4::compress(&[128; 5 * 3], (5, 3)).len(), 2 * 8);
+		assert_eq!(super::BC5::compress(&[128; 17 * 2], (17, 1)).len(), 5 * 16);
+		// clamped edge texels must encode the same as an explicitly replicated block
+		let replicated = [0, 255, 255, 255, 0, 255, 255, 255, 0, 255, 255, 255, 0, 255, 255, 255];
+		assert_eq!(super::BC4::compress(&[0, 255], (2, 1)), super::BC4::compress(&replicated, (4, 4)));
+	}
+	#[test] fn parallel_output_is_identical()
+	{
+		let source = (0 .. 37 * 23 * 2).map(|n| ((n * 7919) % 251) as u8).collect::<Vec<_>>();
+		assert_eq!(super::BC4::compress_parallel(&source[.. 37 * 23], (37, 23)), super::BC4::compress(&source[.. 37 * 23], (37, 23)));
+		assert_eq!(super::BC5::compress_parallel(&source, (37, 23)), super::BC5::compress(&source, (37, 23)));
+	}
+	#[test] fn options_select_channels_and_effort()
+	{
+		use {ImageView, CompressionOptions, Quality};
+		use metrics::rmse;
+
+		// BC5 from the red and alpha channels of a padded RGBA image
+		let size = (9, 6);
+		let pitch = 9 * 4 + 8;
+		let rgba = (0 .. pitch * 6).map(|n| ((n * 37) % 256) as u8).collect::<Vec<_>>();
+		let packed = (0 .. 9 * 6).flat_map(|n| { let o = (n / 9) * pitch + (n % 9) * 4; vec![rgba[o], rgba[o + 3]] }).collect::<Vec<_>>();
+		let options = CompressionOptions { swizzle: [0, 3, 0, 0], .. CompressionOptions::default() };
+		assert_eq!(super::BC5::compress_image(&ImageView::with_pitch(&rgba[..], size, 4, pitch), &options), super::BC5::compress(&packed, size));
+
+		// more effort never loses quality on this source
+		let source = (0 .. 32 * 32).map(|n| (((n % 32) * 5 + (n / 32) * 3 + (n * 7919) % 23) % 256) as u8).collect::<Vec<_>>();
+		let error = |quality|
+		{
+			let options = CompressionOptions { quality, .. CompressionOptions::default() };
+			let compressed = super::BC4::compress_image(&ImageView::new(&source[..], (32, 32), 1), &options);
+			rmse(&source, &super::BC4::decompress(&compressed, (32, 32)), (32, 32), 1)[0]
+		};
+		let (fast, normal, high) = (error(Quality::Fast), error(Quality::Normal), error(Quality::High));
+		assert!(high <= normal && normal <= fast * 1.05, "{} {} {}", fast, normal, high);
+	}
+	#[test] fn decode_reproduces_flat_and_boundary_values()
+	{
+		let source = [0, 255, 0, 255, 64, 64, 64, 64, 255, 0, 255, 0, 128, 128, 128, 128];
+		assert_eq!(super::BC4::decompress(&super::BC4::compress(&source, (4, 4)), (4, 4))[.. 4], source[.. 4]);
+		let flat = [77u8; 6 * 5 * 2];
+		assert_eq!(super::BC5::decompress(&super::BC5::compress(&flat, (6, 5)), (6, 5)), flat.to_vec());
+	}
+	#[test] fn boundary_values_select_the_exact_codec()
+	{
+		use {ImageView, CompressionOptions, Quality};
+		use metrics::rmse;
+
+		// antialiased mask: blocks mixing 0, 255 and edge values
+		let size = (12, 12);
+		let mask = (0 .. size.0 * size.1).map(|n| match (n % size.0 + n / size.0 * 2) % 9 { 0 ..= 2 => 0, 3 => 70, 4 => 180, _ => 255 }).collect::<Vec<u8>>();
+		for &quality in &[Quality::Fast, Quality::Normal, Quality::High]
+		{
+			let exact = CompressionOptions { quality, .. CompressionOptions::default() };
+			let loose = CompressionOptions { exact_extremes: false, .. exact.clone() };
+			let decode = |options: &CompressionOptions| super::BC4::decompress(&super::BC4::compress_image(&ImageView::new(&mask[..], size, 1), options), size);
+			let decoded = decode(&exact);
+			assert!(mask.iter().zip(decoded.iter()).all(|(&s, &d)| (s != 0 && s != 255) || s == d), "{:?}", decoded);
+			// without the guarantee, the codec with the least error is taken
+			assert!(rmse(&mask, &decode(&loose), size, 1)[0] <= rmse(&mask, &decoded, size, 1)[0]);
+		}
+		// blocks without boundary values take the finer 8-step codec(r0 > r1)
+		let ramp = (0 .. 16).map(|n| (20 + n * 13) as u8).collect::<Vec<_>>();
+		let block = super::BC4::compress(&ramp, (4, 4));
+		assert!(block[0] > block[1], "{:?}", block);
+	}
 }
@@ -0,0 +1,72 @@
+// Block Grid Drivers shared by the block encoders/decoders
+
+use std;
+use rayon::prelude::*;
+
+/// Number of blocks required to cover the image(edge blocks are padded by clamping)
+pub fn block_count(size: (usize, usize), extent: (usize, usize)) -> (usize, usize)
+{
+	(size.0.div_ceil(extent.0), size.1.div_ceil(extent.1))
+}
+
+/// Encodes blocks in row-major order: `encoder(bx, by, dest)` receives the texel origin of the block and its output bytes
+pub fn compress_blocks<F>(size: (usize, usize), extent: (usize, usize), block_bytes: usize, encoder: F) -> Vec<u8>
+	where F: Fn(usize, usize, &mut [u8])
+{
+	let (bw, bh) = block_count(size, extent);
+	let mut compressed = vec![0u8; bw * bh * block_bytes];
+	for (n, dest) in compressed.chunks_mut(block_bytes).enumerate() { encoder((n % bw) * extent.0, (n / bw) * extent.1, dest); }
+	compressed
+}
+/// Same as `compress_blocks`, but rows of blocks are encoded on the rayon thread pool.
+/// A row never touches another row's output, so the result does not depend on scheduling
+pub fn compress_blocks_parallel<F>(size: (usize, usize), extent: (usize, usize), block_bytes: usize, encoder: F) -> Vec<u8>
+	where F: Fn(usize, usize, &mut [u8]) + Sync
+{
+	let (bw, bh) = block_count(size, extent);
+	let mut compressed = vec![0u8; bw * bh * block_bytes];
+	compressed.par_chunks_mut(bw * block_bytes).enumerate().for_each(|(by, row)|
+	{
+		for (bx, dest) in row.chunks_mut(block_bytes).enumerate() { encoder(bx * extent.0, by * extent.1, dest); }
+	});
+	compressed
+}
+
+/// Decodes blocks into an interleaved image: `decoder(block, texels)` fills the block's texels(row-major, interleaved).
+/// Texels in the padding of edge blocks are dropped
+pub fn decompress_blocks<F>(compressed: &[u8], size: (usize, usize), extent: (usize, usize), block_bytes: usize, channels: usize, decoder: F) -> Vec<u8>
+	where F: Fn(&[u8], &mut [u8])
+{
+	let (bw, bh) = block_count(size, extent);
+	assert_eq!(bw * bh * block_bytes, compressed.len());
+	let mut dest = vec![0u8; size.0 * size.1 * channels];
+	let mut texels = vec![0u8; extent.0 * extent.1 * channels];
+	for (n, block) in compressed.chunks(block_bytes).enumerate()
+	{
+		let (bx, by) = ((n % bw) * extent.0, (n / bw) * extent.1);
+		decoder(block, &mut texels);
+		for y in 0 .. std::cmp::min(extent.1, size.1 - by)
+		{
+			let w = std::cmp::min(extent.0, size.0 - bx);
+			let row = ((bx + (by + y) * size.0) * channels, y * extent.0 * channels);
+			dest[row.0 .. row.0 + w * channels].copy_from_slice(&texels[row.1 .. row.1 + w * channels]);
+		}
+	}
+	dest
+}
+
+/// Copies texels of a block(row-major, interleaved), clamping coordinates outside the image to the edge
+pub fn gather_block<T: Copy>(source: &[T], size: (usize, usize), channels: usize, origin: (usize, usize), extent: (usize, usize)) -> Vec<T>
+{
+	let mut texels = Vec::with_capacity(extent.0 * extent.1 * channels);
+	for y in 0 .. extent.1
+	{
+		let sy = std::cmp::min(origin.1 + y, size.1 - 1);
+		for x in 0 .. extent.0
+		{
+			let sx = std::cmp::min(origin.0 + x, size.0 - 1);
+			texels.extend_from_slice(&source[(sx + sy * size.0) * channels .. (sx + sy * size.0 + 1) * channels]);
+		}
+	}
+	texels
+}
@@ -0,0 +1,128 @@
+// Content-hash keyed on-disk cache of compressed blocks
+
+use std;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::CompressionAlgorithm;
+
+const MAGIC: [u8; 4] = *b"TCC1";
+
+/// FNV-1a 64-bit hash(stable across hosts and compiler versions, unlike `std::hash`)
+pub fn content_hash(source: &[u8], size: (usize, usize)) -> u64
+{
+	const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+	const PRIME: u64 = 0x100000001b3;
+	let extent = [(size.0 as u64).to_le_bytes(), (size.1 as u64).to_le_bytes()];
+	extent.iter().flat_map(|x| x.iter()).chain(source.iter()).fold(OFFSET_BASIS, |h, &b| (h ^ b as u64).wrapping_mul(PRIME))
+}
+
+/// What `compress_cached` did with the cache
+#[derive(Debug)]
+pub enum CacheStatus
+{
+	/// Blocks were loaded from the cache
+	Hit,
+	/// Blocks were compressed and stored to the cache
+	Stored,
+	/// Blocks were compressed, but could not be stored(the returned blocks are still valid)
+	StoreFailed(std::io::Error)
+}
+
+fn cache_path<Algorithm: CompressionAlgorithm>(key: &str, cache_dir: &Path) -> PathBuf
+{
+	let file_key = key.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' }).collect::<String>();
+	cache_dir.join(format!("{}.{}.tcache", file_key, Algorithm::format().vk_format()))
+}
+// Header: magic, algorithm version(u32), source hash(u64), width(u32), height(u32), data length(u64)
+fn read_cache(path: &Path, version: u32, hash: u64, size: (usize, usize)) -> Option<Vec<u8>>
+{
+	let mut file = Vec::new();
+	std::fs::File::open(path).and_then(|mut fp| fp.read_to_end(&mut file)).ok()?;
+	if file.len() < 32 || file[.. 4] != MAGIC { return None; }
+	let u32_at = |o: usize| u32::from_le_bytes([file[o], file[o + 1], file[o + 2], file[o + 3]]);
+	let u64_at = |o: usize| (u32_at(o) as u64) | ((u32_at(o + 4) as u64) << 32);
+	let matched = u32_at(4) == version && u64_at(8) == hash && u32_at(16) as usize == size.0 && u32_at(20) as usize == size.1
+		&& u64_at(24) as usize == file.len() - 32;
+	if matched { Some(file[32 ..].to_vec()) } else { None }
+}
+fn write_cache(path: &Path, version: u32, hash: u64, size: (usize, usize), data: &[u8]) -> std::io::Result<()>
+{
+	let mut bytes = Vec::with_capacity(32 + data.len());
+	bytes.extend_from_slice(&MAGIC);
+	bytes.extend_from_slice(&version.to_le_bytes());
+	bytes.extend_from_slice(&hash.to_le_bytes());
+	bytes.extend_from_slice(&(size.0 as u32).to_le_bytes());
+	bytes.extend_from_slice(&(size.1 as u32).to_le_bytes());
+	bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+	bytes.extend_from_slice(data);
+
+	if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+	// write to a temporary file and rename, so that concurrent readers never observe a partial file
+	// (named uniquely to the process and the call, so that concurrent writers never share one)
+	static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+	let temp_path = path.with_extension(format!("tcache.{}-{}.tmp", std::process::id(), TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)));
+	let result = std::fs::File::create(&temp_path).and_then(|mut fp| fp.write_all(&bytes)).and_then(|()| std::fs::rename(&temp_path, path));
+	if result.is_err() { let _ = std::fs::remove_file(&temp_path); }
+	result
+}
+
+/// Compresses the source, reusing the blocks stored under `key` in `cache_dir` if the source hash and algorithm version match
+pub fn compress_cached<Algorithm: CompressionAlgorithm>(key: &str, source: &[u8], size: (usize, usize), cache_dir: &Path) -> (Vec<u8>, CacheStatus)
+{
+	let path = cache_path::<Algorithm>(key, cache_dir);
+	let hash = content_hash(source, size);
+	if let Some(data) = read_cache(&path, Algorithm::version(), hash, size) { return (data, CacheStatus::Hit); }
+
+	let data = Algorithm::compress_parallel(source, size);
+	match write_cache(&path, Algorithm::version(), hash, size, &data)
+	{
+		Ok(()) => (data, CacheStatus::Stored),
+		Err(e) => (data, CacheStatus::StoreFailed(e))
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use {BC4, BC5};
+
+	#[test] fn hash_depends_on_extent()
+	{
+		assert_eq!(content_hash(&[1, 2, 3, 4], (2, 2)), content_hash(&[1, 2, 3, 4], (2, 2)));
+		assert!(content_hash(&[1, 2, 3, 4], (2, 2)) != content_hash(&[1, 2, 3, 4], (4, 1)));
+	}
+	#[test] fn stores_then_hits()
+	{
+		let dir = std::env::temp_dir().join(format!("texture_compression_cache_test_{}", std::process::id()));
+		let source = (0 .. 12 * 8 * 2).map(|n| (n * 5 % 256) as u8).collect::<Vec<_>>();
+		let (first, status) = compress_cached::<BC5>("test/areatex", &source, (12, 8), &dir);
+		match status { CacheStatus::Stored => (), s => panic!("unexpected status {:?}", s) }
+		let (second, status) = compress_cached::<BC5>("test/areatex", &source, (12, 8), &dir);
+		match status { CacheStatus::Hit => (), s => panic!("unexpected status {:?}", s) }
+		assert_eq!(first, second);
+		// changed source or another algorithm must not hit
+		let (_, status) = compress_cached::<BC5>("test/areatex", &source, (24, 4), &dir);
+		match status { CacheStatus::Stored => (), s => panic!("unexpected status {:?}", s) }
+		let (_, status) = compress_cached::<BC4>("test/areatex", &source[.. 12 * 8], (12, 8), &dir);
+		match status { CacheStatus::Stored => (), s => panic!("unexpected status {:?}", s) }
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+	#[test] fn concurrent_writers_do_not_collide()
+	{
+		let dir = std::env::temp_dir().join(format!("texture_compression_cache_race_test_{}", std::process::id()));
+		let source = (0 .. 16 * 16).map(|n| (n * 3 % 256) as u8).collect::<Vec<_>>();
+		let expected = BC4::compress(&source, (16, 16));
+		let threads = (0 .. 8).map(|_|
+		{
+			let (dir, source) = (dir.clone(), source.clone());
+			std::thread::spawn(move || (0 .. 8).all(|_| write_cache(&cache_path::<BC4>("race", &dir), 1, 0, (16, 16), &BC4::compress(&source, (16, 16))).is_ok()))
+		}).collect::<Vec<_>>();
+		assert!(threads.into_iter().all(|t| t.join().unwrap()));
+		assert_eq!(read_cache(&cache_path::<BC4>("race", &dir), 1, 0, (16, 16)), Some(expected));
+		// no temporary files are left
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,119 @@
+// DirectDraw Surface(DDS) Container, always written with the DX10 extended header
+
+use std;
+use std::io::prelude::*;
+use super::{ContainerError, TextureFormat, CompressedTexture, BinaryWriterUtils, BinaryReaderUtils};
+
+const MAGIC: u32 = 0x20534444;	// "DDS "
+const HEADER_SIZE: u32 = 124;
+const PIXELFORMAT_SIZE: u32 = 32;
+
+const DDSD_CAPS: u32 = 0x01;
+const DDSD_HEIGHT: u32 = 0x02;
+const DDSD_WIDTH: u32 = 0x04;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDPF_FOURCC: u32 = 0x04;
+const DDSCAPS_COMPLEX: u32 = 0x08;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+fn fourcc(code: &[u8; 4]) -> u32 { u32::from_le_bytes(*code) }
+// Legacy FourCC codes of the formats(readers only)
+fn format_from_legacy_fourcc(code: u32) -> Option<TextureFormat>
+{
+	if code == fourcc(b"ATI1") || code == fourcc(b"BC4U") { Some(TextureFormat::BC4Unorm) }
+	else if code == fourcc(b"ATI2") || code == fourcc(b"BC5U") { Some(TextureFormat::BC5Unorm) }
+	else { None }
+}
+
+/// Writes the texture as DDS(fails with InvalidInput for formats without a DXGI_FORMAT, such as ETC2)
+pub fn write_dds<W: Write>(writer: &mut W, texture: &CompressedTexture) -> std::io::Result<()>
+{
+	let dxgi_format = texture.format.dxgi_format()
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{:?} cannot be stored in DDS", texture.format)))?;
+	let mipmapped = texture.levels.len() > 1;
+	let flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_LINEARSIZE | if mipmapped { DDSD_MIPMAPCOUNT } else { 0 };
+	let caps = DDSCAPS_TEXTURE | if mipmapped { DDSCAPS_COMPLEX | DDSCAPS_MIPMAP } else { 0 };
+
+	writer.write_u32_le(MAGIC)?;
+	// DDS_HEADER
+	for &v in &[HEADER_SIZE, flags, texture.size.1 as u32, texture.size.0 as u32, texture.levels[0].length as u32, 0, texture.levels.len() as u32]
+	{
+		writer.write_u32_le(v)?;
+	}
+	for _ in 0 .. 11 { writer.write_u32_le(0)?; }
+	// DDS_PIXELFORMAT
+	for &v in &[PIXELFORMAT_SIZE, DDPF_FOURCC, fourcc(b"DX10"), 0, 0, 0, 0, 0] { writer.write_u32_le(v)?; }
+	for &v in &[caps, 0, 0, 0, 0] { writer.write_u32_le(v)?; }
+	// DDS_HEADER_DXT10
+	for &v in &[dxgi_format, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 1, 0] { writer.write_u32_le(v)?; }
+
+	for n in 0 .. texture.levels.len() { writer.write_all(texture.level_data(n))?; }
+	Ok(())
+}
+
+/// Reads a DDS written by `write_dds`(or a legacy BC4/BC5 FourCC one)
+pub fn read_dds<R: Read>(reader: &mut R) -> Result<CompressedTexture, ContainerError>
+{
+	if reader.read_u32_le()? != MAGIC { return Err(ContainerError::SignatureMismatching("DDS")); }
+	let mut header = [0u32; 31];
+	for v in header.iter_mut() { *v = reader.read_u32_le()?; }
+	if header[0] != HEADER_SIZE || header[18] != PIXELFORMAT_SIZE { return Err(ContainerError::StructureSizeMismatching); }
+	let (height, width) = (header[2] as usize, header[3] as usize);
+	let level_count = if (header[1] & DDSD_MIPMAPCOUNT) != 0 { std::cmp::max(header[6], 1) as usize } else { 1 };
+	if (header[19] & DDPF_FOURCC) == 0 { return Err(ContainerError::UnsupportedFeature("uncompressed pixel format")); }
+
+	let format = if header[20] == fourcc(b"DX10")
+	{
+		let dxgi_format = reader.read_u32_le()?;
+		let (dimension, _, array_size, _) = (reader.read_u32_le()?, reader.read_u32_le()?, reader.read_u32_le()?, reader.read_u32_le()?);
+		if dimension != D3D10_RESOURCE_DIMENSION_TEXTURE2D { return Err(ContainerError::UnsupportedFeature("non-2D resource")); }
+		if array_size > 1 { return Err(ContainerError::UnsupportedFeature("texture array")); }
+		TextureFormat::from_dxgi_format(dxgi_format).ok_or(ContainerError::UnsupportedFormat(dxgi_format))?
+	}
+	else
+	{
+		format_from_legacy_fourcc(header[20]).ok_or(ContainerError::UnsupportedFormat(header[20]))?
+	};
+
+	let levels = CompressedTexture::packed_levels(format, (width, height), level_count)?;
+	let data = reader.read_bytes(levels.iter().fold(0, |a, l| a + l.length))?;
+	Ok(CompressedTexture { format, size: (width, height), levels, data })
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use {BC5, MipFilter};
+
+	#[test] fn roundtrip_with_mipmaps()
+	{
+		let source = (0 .. 24 * 12 * 2).map(|n| (n % 253) as u8).collect::<Vec<_>>();
+		let texture = CompressedTexture::compress::<BC5>(&source, (24, 12), Some(MipFilter::Box));
+		let mut file = Vec::new();
+		write_dds(&mut file, &texture).unwrap();
+		assert_eq!(&file[.. 4], b"DDS ");
+		assert_eq!(file.len(), 4 + 124 + 20 + texture.data.len());
+		assert_eq!(read_dds(&mut &file[..]).unwrap(), texture);
+	}
+	#[test] fn rejects_broken_headers()
+	{
+		let texture = CompressedTexture::compress::<BC5>(&vec![0u8; 16 * 16 * 2], (16, 16), Some(MipFilter::Box));
+		let mut file = Vec::new();
+		write_dds(&mut file, &texture).unwrap();
+		let patched = |offset: usize, v: &[u8]| { let mut f = file.clone(); f[offset .. offset + v.len()].copy_from_slice(v); f };
+
+		assert!(matches!(read_dds(&mut &file[.. file.len() - 1]), Err(ContainerError::IOError(_))));
+		assert!(matches!(read_dds(&mut &file[.. 100]), Err(ContainerError::IOError(_))));
+		// mipmap count
+		assert!(matches!(read_dds(&mut &patched(28, &200u32.to_le_bytes())[..]), Err(ContainerError::TooManyLevels(200))));
+		assert!(matches!(read_dds(&mut &patched(28, &6u32.to_le_bytes())[..]), Err(ContainerError::TooManyLevels(6))));
+		// extents: overflowing, or larger than the data(which is not allocated up front)
+		assert!(matches!(read_dds(&mut &patched(12, &[0xFF; 8])[..]), Err(ContainerError::TooLargeTexture)));
+		assert!(matches!(read_dds(&mut &patched(16, &(1u32 << 24).to_le_bytes())[..]), Err(ContainerError::IOError(_))));
+	}
+}
@@ -0,0 +1,208 @@
+// Khronos Texture Containers(KTX 1.1 and KTX 2.0)
+
+use std;
+use std::io::prelude::*;
+use super::{ContainerError, TextureFormat, CompressedTexture, BinaryWriterUtils, BinaryReaderUtils};
+
+const KTX1_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const KTX1_ENDIANNESS: u32 = 0x04030201;
+const KTX2_HEADER_SIZE: usize = 12 + 4 * 9 + 4 * 4 + 8 * 2;
+
+fn align(v: usize, a: usize) -> usize { v.div_ceil(a) * a }
+fn gcd(a: usize, b: usize) -> usize { if b == 0 { a } else { gcd(b, a % b) } }
+fn read_identifier<R: Read>(reader: &mut R, identifier: &[u8; 12], name: &'static str) -> Result<(), ContainerError>
+{
+	let mut id = [0u8; 12];
+	reader.read_exact(&mut id)?;
+	if &id != identifier { Err(ContainerError::SignatureMismatching(name)) } else { Ok(()) }
+}
+
+/// Writes the texture as KTX 1.1
+pub fn write_ktx<W: Write>(writer: &mut W, texture: &CompressedTexture) -> std::io::Result<()>
+{
+	let (internal_format, base_internal_format) = texture.format.gl_internal_format();
+	writer.write_all(&KTX1_IDENTIFIER)?;
+	for &v in &[KTX1_ENDIANNESS, 0 /* glType */, 1 /* glTypeSize */, 0 /* glFormat */, internal_format, base_internal_format,
+		texture.size.0 as u32, texture.size.1 as u32, 0 /* depth */, 0 /* array elements */, 1 /* faces */, texture.levels.len() as u32, 0 /* key-value bytes */]
+	{
+		writer.write_u32_le(v)?;
+	}
+	for n in 0 .. texture.levels.len()
+	{
+		let level = texture.level_data(n);
+		writer.write_u32_le(level.len() as u32)?;
+		writer.write_all(level)?;
+		writer.write_all(&[0u8; 3][.. align(level.len(), 4) - level.len()])?;
+	}
+	Ok(())
+}
+/// Reads a little-endian KTX 1.1 of a 2D texture
+pub fn read_ktx<R: Read>(reader: &mut R) -> Result<CompressedTexture, ContainerError>
+{
+	read_identifier(reader, &KTX1_IDENTIFIER, "KTX 11")?;
+	let mut header = [0u32; 13];
+	for v in header.iter_mut() { *v = reader.read_u32_le()?; }
+	if header[0] != KTX1_ENDIANNESS { return Err(ContainerError::UnsupportedFeature("big-endian KTX")); }
+	let format = TextureFormat::from_gl_internal_format(header[4]).ok_or(ContainerError::UnsupportedFormat(header[4]))?;
+	let size = (header[6] as usize, std::cmp::max(header[7], 1) as usize);
+	if header[8] > 1 || header[9] > 0 || header[10] != 1 { return Err(ContainerError::UnsupportedFeature("non-2D texture")); }
+	reader.skip_bytes(header[12] as usize)?;
+
+	let levels = CompressedTexture::packed_levels(format, size, std::cmp::max(header[11], 1) as usize)?;
+	let mut data = Vec::new();
+	for l in &levels
+	{
+		let image_size = reader.read_u32_le()? as usize;
+		if image_size != l.length { return Err(ContainerError::StructureSizeMismatching); }
+		data.extend_from_slice(&reader.read_bytes(image_size)?);
+		reader.skip_bytes(align(image_size, 4) - image_size)?;
+	}
+	Ok(CompressedTexture { format, size, levels, data })
+}
+
+// Basic Data Format Descriptor for the format
+fn build_dfd(format: TextureFormat) -> Vec<u8>
+{
+	let samples = format.df_samples();
+	let block_size = 24 + 16 * samples.len();
+	let (bw, bh) = format.block_extent();
+	let mut words = vec![
+		(4 + block_size) as u32,									// dfdTotalSize
+		0,															// vendorId = KHRONOS, descriptorType = BASICFORMAT
+		2 | ((block_size as u32) << 16),							// versionNumber = 1.3, descriptorBlockSize
+		format.df_color_model() as u32 | (1 << 8) | (1 << 16),		// BT709 primaries, LINEAR transfer, straight alpha
+		(bw as u32 - 1) | ((bh as u32 - 1) << 8),					// texelBlockDimension
+		format.block_bytes() as u32, 0								// bytesPlane0..7
+	];
+	for (offset, length, channel) in samples
+	{
+		// sampleLower/Upper: -1.0(signed) or 0.0 to 1.0 for float samples, the full integer range otherwise
+		let (lower, upper) = if channel & 0x80 != 0 { (if channel & 0x40 != 0 { 0xBF800000 } else { 0 }, 0x3F800000) } else { (0, 0xFFFFFFFF) };
+		words.extend_from_slice(&[offset as u32 | ((length as u32 - 1) << 16) | ((channel as u32) << 24), 0, lower, upper]);
+	}
+	words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect()
+}
+
+/// Writes the texture as KTX 2.0(no supercompression)
+pub fn write_ktx2<W: Write>(writer: &mut W, texture: &CompressedTexture) -> std::io::Result<()>
+{
+	let dfd = build_dfd(texture.format);
+	let level_index_size = 24 * texture.levels.len();
+	let dfd_offset = KTX2_HEADER_SIZE + level_index_size;
+	// levels are stored from the smallest, each aligned to lcm(block size, 4)
+	let block_bytes = texture.format.block_bytes();
+	let level_alignment = block_bytes * 4 / gcd(block_bytes, 4);
+	let mut level_offsets = vec![0; texture.levels.len()];
+	let mut cursor = dfd_offset + dfd.len();
+	for (n, l) in texture.levels.iter().enumerate().rev()
+	{
+		cursor = align(cursor, level_alignment);
+		level_offsets[n] = cursor;
+		cursor += l.length;
+	}
+
+	writer.write_all(&KTX2_IDENTIFIER)?;
+	for &v in &[texture.format.vk_format(), 1 /* typeSize */, texture.size.0 as u32, texture.size.1 as u32, 0 /* depth */, 0 /* layers */, 1 /* faces */,
+		texture.levels.len() as u32, 0 /* supercompression */, dfd_offset as u32, dfd.len() as u32, 0 /* kvd offset */, 0 /* kvd length */]
+	{
+		writer.write_u32_le(v)?;
+	}
+	writer.write_u64_le(0)?; writer.write_u64_le(0)?;	// sgd
+	for (l, &offset) in texture.levels.iter().zip(level_offsets.iter())
+	{
+		for &v in &[offset as u64, l.length as u64, l.length as u64] { writer.write_u64_le(v)?; }
+	}
+	writer.write_all(&dfd)?;
+	let mut written = dfd_offset + dfd.len();
+	for n in (0 .. texture.levels.len()).rev()
+	{
+		writer.write_all(&vec![0u8; level_offsets[n] - written])?;
+		writer.write_all(texture.level_data(n))?;
+		written = level_offsets[n] + texture.levels[n].length;
+	}
+	Ok(())
+}
+/// Reads a KTX 2.0 of a 2D texture without supercompression
+pub fn read_ktx2<R: Read>(reader: &mut R) -> Result<CompressedTexture, ContainerError>
+{
+	let mut file = Vec::new();
+	reader.read_to_end(&mut file)?;
+	let mut header = &file[..];
+	read_identifier(&mut header, &KTX2_IDENTIFIER, "KTX 20")?;
+	let mut fields = [0u32; 13];
+	for v in fields.iter_mut() { *v = header.read_u32_le()?; }
+	let format = TextureFormat::from_vk_format(fields[0]).ok_or(ContainerError::UnsupportedFormat(fields[0]))?;
+	let size = (fields[2] as usize, std::cmp::max(fields[3], 1) as usize);
+	if fields[4] > 0 || fields[5] > 0 || fields[6] != 1 { return Err(ContainerError::UnsupportedFeature("non-2D texture")); }
+	if fields[8] != 0 { return Err(ContainerError::UnsupportedFeature("supercompression")); }
+	header.skip_bytes(16)?;	// sgd
+
+	let levels = CompressedTexture::packed_levels(format, size, std::cmp::max(fields[7], 1) as usize)?;
+	let mut data = Vec::new();
+	for l in &levels
+	{
+		let (offset, length, _) = (header.read_u64_le()?, header.read_u64_le()?, header.read_u64_le()?);
+		let end = offset.checked_add(length).filter(|&e| length == l.length as u64 && e <= file.len() as u64)
+			.ok_or(ContainerError::StructureSizeMismatching)?;
+		data.extend_from_slice(&file[offset as usize .. end as usize]);
+	}
+	Ok(CompressedTexture { format, size, levels, data })
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use {BC4, MipFilter};
+
+	fn sample_texture() -> CompressedTexture
+	{
+		let source = (0 .. 20 * 9).map(|n| (n * 3 % 256) as u8).collect::<Vec<_>>();
+		CompressedTexture::compress::<BC4>(&source, (20, 9), Some(MipFilter::Kaiser))
+	}
+
+	#[test] fn ktx1_roundtrip()
+	{
+		let texture = sample_texture();
+		let mut file = Vec::new();
+		write_ktx(&mut file, &texture).unwrap();
+		assert_eq!(&file[.. 12], &KTX1_IDENTIFIER);
+		assert_eq!(read_ktx(&mut &file[..]).unwrap(), texture);
+	}
+	#[test] fn ktx2_roundtrip()
+	{
+		let texture = sample_texture();
+		let mut file = Vec::new();
+		write_ktx2(&mut file, &texture).unwrap();
+		assert_eq!(&file[.. 12], &KTX2_IDENTIFIER);
+		// smallest level comes first in the file
+		let last = texture.levels.len() - 1;
+		let base_offset = u64::from_le_bytes([file[80], file[81], file[82], file[83], file[84], file[85], file[86], file[87]]) as usize;
+		assert!(base_offset > KTX2_HEADER_SIZE + 24 * texture.levels.len() + texture.levels[last].length);
+		assert_eq!(base_offset % 8, 0);
+		assert_eq!(read_ktx2(&mut &file[..]).unwrap(), texture);
+	}
+	#[test] fn rejects_broken_headers()
+	{
+		let texture = sample_texture();
+		let (mut ktx1, mut ktx2) = (Vec::new(), Vec::new());
+		write_ktx(&mut ktx1, &texture).unwrap();
+		write_ktx2(&mut ktx2, &texture).unwrap();
+		let patched = |file: &[u8], offset: usize, v: &[u8]| { let mut f = file.to_vec(); f[offset .. offset + v.len()].copy_from_slice(v); f };
+
+		assert!(matches!(read_ktx(&mut &ktx1[.. ktx1.len() - 1]), Err(ContainerError::IOError(_))));
+		assert!(matches!(read_ktx(&mut &patched(&ktx1, 56, &200u32.to_le_bytes())[..]), Err(ContainerError::TooManyLevels(200))));
+		// extent not matching the image size
+		assert!(matches!(read_ktx(&mut &patched(&ktx1, 36, &[0xFF; 8])[..]), Err(ContainerError::StructureSizeMismatching)));
+		// key-value bytes beyond the end
+		assert!(matches!(read_ktx(&mut &patched(&ktx1, 60, &u32::MAX.to_le_bytes())[..]), Err(ContainerError::IOError(_))));
+
+		assert!(matches!(read_ktx2(&mut &ktx2[.. ktx2.len() - 1]), Err(ContainerError::StructureSizeMismatching)));
+		assert!(matches!(read_ktx2(&mut &ktx2[.. 60]), Err(ContainerError::IOError(_))));
+		assert!(matches!(read_ktx2(&mut &patched(&ktx2, 40, &200u32.to_le_bytes())[..]), Err(ContainerError::TooManyLevels(200))));
+		// level offset/length
+		assert!(matches!(read_ktx2(&mut &patched(&ktx2, 80, &u64::MAX.to_le_bytes())[..]), Err(ContainerError::StructureSizeMismatching)));
+		assert!(matches!(read_ktx2(&mut &patched(&ktx2, 88, &u64::MAX.to_le_bytes())[..]), Err(ContainerError::StructureSizeMismatching)));
+	}
+}
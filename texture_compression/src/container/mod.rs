@@ -0,0 +1,240 @@
+// Texture Containers(DDS, KTX/KTX2): Wraps compressed blocks with format and mip metadata
+
+use std;
+use std::io::prelude::*;
+use super::{CompressionAlgorithm, ImageView, CompressionOptions, MipFilter, MipLevel, compress_mipmapped_image};
+use super::mipmap::level_count;
+
+pub mod dds;
+pub mod ktx;
+
+/// Errors occured in reading containers
+#[derive(Debug)]
+pub enum ContainerError
+{
+	IOError(std::io::Error),
+	SignatureMismatching(&'static str), UnsupportedFormat(u32), UnsupportedFeature(&'static str),
+	StructureSizeMismatching,
+	/// More levels than the full mipmap chain of the extent
+	TooManyLevels(usize),
+	/// Byte length of the levels does not fit in the address space
+	TooLargeTexture
+}
+impl std::convert::From<std::io::Error> for ContainerError
+{
+	fn from(v: std::io::Error) -> ContainerError { ContainerError::IOError(v) }
+}
+
+/// Compressed pixel formats emitted by this crate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat
+{
+	BC4Unorm, BC5Unorm,
+	ETC2RGB8Unorm, ETC2RGBA8Unorm, EACR11Unorm, EACRG11Unorm,
+	ASTC4x4Unorm, ASTC6x6Unorm, ASTC8x8Unorm,
+	BC6HUfloat, BC6HSfloat
+}
+impl TextureFormat
+{
+	/// Texel extent of a block
+	pub fn block_extent(&self) -> (usize, usize)
+	{
+		match *self
+		{
+			TextureFormat::ASTC6x6Unorm => (6, 6), TextureFormat::ASTC8x8Unorm => (8, 8),
+			_ => (4, 4)
+		}
+	}
+	/// Byte size of a block
+	pub fn block_bytes(&self) -> usize
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm | TextureFormat::ETC2RGB8Unorm | TextureFormat::EACR11Unorm => 8,
+			_ => 16
+		}
+	}
+	/// Byte length of a level with the extent
+	pub fn level_length(&self, size: (usize, usize)) -> usize
+	{
+		let (bw, bh) = self.block_extent();
+		size.0.div_ceil(bw) * size.1.div_ceil(bh) * self.block_bytes()
+	}
+
+	/// DXGI_FORMAT value(for DDS DX10 header), None if Direct3D has no such format
+	pub fn dxgi_format(&self) -> Option<u32>
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm => Some(80), TextureFormat::BC5Unorm => Some(83),
+			TextureFormat::BC6HUfloat => Some(95), TextureFormat::BC6HSfloat => Some(96),
+			_ => None
+		}
+	}
+	pub fn from_dxgi_format(v: u32) -> Option<Self>
+	{
+		Self::all().iter().cloned().find(|f| f.dxgi_format() == Some(v))
+	}
+	/// VkFormat value(for KTX2)
+	pub fn vk_format(&self) -> u32
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm => 139, TextureFormat::BC5Unorm => 141,
+			TextureFormat::ETC2RGB8Unorm => 147, TextureFormat::ETC2RGBA8Unorm => 151,
+			TextureFormat::EACR11Unorm => 153, TextureFormat::EACRG11Unorm => 155,
+			TextureFormat::ASTC4x4Unorm => 157, TextureFormat::ASTC6x6Unorm => 165, TextureFormat::ASTC8x8Unorm => 171,
+			TextureFormat::BC6HUfloat => 143, TextureFormat::BC6HSfloat => 144
+		}
+	}
+	pub fn from_vk_format(v: u32) -> Option<Self>
+	{
+		Self::all().iter().cloned().find(|f| f.vk_format() == v)
+	}
+	/// (glInternalFormat, glBaseInternalFormat) pair(for KTX)
+	pub fn gl_internal_format(&self) -> (u32, u32)
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm => (0x8DBB /* GL_COMPRESSED_RED_RGTC1 */, 0x1903 /* GL_RED */),
+			TextureFormat::BC5Unorm => (0x8DBD /* GL_COMPRESSED_RG_RGTC2 */, 0x8227 /* GL_RG */),
+			TextureFormat::ETC2RGB8Unorm => (0x9274 /* GL_COMPRESSED_RGB8_ETC2 */, 0x1907 /* GL_RGB */),
+			TextureFormat::ETC2RGBA8Unorm => (0x9278 /* GL_COMPRESSED_RGBA8_ETC2_EAC */, 0x1908 /* GL_RGBA */),
+			TextureFormat::EACR11Unorm => (0x9270 /* GL_COMPRESSED_R11_EAC */, 0x1903 /* GL_RED */),
+			TextureFormat::EACRG11Unorm => (0x9272 /* GL_COMPRESSED_RG11_EAC */, 0x8227 /* GL_RG */),
+			TextureFormat::ASTC4x4Unorm => (0x93B0 /* GL_COMPRESSED_RGBA_ASTC_4x4_KHR */, 0x1908 /* GL_RGBA */),
+			TextureFormat::ASTC6x6Unorm => (0x93B4 /* GL_COMPRESSED_RGBA_ASTC_6x6_KHR */, 0x1908 /* GL_RGBA */),
+			TextureFormat::ASTC8x8Unorm => (0x93B7 /* GL_COMPRESSED_RGBA_ASTC_8x8_KHR */, 0x1908 /* GL_RGBA */),
+			TextureFormat::BC6HUfloat => (0x8E8F /* GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT */, 0x1907 /* GL_RGB */),
+			TextureFormat::BC6HSfloat => (0x8E8E /* GL_COMPRESSED_RGB_BPTC_SIGNED_FLOAT */, 0x1907 /* GL_RGB */)
+		}
+	}
+	pub fn from_gl_internal_format(v: u32) -> Option<Self>
+	{
+		Self::all().iter().cloned().find(|f| f.gl_internal_format().0 == v)
+	}
+	/// Khronos Data Format color model(for KTX2 DFD)
+	pub fn df_color_model(&self) -> u8
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm => 131, TextureFormat::BC5Unorm => 132,
+			TextureFormat::BC6HUfloat | TextureFormat::BC6HSfloat => 133,
+			TextureFormat::ASTC4x4Unorm | TextureFormat::ASTC6x6Unorm | TextureFormat::ASTC8x8Unorm => 162,
+			_ => 161	// ETC2(EAC included)
+		}
+	}
+	/// Khronos Data Format samples: (bit offset, bit length, channel id with the FLOAT(0x80)/SIGNED(0x40) qualifiers)
+	pub fn df_samples(&self) -> Vec<(u16, u8, u8)>
+	{
+		match *self
+		{
+			TextureFormat::BC4Unorm | TextureFormat::EACR11Unorm => vec![(0, 64, 0)],
+			TextureFormat::BC5Unorm | TextureFormat::EACRG11Unorm => vec![(0, 64, 0), (64, 64, 1)],
+			TextureFormat::ETC2RGB8Unorm => vec![(0, 64, 2)],
+			TextureFormat::ETC2RGBA8Unorm => vec![(0, 64, 15), (64, 64, 2)],
+			TextureFormat::BC6HUfloat => vec![(0, 128, 0x80)], TextureFormat::BC6HSfloat => vec![(0, 128, 0xC0)],
+			_ => vec![(0, 128, 0)]	// ASTC data
+		}
+	}
+
+	fn all() -> &'static [TextureFormat]
+	{
+		static ALL: [TextureFormat; 11] = [
+			TextureFormat::BC4Unorm, TextureFormat::BC5Unorm,
+			TextureFormat::ETC2RGB8Unorm, TextureFormat::ETC2RGBA8Unorm, TextureFormat::EACR11Unorm, TextureFormat::EACRG11Unorm,
+			TextureFormat::ASTC4x4Unorm, TextureFormat::ASTC6x6Unorm, TextureFormat::ASTC8x8Unorm,
+			TextureFormat::BC6HUfloat, TextureFormat::BC6HSfloat
+		];
+		&ALL
+	}
+}
+
+/// Compressed texture data with its format and mipmap layout
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedTexture
+{
+	pub format: TextureFormat,
+	/// Extent of the base level
+	pub size: (usize, usize),
+	pub levels: Vec<MipLevel>,
+	pub data: Vec<u8>
+}
+impl CompressedTexture
+{
+	/// Compresses a texture, optionally with the full mipmap chain
+	pub fn compress<Algorithm: CompressionAlgorithm>(source: &[u8], size: (usize, usize), mip_filter: Option<MipFilter>) -> Self
+	{
+		Self::compress_image::<Algorithm>(&ImageView::new(source, size, Algorithm::source_channels()), &CompressionOptions::default(), mip_filter)
+	}
+	/// `compress` with encoder options
+	pub fn compress_image<Algorithm: CompressionAlgorithm>(image: &ImageView, options: &CompressionOptions, mip_filter: Option<MipFilter>) -> Self
+	{
+		let (data, levels) = match mip_filter
+		{
+			Some(filter) => compress_mipmapped_image::<Algorithm>(image, options, filter),
+			None =>
+			{
+				let data = Algorithm::compress_image(image, options);
+				let level = MipLevel { size: image.size, offset: 0, length: data.len() };
+				(data, vec![level])
+			}
+		};
+		CompressedTexture { format: Algorithm::format(), size: image.size, levels, data }
+	}
+	/// Builds tightly packed level layout for `count` levels(read from a header, so that it is validated)
+	pub fn packed_levels(format: TextureFormat, size: (usize, usize), count: usize) -> Result<Vec<MipLevel>, ContainerError>
+	{
+		if count > level_count(size) { return Err(ContainerError::TooManyLevels(count)); }
+		let (bw, bh) = format.block_extent();
+		let mut offset: usize = 0;
+		(0 .. count).map(|n|
+		{
+			let lsize = (std::cmp::max(size.0.checked_shr(n as u32).unwrap_or(0), 1), std::cmp::max(size.1.checked_shr(n as u32).unwrap_or(0), 1));
+			let length = lsize.0.div_ceil(bw).checked_mul(lsize.1.div_ceil(bh)).and_then(|b| b.checked_mul(format.block_bytes()));
+			let level = MipLevel { size: lsize, offset, length: length.ok_or(ContainerError::TooLargeTexture)? };
+			offset = offset.checked_add(level.length).ok_or(ContainerError::TooLargeTexture)?;
+			Ok(level)
+		}).collect()
+	}
+	/// Bytes of the level
+	pub fn level_data(&self, index: usize) -> &[u8]
+	{
+		let l = &self.levels[index];
+		&self.data[l.offset .. l.offset + l.length]
+	}
+}
+
+// Helper functions for writing/reading little-endian integer values
+trait BinaryWriterUtils : Write
+{
+	fn write_u32_le(&mut self, v: u32) -> std::io::Result<()> { self.write_all(&v.to_le_bytes()) }
+	fn write_u64_le(&mut self, v: u64) -> std::io::Result<()> { self.write_all(&v.to_le_bytes()) }
+}
+impl<T> BinaryWriterUtils for T where T: Write {}
+trait BinaryReaderUtils : Read
+{
+	fn read_u32_le(&mut self) -> std::io::Result<u32>
+	{
+		let mut bytes = [0u8; 4];
+		self.read_exact(&mut bytes).map(|()| u32::from_le_bytes(bytes))
+	}
+	fn read_u64_le(&mut self) -> std::io::Result<u64>
+	{
+		let mut bytes = [0u8; 8];
+		self.read_exact(&mut bytes).map(|()| u64::from_le_bytes(bytes))
+	}
+	// reads without allocating the whole `count` first, so that a bogus length fails at the end of the data
+	fn read_bytes(&mut self, count: usize) -> std::io::Result<Vec<u8>> where Self: Sized
+	{
+		let mut bytes = Vec::new();
+		self.by_ref().take(count as u64).read_to_end(&mut bytes)?;
+		if bytes.len() == count { Ok(bytes) } else { Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated data")) }
+	}
+	fn skip_bytes(&mut self, count: usize) -> std::io::Result<()> where Self: Sized
+	{
+		let skipped = std::io::copy(&mut self.by_ref().take(count as u64), &mut std::io::sink())?;
+		if skipped == count as u64 { Ok(()) } else { Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated data")) }
+	}
+}
+impl<T> BinaryReaderUtils for T where T: Read {}
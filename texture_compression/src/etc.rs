@@ -0,0 +1,577 @@
+// ETC2/EAC Compression Algorithms(for OpenGL ES 3.0 / Vulkan mobile targets)
+// Block layouts follow the Khronos Data Format Specification, section "ETC2 Compressed Texture Image Formats".
+// Blocks are stored as big-endian 64-bit words; pixel indices address texels in column-major order(i = x * 4 + y)
+
+use super::{CompressionAlgorithm, TextureFormat, ImageView, CompressionOptions, Quality};
+use blocks;
+
+const BLOCK_LEN: usize = 4;
+
+/// Intensity modifiers of ETC1 individual/differential modes(+a and +b, negated for the upper indices)
+static ETC1_MODIFIERS: [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+/// Paint color distances of ETC2 T and H modes
+static ETC2_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+/// EAC modifier tables
+static EAC_MODIFIERS: [[i32; 8]; 16] = [
+	[-3, -6, -9, -15, 2, 5, 8, 14], [-3, -7, -10, -13, 2, 6, 9, 12], [-2, -5, -8, -13, 1, 4, 7, 12], [-2, -4, -6, -13, 1, 3, 5, 12],
+	[-3, -6, -8, -12, 2, 5, 7, 11], [-3, -7, -9, -11, 2, 6, 8, 10], [-4, -7, -8, -11, 3, 6, 7, 10], [-3, -5, -8, -11, 2, 4, 7, 10],
+	[-2, -6, -8, -10, 1, 5, 7, 9], [-2, -5, -8, -10, 1, 4, 7, 9], [-2, -4, -8, -10, 1, 3, 7, 9], [-2, -5, -7, -10, 1, 4, 6, 9],
+	[-3, -4, -7, -10, 2, 3, 6, 9], [-1, -2, -3, -10, 0, 1, 2, 9], [-4, -6, -8, -9, 3, 5, 7, 8], [-3, -5, -7, -9, 2, 4, 6, 8]
+];
+
+// Bit Field Utilities: `hi` is the index of the most significant bit of the field
+fn bits(block: u64, hi: u32, count: u32) -> i32 { ((block >> (hi + 1 - count)) & ((1 << count) - 1)) as i32 }
+fn put_bits(block: &mut u64, hi: u32, count: u32, value: i32)
+{
+	let mask = ((1u64 << count) - 1) << (hi + 1 - count);
+	*block = (*block & !mask) | (((value as u64) << (hi + 1 - count)) & mask);
+}
+fn extend4(v: i32) -> i32 { (v << 4) | v }
+fn extend5(v: i32) -> i32 { (v << 3) | (v >> 2) }
+fn extend6(v: i32) -> i32 { (v << 2) | (v >> 4) }
+fn extend7(v: i32) -> i32 { (v << 1) | (v >> 6) }
+fn signed3(v: i32) -> i32 { if v >= 4 { v - 8 } else { v } }
+fn clamp8(v: i32) -> i32 { v.clamp(0, 255) }
+fn pixel_index(block: u64, x: usize, y: usize) -> usize
+{
+	let i = x * BLOCK_LEN + y;
+	((((block >> (16 + i)) & 1) << 1) | ((block >> i) & 1)) as usize
+}
+fn put_pixel_index(block: &mut u64, x: usize, y: usize, index: usize)
+{
+	let i = x * BLOCK_LEN + y;
+	*block |= ((index as u64 >> 1) << (16 + i)) | ((index as u64 & 1) << i);
+}
+
+/// Color modes of an ETC2 RGB block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Etc2Mode { Individual, Differential, T, H, Planar }
+fn etc2_mode(block: u64) -> Etc2Mode
+{
+	if (block >> 33) & 1 == 0 { return Etc2Mode::Individual; }
+	// an overflowing differential color selects one of the ETC2 modes
+	let overflows = |hi| { let v = bits(block, hi, 5) + signed3(bits(block, hi - 5, 3)); !(0 ..= 31).contains(&v) };
+	if overflows(63) { Etc2Mode::T }
+	else if overflows(55) { Etc2Mode::H }
+	else if overflows(47) { Etc2Mode::Planar }
+	else { Etc2Mode::Differential }
+}
+
+// Texels selecting one of 4 paint colors by the pixel indices(T and H modes)
+fn paint_block(block: u64, colors: [[i32; 3]; 4]) -> [[i32; 3]; 16]
+{
+	let mut texels = [[0; 3]; 16];
+	for (n, t) in texels.iter_mut().enumerate()
+	{
+		let c = colors[pixel_index(block, n % BLOCK_LEN, n / BLOCK_LEN)];
+		*t = [clamp8(c[0]), clamp8(c[1]), clamp8(c[2])];
+	}
+	texels
+}
+fn offset_color(c: [i32; 3], d: i32) -> [i32; 3] { [c[0] + d, c[1] + d, c[2] + d] }
+/// Decodes an ETC2 RGB block into row-major texels
+fn decode_etc2_rgb(block: u64) -> [[i32; 3]; 16]
+{
+	let mut texels = [[0; 3]; 16];
+	match etc2_mode(block)
+	{
+		mode @ Etc2Mode::Individual | mode @ Etc2Mode::Differential =>
+		{
+			let bases = if mode == Etc2Mode::Individual
+			{
+				[[extend4(bits(block, 63, 4)), extend4(bits(block, 55, 4)), extend4(bits(block, 47, 4))],
+				 [extend4(bits(block, 59, 4)), extend4(bits(block, 51, 4)), extend4(bits(block, 43, 4))]]
+			}
+			else
+			{
+				let (r, g, b) = (bits(block, 63, 5), bits(block, 55, 5), bits(block, 47, 5));
+				[[extend5(r), extend5(g), extend5(b)],
+				 [extend5(r + signed3(bits(block, 58, 3))), extend5(g + signed3(bits(block, 50, 3))), extend5(b + signed3(bits(block, 42, 3)))]]
+			};
+			let tables = [bits(block, 39, 3) as usize, bits(block, 36, 3) as usize];
+			let flipped = (block >> 32) & 1 == 1;
+			for (n, t) in texels.iter_mut().enumerate()
+			{
+				let (x, y) = (n % BLOCK_LEN, n / BLOCK_LEN);
+				let sub = if flipped { y / 2 } else { x / 2 };
+				let m = ETC1_MODIFIERS[tables[sub]];
+				let d = [m[0], m[1], -m[0], -m[1]][pixel_index(block, x, y)];
+				*t = [clamp8(bases[sub][0] + d), clamp8(bases[sub][1] + d), clamp8(bases[sub][2] + d)];
+			}
+		},
+		Etc2Mode::T =>
+		{
+			let c1 = [extend4((bits(block, 60, 2) << 2) | bits(block, 57, 2)), extend4(bits(block, 55, 4)), extend4(bits(block, 51, 4))];
+			let c2 = [extend4(bits(block, 47, 4)), extend4(bits(block, 43, 4)), extend4(bits(block, 39, 4))];
+			let d = ETC2_DISTANCES[((bits(block, 35, 2) << 1) | bits(block, 32, 1)) as usize];
+			texels = paint_block(block, [c1, offset_color(c2, d), c2, offset_color(c2, -d)]);
+		},
+		Etc2Mode::H =>
+		{
+			let q1 = [bits(block, 62, 4), (bits(block, 58, 3) << 1) | bits(block, 52, 1), (bits(block, 51, 1) << 3) | bits(block, 49, 3)];
+			let q2 = [bits(block, 46, 4), bits(block, 42, 4), bits(block, 38, 4)];
+			let ordered = ((q1[0] << 8) | (q1[1] << 4) | q1[2]) >= ((q2[0] << 8) | (q2[1] << 4) | q2[2]);
+			let d = ETC2_DISTANCES[((bits(block, 34, 1) << 2) | (bits(block, 32, 1) << 1) | ordered as i32) as usize];
+			let (c1, c2) = ([extend4(q1[0]), extend4(q1[1]), extend4(q1[2])], [extend4(q2[0]), extend4(q2[1]), extend4(q2[2])]);
+			texels = paint_block(block, [offset_color(c1, d), offset_color(c1, -d), offset_color(c2, d), offset_color(c2, -d)]);
+		},
+		Etc2Mode::Planar =>
+		{
+			let o = [extend6(bits(block, 62, 6)), extend7((bits(block, 56, 1) << 6) | bits(block, 54, 6)),
+				extend6((bits(block, 48, 1) << 5) | (bits(block, 44, 2) << 3) | bits(block, 41, 3))];
+			let h = [extend6((bits(block, 38, 5) << 1) | bits(block, 32, 1)), extend7(bits(block, 31, 7)), extend6(bits(block, 24, 6))];
+			let v = [extend6(bits(block, 18, 6)), extend7(bits(block, 12, 7)), extend6(bits(block, 5, 6))];
+			for (n, t) in texels.iter_mut().enumerate()
+			{
+				let (x, y) = ((n % BLOCK_LEN) as i32, (n / BLOCK_LEN) as i32);
+				for c in 0 .. 3 { t[c] = clamp8((x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2); }
+			}
+		}
+	}
+	texels
+}
+
+/// Weights of the color error: per channel, and per texel(the alpha for alpha-weighted error, otherwise 1)
+struct ColorMetric { channels: [i64; 3], texels: [i64; 16] }
+impl ColorMetric
+{
+	fn new(texels: &[u8], channels: usize, options: &CompressionOptions) -> Self
+	{
+		let w = &options.channel_weights;
+		let mut metric = ColorMetric { channels: [w[0] as i64, w[1] as i64, w[2] as i64], texels: [1; 16] };
+		if options.alpha_weighted && channels == 4
+		{
+			for (n, t) in metric.texels.iter_mut().enumerate() { *t = texels[n * 4 + 3] as i64; }
+		}
+		metric
+	}
+}
+
+fn color_error(a: [i32; 3], b: &[u8], metric: &ColorMetric) -> i64
+{
+	(0 .. 3).map(|c| metric.channels[c] * ((a[c] - b[c] as i32) * (a[c] - b[c] as i32)) as i64).sum()
+}
+fn block_error(block: u64, texels: &[u8], metric: &ColorMetric) -> i64
+{
+	decode_etc2_rgb(block).iter().zip(texels.chunks(3)).zip(metric.texels.iter()).map(|((&d, s), &w)| w * color_error(d, s, metric)).sum()
+}
+
+/// Texel positions of a sub-block
+fn sub_block_texels(flipped: bool, sub: usize) -> Vec<(usize, usize)>
+{
+	(0 .. BLOCK_LEN * BLOCK_LEN).map(|n| (n % BLOCK_LEN, n / BLOCK_LEN))
+		.filter(|&(x, y)| (if flipped { y / 2 } else { x / 2 }) == sub).collect()
+}
+/// Chooses the modifier table and indices of a sub-block with the base color: returns (table, error)
+fn fit_sub_block(block: &mut u64, texels: &[u8], positions: &[(usize, usize)], base: [i32; 3], metric: &ColorMetric) -> (usize, i64)
+{
+	let fits = ETC1_MODIFIERS.iter().map(|m|
+	{
+		positions.iter().map(|&(x, y)|
+		{
+			let n = x + y * BLOCK_LEN;
+			let (i, e) = [m[0], m[1], -m[0], -m[1]].iter().enumerate()
+				.map(|(i, &d)| (i, color_error([clamp8(base[0] + d), clamp8(base[1] + d), clamp8(base[2] + d)], &texels[n * 3 ..], metric)))
+				.min_by_key(|&(_, e)| e).unwrap();
+			(i, metric.texels[n] * e)
+		}).collect::<Vec<_>>()
+	}).collect::<Vec<_>>();
+	let (table, indices) = fits.iter().enumerate().min_by_key(|&(_, f)| f.iter().map(|&(_, e)| e).sum::<i64>()).unwrap();
+	for (&(x, y), &(index, _)) in positions.iter().zip(indices.iter()) { put_pixel_index(block, x, y, index); }
+	(table, indices.iter().map(|&(_, e)| e).sum())
+}
+/// Individual or differential mode candidate with the flip direction
+fn encode_etc1_mode(texels: &[u8], flipped: bool, differential: bool, metric: &ColorMetric) -> Option<u64>
+{
+	let positions = [sub_block_texels(flipped, 0), sub_block_texels(flipped, 1)];
+	// weighted averages(a sub-block without any weight falls back to the plain average)
+	let averages = [0, 1].iter().map(|&s|
+	{
+		let ignored = positions[s].iter().all(|&(x, y)| metric.texels[x + y * BLOCK_LEN] == 0);
+		let (mut sum, mut total) = ([0i64; 3], 0i64);
+		for &(x, y) in &positions[s]
+		{
+			let n = x + y * BLOCK_LEN;
+			let w = if ignored { 1 } else { metric.texels[n] };
+			for c in 0 .. 3 { sum[c] += texels[n * 3 + c] as i64 * w; }
+			total += w;
+		}
+		[sum[0] as f32 / total as f32, sum[1] as f32 / total as f32, sum[2] as f32 / total as f32]
+	}).collect::<Vec<_>>();
+	let quantize = |v: f32, max: i32| ((v * max as f32 / 255.0 + 0.5) as i32).min(max);
+
+	let mut block = if flipped { 1u64 << 32 } else { 0 };
+	let bases = if differential
+	{
+		let q: Vec<[i32; 3]> = averages.iter().map(|a| [quantize(a[0], 31), quantize(a[1], 31), quantize(a[2], 31)]).collect();
+		for (c, (&q0, &q1)) in q[0].iter().zip(q[1].iter()).enumerate()
+		{
+			if !(-4 ..= 3).contains(&(q1 - q0)) { return None; }
+			put_bits(&mut block, 63 - 8 * c as u32, 5, q0);
+			put_bits(&mut block, 58 - 8 * c as u32, 3, (q1 - q0) & 0b111);
+		}
+		block |= 1 << 33;
+		[[extend5(q[0][0]), extend5(q[0][1]), extend5(q[0][2])], [extend5(q[1][0]), extend5(q[1][1]), extend5(q[1][2])]]
+	}
+	else
+	{
+		let q: Vec<[i32; 3]> = averages.iter().map(|a| [quantize(a[0], 15), quantize(a[1], 15), quantize(a[2], 15)]).collect();
+		for (c, (&q0, &q1)) in q[0].iter().zip(q[1].iter()).enumerate()
+		{
+			put_bits(&mut block, 63 - 8 * c as u32, 4, q0);
+			put_bits(&mut block, 59 - 8 * c as u32, 4, q1);
+		}
+		[[extend4(q[0][0]), extend4(q[0][1]), extend4(q[0][2])], [extend4(q[1][0]), extend4(q[1][1]), extend4(q[1][2])]]
+	};
+	let (table0, _) = fit_sub_block(&mut block, texels, &positions[0], bases[0], metric);
+	let (table1, _) = fit_sub_block(&mut block, texels, &positions[1], bases[1], metric);
+	put_bits(&mut block, 39, 3, table0 as i32);
+	put_bits(&mut block, 36, 3, table1 as i32);
+	Some(block)
+}
+/// Planar mode candidate: least squares plane through the texels
+fn encode_planar(texels: &[u8]) -> u64
+{
+	let (mut o, mut h, mut v) = ([0; 3], [0; 3], [0; 3]);
+	for c in 0 .. 3
+	{
+		// c(x, y) = a + b * x + d * y, over the symmetric 4x4 grid(x and y are uncorrelated)
+		let (mut sum, mut sum_x, mut sum_y) = (0.0f32, 0.0f32, 0.0f32);
+		for n in 0 .. BLOCK_LEN * BLOCK_LEN
+		{
+			let (x, y, p) = ((n % BLOCK_LEN) as f32 - 1.5, (n / BLOCK_LEN) as f32 - 1.5, texels[n * 3 + c] as f32);
+			sum += p; sum_x += p * x; sum_y += p * y;
+		}
+		let (b, d) = (sum_x / 20.0, sum_y / 20.0);
+		let a = sum / 16.0 - 1.5 * b - 1.5 * d;
+		let max = if c == 1 { 127 } else { 63 };
+		let quantize = |v: f32| ((v * max as f32 / 255.0 + 0.5) as i32).max(0).min(max);
+		o[c] = quantize(a); h[c] = quantize(a + 4.0 * b); v[c] = quantize(a + 4.0 * d);
+	}
+
+	let mut block = 1u64 << 33;
+	put_bits(&mut block, 62, 6, o[0]);
+	put_bits(&mut block, 56, 1, o[1] >> 6); put_bits(&mut block, 54, 6, o[1]);
+	put_bits(&mut block, 48, 1, o[2] >> 5); put_bits(&mut block, 44, 2, o[2] >> 3); put_bits(&mut block, 41, 3, o[2]);
+	put_bits(&mut block, 38, 5, h[0] >> 1); put_bits(&mut block, 32, 1, h[0]);
+	put_bits(&mut block, 31, 7, h[1]); put_bits(&mut block, 24, 6, h[2]);
+	put_bits(&mut block, 18, 6, v[0]); put_bits(&mut block, 12, 7, v[1]); put_bits(&mut block, 5, 6, v[2]);
+	// the remaining bits(63, 55, 47..45, 42) only steer the mode detection
+	(0 .. 64).map(|n|
+	{
+		let mut candidate = block;
+		put_bits(&mut candidate, 63, 1, n); put_bits(&mut candidate, 55, 1, n >> 1);
+		put_bits(&mut candidate, 47, 3, n >> 2); put_bits(&mut candidate, 42, 1, n >> 5);
+		candidate
+	}).find(|&b| etc2_mode(b) == Etc2Mode::Planar).unwrap()
+}
+/// Encodes an ETC2 RGB block from row-major RGB texels(Fast quality only tries unflipped individual/differential modes)
+fn encode_etc2_rgb(texels: &[u8], quality: Quality, metric: &ColorMetric) -> u64
+{
+	if quality == Quality::Fast
+	{
+		let candidates = encode_etc1_mode(texels, false, true, metric).into_iter().chain(encode_etc1_mode(texels, false, false, metric));
+		return candidates.min_by_key(|&b| block_error(b, texels, metric)).unwrap();
+	}
+	let mut candidates = vec![encode_planar(texels)];
+	for &flipped in &[false, true]
+	{
+		candidates.extend(encode_etc1_mode(texels, flipped, false, metric));
+		candidates.extend(encode_etc1_mode(texels, flipped, true, metric));
+	}
+	candidates.into_iter().min_by_key(|&b| block_error(b, texels, metric)).unwrap()
+}
+
+/// Decoded value of an EAC texel: 8-bit for ETC2 alpha, 11-bit for R11
+fn eac_value(base: i32, multiplier: i32, modifier: i32, eleven_bit: bool) -> i32
+{
+	if eleven_bit
+	{
+		let m = if multiplier == 0 { modifier } else { modifier * multiplier * 8 };
+		(base * 8 + 4 + m).clamp(0, 2047)
+	}
+	else { clamp8(base + modifier * multiplier) }
+}
+fn decode_eac(block: u64, eleven_bit: bool) -> [i32; 16]
+{
+	let (base, multiplier, table) = (bits(block, 63, 8), bits(block, 55, 4), &EAC_MODIFIERS[bits(block, 51, 4) as usize]);
+	let mut texels = [0; 16];
+	for (n, t) in texels.iter_mut().enumerate()
+	{
+		let i = (n % BLOCK_LEN) * BLOCK_LEN + n / BLOCK_LEN;
+		*t = eac_value(base, multiplier, table[((block >> (45 - 3 * i)) & 0b111) as usize], eleven_bit);
+	}
+	texels
+}
+/// Encodes an EAC block from row-major target values(8-bit, or 11-bit for R11).
+/// Multipliers and base values are searched around the estimates, in a window widened by the quality
+fn encode_eac(values: &[i32; 16], eleven_bit: bool, quality: Quality) -> u64
+{
+	let window = match quality { Quality::Fast => 0, Quality::Normal => 1, Quality::High => 2 };
+	let (lo, hi) = values.iter().fold((values[0], values[0]), |(mn, mx), &v| (mn.min(v), mx.max(v)));
+	let scale = if eleven_bit { 8.0 } else { 1.0 };
+	let mut best = (i64::MAX, 0u64);
+	for (t, table) in EAC_MODIFIERS.iter().enumerate()
+	{
+		let span = (table[7] - table[3]) as f32 * scale;
+		let estimated = ((hi - lo) as f32 / span).ceil() as i32;
+		let min_multiplier = if eleven_bit { 0 } else { 1 };
+		for multiplier in (estimated - window .. estimated + window + 1).map(|m| m.max(min_multiplier).min(15))
+		{
+			let center = (lo + hi) as f32 / 2.0 - if eleven_bit { 4.0 } else { 0.0 }
+				- (table[3] + table[7]) as f32 / 2.0 * if eleven_bit && multiplier == 0 { 1.0 } else { multiplier as f32 * scale };
+			let estimated_base = (center / scale).round() as i32;
+			for base in (estimated_base - window .. estimated_base + window + 1).map(|b| b.clamp(0, 255))
+			{
+				let mut block = ((base as u64) << 56) | ((multiplier as u64) << 52) | ((t as u64) << 48);
+				let mut error = 0i64;
+				for (n, &v) in values.iter().enumerate()
+				{
+					let (index, e) = table.iter().enumerate()
+						.map(|(i, &m)| (i, (eac_value(base, multiplier, m, eleven_bit) - v).abs() as i64))
+						.min_by_key(|&(_, e)| e).unwrap();
+					error += e * e;
+					block |= (index as u64) << (45 - 3 * ((n % BLOCK_LEN) * BLOCK_LEN + n / BLOCK_LEN));
+				}
+				if error < best.0 { best = (error, block); }
+			}
+		}
+	}
+	best.1
+}
+fn to_eleven_bit(v: u8) -> i32 { (v as i32 * 2047 + 127) / 255 }
+fn from_eleven_bit(v: i32) -> u8 { ((v * 255 + 1023) / 2047) as u8 }
+/// Target values of a channel of row-major interleaved texels
+fn channel_values(texels: &[u8], channels: usize, channel: usize, eleven_bit: bool) -> [i32; 16]
+{
+	let mut values = [0; 16];
+	for (n, v) in values.iter_mut().enumerate()
+	{
+		let t = texels[n * channels + channel];
+		*v = if eleven_bit { to_eleven_bit(t) } else { t as i32 };
+	}
+	values
+}
+
+/// Encodes a block of the format from row-major interleaved texels
+fn encode_block(texels: &[u8], format: TextureFormat, options: &CompressionOptions, dest: &mut [u8])
+{
+	let quality = options.quality;
+	let words = match format
+	{
+		TextureFormat::ETC2RGB8Unorm => vec![encode_etc2_rgb(texels, quality, &ColorMetric::new(texels, 3, options))],
+		TextureFormat::ETC2RGBA8Unorm =>
+		{
+			let rgb = texels.chunks(4).flat_map(|t| t[.. 3].iter().cloned()).collect::<Vec<_>>();
+			let metric = ColorMetric::new(texels, 4, options);
+			vec![encode_eac(&channel_values(texels, 4, 3, false), false, quality), encode_etc2_rgb(&rgb, quality, &metric)]
+		},
+		TextureFormat::EACR11Unorm => vec![encode_eac(&channel_values(texels, 1, 0, true), true, quality)],
+		TextureFormat::EACRG11Unorm => (0 .. 2).map(|c| encode_eac(&channel_values(texels, 2, c, true), true, quality)).collect(),
+		_ => unreachable!()
+	};
+	for (d, w) in dest.chunks_mut(8).zip(words) { d.copy_from_slice(&w.to_be_bytes()); }
+}
+/// Decodes a block of the format into row-major interleaved texels
+fn decode_block(block: &[u8], format: TextureFormat, texels: &mut [u8])
+{
+	let word = |n: usize| { let mut b = [0u8; 8]; b.copy_from_slice(&block[n * 8 .. n * 8 + 8]); u64::from_be_bytes(b) };
+	match format
+	{
+		TextureFormat::ETC2RGB8Unorm => for (t, c) in texels.chunks_mut(3).zip(decode_etc2_rgb(word(0)).iter())
+		{
+			for k in 0 .. 3 { t[k] = c[k] as u8; }
+		},
+		TextureFormat::ETC2RGBA8Unorm =>
+		{
+			let (alpha, rgb) = (decode_eac(word(0), false), decode_etc2_rgb(word(1)));
+			for (n, t) in texels.chunks_mut(4).enumerate()
+			{
+				for k in 0 .. 3 { t[k] = rgb[n][k] as u8; }
+				t[3] = alpha[n] as u8;
+			}
+		},
+		TextureFormat::EACR11Unorm => for (t, &v) in texels.iter_mut().zip(decode_eac(word(0), true).iter()) { *t = from_eleven_bit(v); },
+		TextureFormat::EACRG11Unorm => for c in 0 .. 2
+		{
+			for (n, &v) in decode_eac(word(c), true).iter().enumerate() { texels[n * 2 + c] = from_eleven_bit(v); }
+		},
+		_ => unreachable!()
+	}
+}
+
+fn compress_with<A: CompressionAlgorithm>(image: &ImageView, options: &CompressionOptions) -> Vec<u8>
+{
+	let channels = A::source_channels();
+	let (source, size) = (image.pack(&options.swizzle[.. channels]), image.size);
+	assert!(size.0 > 0 && size.1 > 0);							// at least a texel
+
+	let format = A::format();
+	let encoder = |bx, by, dest: &mut [u8]|
+	{
+		encode_block(&blocks::gather_block(&source, size, channels, (bx, by), (BLOCK_LEN, BLOCK_LEN)), format, options, dest)
+	};
+	if options.parallel { blocks::compress_blocks_parallel(size, (BLOCK_LEN, BLOCK_LEN), format.block_bytes(), encoder) }
+	else { blocks::compress_blocks(size, (BLOCK_LEN, BLOCK_LEN), format.block_bytes(), encoder) }
+}
+fn decompress_with<A: CompressionAlgorithm>(compressed: &[u8], size: (usize, usize)) -> Vec<u8>
+{
+	let format = A::format();
+	blocks::decompress_blocks(compressed, size, (BLOCK_LEN, BLOCK_LEN), format.block_bytes(), A::source_channels(), |block, texels| decode_block(block, format, texels))
+}
+
+/// ETC2 RGB8: 3-channel source
+pub enum ETC2RGB {}
+/// ETC2 RGBA8(EAC alpha): 4-channel source
+pub enum ETC2RGBA8 {}
+/// EAC R11: 1-channel source(counterpart of BC4)
+pub enum EACR11 {}
+/// EAC RG11: 2-channel source(counterpart of BC5)
+pub enum EACRG11 {}
+macro_rules! ImplEtcAlgorithm
+{
+	($t: ty, $channels: expr, $format: expr) =>
+	{
+		impl CompressionAlgorithm for $t
+		{
+			fn source_channels() -> usize { $channels }
+			fn format() -> TextureFormat { $format }
+			fn version() -> u32 { 1 }
+			fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8> { compress_with::<Self>(image, options) }
+			fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8> { decompress_with::<Self>(compressed, size) }
+		}
+	}
+}
+ImplEtcAlgorithm!(ETC2RGB, 3, TextureFormat::ETC2RGB8Unorm);
+ImplEtcAlgorithm!(ETC2RGBA8, 4, TextureFormat::ETC2RGBA8Unorm);
+ImplEtcAlgorithm!(EACR11, 1, TextureFormat::EACR11Unorm);
+ImplEtcAlgorithm!(EACRG11, 2, TextureFormat::EACRG11Unorm);
+
+#[cfg(test)]
+mod testings
+{
+	use CompressionAlgorithm;
+	use metrics::{psnr, rmse};
+
+	#[test] fn decodes_individual_block()
+	{
+		// base colors (8, 4, 2) and (1, 3, 5) side by side, tables 0 and 7, all indices 1(+b)
+		let texels = super::decode_etc2_rgb(0x8143_251c_0000_ffff);
+		assert_eq!(texels[0], [0x88 + 8, 0x44 + 8, 0x22 + 8]);
+		assert_eq!(texels[2], [0x11 + 183, 0x33 + 183, 255]);
+	}
+	#[test] fn detects_etc2_modes()
+	{
+		// differential R = 31 with dR = +1 overflows into T mode, and so on
+		assert_eq!(super::etc2_mode(0xf900_0002_0000_0000), super::Etc2Mode::T);
+		assert_eq!(super::etc2_mode(0x00f9_0002_0000_0000), super::Etc2Mode::H);
+		assert_eq!(super::etc2_mode(0x0000_f902_0000_0000), super::Etc2Mode::Planar);
+		assert_eq!(super::etc2_mode(0x0808_0802_0000_0000), super::Etc2Mode::Differential);
+		assert_eq!(super::etc2_mode(0xf9f9_f900_0000_0000), super::Etc2Mode::Individual);
+	}
+	#[test] fn decodes_t_and_h_blocks()
+	{
+		// T mode: C1 = 0xf(R) / 0 / 0, C2 = 0x8 gray, distance 64; indices select 0, 1, 2, 3 along y
+		let mut block = 0xf900_0002_0000_0000u64;
+		super::put_bits(&mut block, 60, 2, 3); super::put_bits(&mut block, 57, 2, 3);
+		super::put_bits(&mut block, 55, 8, 0); super::put_bits(&mut block, 47, 12, 0x888);
+		super::put_bits(&mut block, 35, 2, 3); super::put_bits(&mut block, 32, 1, 1);
+		for y in 0 .. 4 { for x in 0 .. 4 { super::put_pixel_index(&mut block, x, y, y); } }
+		assert_eq!(super::etc2_mode(block), super::Etc2Mode::T);
+		let texels = super::decode_etc2_rgb(block);
+		assert_eq!(texels[0], [255, 0, 0]);
+		assert_eq!(texels[4], [0x88 + 64, 0x88 + 64, 0x88 + 64]);
+		assert_eq!(texels[8], [0x88, 0x88, 0x88]);
+		assert_eq!(texels[12], [0x88 - 64, 0x88 - 64, 0x88 - 64]);
+
+		// H mode: C1 = 0x2 gray, C2 = 0x1 gray, C1 >= C2 so the distance index LSB is 1(distance 6).
+		// differential G = 0 with a negative dG overflows
+		let mut block = 1u64 << 33;
+		super::put_bits(&mut block, 62, 4, 2); super::put_bits(&mut block, 58, 3, 1);
+		super::put_bits(&mut block, 50, 1, 1); super::put_bits(&mut block, 49, 3, 2);
+		super::put_bits(&mut block, 46, 12, 0x111);
+		for x in 0 .. 4 { for y in 0 .. 4 { super::put_pixel_index(&mut block, x, y, x); } }
+		assert_eq!(super::etc2_mode(block), super::Etc2Mode::H);
+		let texels = super::decode_etc2_rgb(block);
+		assert_eq!(texels[0], [0x22 + 6, 0x22 + 6, 0x22 + 6]);
+		assert_eq!(texels[1], [0x22 - 6, 0x22 - 6, 0x22 - 6]);
+		assert_eq!(texels[2], [0x11 + 6, 0x11 + 6, 0x11 + 6]);
+		assert_eq!(texels[3], [0x11 - 6, 0x11 - 6, 0x11 - 6]);
+	}
+	#[test] fn planar_block_roundtrip()
+	{
+		let texels = (0 .. 16).flat_map(|n| { let (x, y) = (n % 4, n / 4); vec![(x * 40 + 20) as u8, (y * 50 + 10) as u8, (x * 20 + y * 20) as u8] }).collect::<Vec<_>>();
+		let block = super::encode_planar(&texels);
+		assert_eq!(super::etc2_mode(block), super::Etc2Mode::Planar);
+		for (d, s) in super::decode_etc2_rgb(block).iter().zip(texels.chunks(3))
+		{
+			for c in 0 .. 3 { assert!((d[c] - s[c] as i32).abs() <= 4, "{:?} vs {:?}", d, s); }
+		}
+	}
+	#[test] fn eac_flat_and_extremes_are_exact()
+	{
+		for &v in &[0u8, 1, 77, 128, 200, 254, 255]
+		{
+			let flat = [v; 5 * 3];
+			assert_eq!(super::EACR11::decompress(&super::EACR11::compress(&flat, (5, 3)), (5, 3)), flat.to_vec());
+		}
+		let extremes = (0 .. 16).map(|n| if n % 3 == 0 { 255 } else { 0 }).collect::<Vec<u8>>();
+		assert_eq!(super::EACR11::decompress(&super::EACR11::compress(&extremes, (4, 4)), (4, 4)), extremes);
+		let rgba = extremes.iter().flat_map(|&a| vec![128, 128, 128, a]).collect::<Vec<_>>();
+		let decoded = super::ETC2RGBA8::decompress(&super::ETC2RGBA8::compress(&rgba, (4, 4)), (4, 4));
+		assert_eq!(decoded.iter().skip(3).step_by(4).cloned().collect::<Vec<_>>(), extremes);
+	}
+	#[test] fn codecs_roundtrip_within_tolerance()
+	{
+		fn check<A: CompressionAlgorithm>(size: (usize, usize), min_psnr: f64)
+		{
+			let channels = A::source_channels();
+			let source = (0 .. size.0 * size.1 * channels).map(|n|
+			{
+				let (x, y, c) = ((n / channels) % size.0, (n / channels) / size.0, n % channels);
+				(x * 100 / size.0 + y * 4 + c * 30) as u8
+			}).collect::<Vec<_>>();
+			let compressed = A::compress(&source, size);
+			assert_eq!(compressed.len(), A::format().level_length(size));
+			assert_eq!(A::compress_parallel(&source, size), compressed);
+			for p in psnr(&source, &A::decompress(&compressed, size), size, channels) { assert!(p > min_psnr, "{:?}: PSNR {}", A::format(), p); }
+		}
+		check::<super::ETC2RGB>((21, 14), 30.0);
+		check::<super::ETC2RGBA8>((21, 14), 30.0);
+		check::<super::EACR11>((21, 14), 40.0);
+		check::<super::EACRG11>((21, 14), 40.0);
+	}
+	#[test] fn weighting_follows_the_options()
+	{
+		use {ImageView, CompressionOptions};
+
+		// sprite: a smooth opaque disc over transparent texels of arbitrary color
+		let size = (12, 12);
+		let rgba = (0 .. size.0 * size.1).flat_map(|n|
+		{
+			let (x, y) = ((n % size.0) as i32 - 6, (n / size.0) as i32 - 6);
+			if x * x + y * y < 20 { vec![(120 + x * 8) as u8, (90 + y * 6) as u8, 60, 255] }
+			else { vec![(n * 97 % 256) as u8, (n * 61 % 256) as u8, (n * 29 % 256) as u8, 0] }
+		}).collect::<Vec<_>>();
+		let image = ImageView::new(&rgba[..], size, 4);
+		let opaque_error = |options: &CompressionOptions|
+		{
+			let decoded = super::ETC2RGBA8::decompress(&super::ETC2RGBA8::compress_image(&image, options), size);
+			rgba.chunks(4).zip(decoded.chunks(4)).filter(|&(s, _)| s[3] == 255)
+				.map(|(s, d)| (0 .. 3).map(|c| (s[c] as i32 - d[c] as i32).pow(2)).sum::<i32>()).sum::<i32>()
+		};
+		let weighted = CompressionOptions { alpha_weighted: true, .. CompressionOptions::default() };
+		assert!(opaque_error(&weighted) < opaque_error(&CompressionOptions::default()));
+
+		// a heavier channel is fitted at the expense of the others
+		let noisy = (0 .. 16 * 16 * 3).map(|n| ((n * 7919 + n / 48 * 31) % 256) as u8).collect::<Vec<_>>();
+		let channel_error = |weights: [u32; 4]|
+		{
+			let options = CompressionOptions { channel_weights: weights, .. CompressionOptions::default() };
+			let decoded = super::ETC2RGB::decompress(&super::ETC2RGB::compress_image(&ImageView::new(&noisy[..], (16, 16), 3), &options), (16, 16));
+			rmse(&noisy, &decoded, (16, 16), 3)
+		};
+		let (uniform, green) = (channel_error([1, 1, 1, 1]), channel_error([1, 16, 1, 1]));
+		assert!(green[1] < uniform[1], "{:?} {:?}", uniform, green);
+	}
+}
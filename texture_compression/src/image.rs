@@ -0,0 +1,179 @@
+// Encoder Inputs: typed image views and compression options
+
+use std;
+use half::f16;
+
+/// Texel storage of an image: 8-bit unsigned normalized, or 32/16-bit float(0.0 ..= 1.0 maps onto the 8-bit range)
+#[derive(Clone, Copy, Debug)]
+pub enum TexelData<'a> { U8(&'a [u8]), F32(&'a [f32]), F16(&'a [f16]) }
+impl<'a> TexelData<'a>
+{
+	fn len(&self) -> usize
+	{
+		match *self { TexelData::U8(d) => d.len(), TexelData::F32(d) => d.len(), TexelData::F16(d) => d.len() }
+	}
+}
+impl<'a> std::convert::From<&'a [u8]> for TexelData<'a> { fn from(v: &'a [u8]) -> Self { TexelData::U8(v) } }
+impl<'a> std::convert::From<&'a [f32]> for TexelData<'a> { fn from(v: &'a [f32]) -> Self { TexelData::F32(v) } }
+impl<'a> std::convert::From<&'a [f16]> for TexelData<'a> { fn from(v: &'a [f16]) -> Self { TexelData::F16(v) } }
+
+/// Read-only view of an interleaved image
+#[derive(Clone, Copy, Debug)]
+pub struct ImageView<'a>
+{
+	pub data: TexelData<'a>,
+	pub size: (usize, usize),
+	/// Interleaved channels per texel
+	pub channels: usize,
+	/// Elements(not texels) between the starts of two rows
+	pub pitch: usize
+}
+impl<'a> ImageView<'a>
+{
+	/// Tightly packed image
+	pub fn new<D: Into<TexelData<'a>>>(data: D, size: (usize, usize), channels: usize) -> Self
+	{
+		let data = data.into();
+		assert_eq!(size.0 * size.1 * channels, data.len());		// size matching
+		ImageView { data, size, channels, pitch: size.0 * channels }
+	}
+	/// Image whose rows are `pitch` elements apart(e.g. a region of a larger image)
+	pub fn with_pitch<D: Into<TexelData<'a>>>(data: D, size: (usize, usize), channels: usize, pitch: usize) -> Self
+	{
+		let data = data.into();
+		assert!(pitch >= size.0 * channels);
+		assert!(size.1 == 0 || (size.1 - 1) * pitch + size.0 * channels <= data.len());
+		ImageView { data, size, channels, pitch }
+	}
+
+	/// View of the rectangle at `origin`(shares the data and the pitch)
+	pub fn region(&self, origin: (usize, usize), size: (usize, usize)) -> ImageView<'a>
+	{
+		assert!(origin.0 + size.0 <= self.size.0 && origin.1 + size.1 <= self.size.1,
+			"region {:?}+{:?} exceeds the image extent {:?}", origin, size, self.size);
+		let start = self.index(origin.0, origin.1, 0);
+		let data = match self.data
+		{
+			TexelData::U8(d) => TexelData::U8(&d[start ..]),
+			TexelData::F32(d) => TexelData::F32(&d[start ..]),
+			TexelData::F16(d) => TexelData::F16(&d[start ..])
+		};
+		ImageView::with_pitch(data, size, self.channels, self.pitch)
+	}
+
+	/// A channel of the texel as 8-bit value
+	pub fn texel(&self, x: usize, y: usize, channel: usize) -> u8
+	{
+		match self.data
+		{
+			TexelData::U8(d) => d[self.index(x, y, channel)],
+			_ => (self.texel_f32(x, y, channel).clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+		}
+	}
+	/// A channel of the texel as float value: out of range values are kept(8-bit values are normalized)
+	pub fn texel_f32(&self, x: usize, y: usize, channel: usize) -> f32
+	{
+		let index = self.index(x, y, channel);
+		match self.data
+		{
+			TexelData::U8(d) => d[index] as f32 / 255.0,
+			TexelData::F32(d) => d[index],
+			TexelData::F16(d) => f32::from(d[index])
+		}
+	}
+	fn index(&self, x: usize, y: usize, channel: usize) -> usize { y * self.pitch + x * self.channels + channel }
+
+	/// Tightly packed 8-bit image of the channels listed in `swizzle`(in that order)
+	pub fn pack(&self, swizzle: &[usize]) -> Vec<u8> { self.pack_with(swizzle, |x, y, c| self.texel(x, y, c)) }
+	/// Tightly packed float image of the channels listed in `swizzle`(in that order)
+	pub fn pack_f32(&self, swizzle: &[usize]) -> Vec<f32> { self.pack_with(swizzle, |x, y, c| self.texel_f32(x, y, c)) }
+	fn pack_with<T, F: Fn(usize, usize, usize) -> T>(&self, swizzle: &[usize], fetch: F) -> Vec<T>
+	{
+		assert!(swizzle.iter().all(|&c| c < self.channels), "swizzle {:?} selects a channel out of {}", swizzle, self.channels);
+		let mut packed = Vec::with_capacity(self.size.0 * self.size.1 * swizzle.len());
+		for y in 0 .. self.size.1
+		{
+			for x in 0 .. self.size.0 { packed.extend(swizzle.iter().map(|&c| fetch(x, y, c))); }
+		}
+		packed
+	}
+}
+
+/// Encoder effort: encoders without a cheaper or finer search treat levels alike
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quality
+{
+	/// Skips endpoint refinement and secondary modes
+	Fast,
+	Normal,
+	/// Additionally searches around the chosen endpoints
+	High
+}
+
+/// Options for `CompressionAlgorithm::compress_image`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressionOptions
+{
+	/// Source channel read for each encoder channel(e.g. `[0, 3, ..]` feeds red and alpha into BC5)
+	pub swizzle: [usize; 4],
+	pub quality: Quality,
+	/// Encodes block rows on the rayon thread pool(the output is identical)
+	pub parallel: bool,
+	/// Relative weights of the squared error of each encoder channel, for encoders fitting channels together(ETC2 color, ASTC)
+	pub channel_weights: [u32; 4],
+	/// Scales the color error of each texel by its alpha(4-channel encoders): colors of fully transparent texels are ignored
+	pub alpha_weighted: bool,
+	/// BC4/BC5 blocks holding 0 or 255 keep them exact(a mask stays a mask), instead of taking the codec with the least error
+	pub exact_extremes: bool
+}
+impl CompressionOptions
+{
+	/// Channel weights approximating the luma contribution of red, green and blue
+	pub const PERCEPTUAL_WEIGHTS: [u32; 4] = [3, 6, 1, 1];
+}
+impl Default for CompressionOptions
+{
+	fn default() -> Self
+	{
+		CompressionOptions
+		{
+			swizzle: [0, 1, 2, 3], quality: Quality::Normal, parallel: false,
+			channel_weights: [1, 1, 1, 1], alpha_weighted: false, exact_extremes: true
+		}
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::ImageView;
+	use half::f16;
+
+	#[test] fn pack_applies_pitch_and_swizzle()
+	{
+		let data = [1u8, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0];
+		let image = ImageView::with_pitch(&data[..], (2, 2), 3, 8);
+		assert_eq!(image.pack(&[2, 0]), vec![3, 1, 6, 4, 9, 7, 12, 10]);
+		assert_eq!(image.pack(&[1]), vec![2, 5, 8, 11]);
+	}
+	#[test] fn float_texels_are_normalized()
+	{
+		let data = [0.0f32, 1.0, 0.5, -1.0, 2.0, 0.25];
+		assert_eq!(ImageView::new(&data[..], (3, 2), 1).pack(&[0]), vec![0, 255, 128, 0, 255, 64]);
+		let halves = data.iter().map(|&v| f16::from_f32(v)).collect::<Vec<_>>();
+		let image = ImageView::new(&halves[..], (3, 2), 1);
+		assert_eq!(image.pack(&[0]), vec![0, 255, 128, 0, 255, 64]);
+		assert_eq!(image.pack_f32(&[0]), data.to_vec());
+	}
+	#[test] fn regions_share_the_pitch()
+	{
+		let data = (0 .. 5 * 4 * 2).map(|n| n as u8).collect::<Vec<_>>();
+		let region = ImageView::new(&data[..], (5, 4), 2).region((2, 1), (3, 2));
+		assert_eq!(region.pack(&[1]), vec![15, 17, 19, 25, 27, 29]);
+		assert_eq!(region.region((1, 1), (1, 1)).pack(&[0, 1]), vec![26, 27]);
+	}
+	#[test] #[should_panic] fn swizzle_out_of_channels()
+	{
+		ImageView::new(&[0u8; 8][..], (2, 2), 2).pack(&[0, 2]);
+	}
+}
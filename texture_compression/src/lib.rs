@@ -1,9 +1,56 @@
-/// Texture Compression crate: Various Algorithms of Texture Compression implemented by Rustlang
+//! Texture Compression crate: Various Algorithms of Texture Compression implemented by Rustlang
 
+extern crate rayon;
+extern crate half;
+
+mod blocks;
+pub mod image;
+pub use image::{ImageView, TexelData, CompressionOptions, Quality};
 pub mod block_compression;
 pub use block_compression::{BC4, BC5};
+pub mod bc6h;
+pub use bc6h::{BC6HUF16, BC6HSF16};
+pub mod etc;
+pub use etc::{ETC2RGB, ETC2RGBA8, EACR11, EACRG11};
+pub mod astc;
+pub use astc::{ASTC4x4, ASTC6x6, ASTC8x8};
+pub mod mipmap;
+pub use mipmap::{MipFilter, MipLevel, compress_mipmapped, compress_mipmapped_image};
+pub mod container;
+pub use container::{TextureFormat, CompressedTexture, ContainerError};
+pub mod cache;
+pub use cache::{CacheStatus, compress_cached};
+pub mod atlas;
+pub use atlas::{AtlasRegion, CompressedEntry, CompressedBatch, compress_atlas, compress_layers};
+pub mod metrics;
+pub use metrics::{ChannelMetrics, BlockErrorMap, QualityReport, quality_report};
+pub mod normal_map;
+pub use normal_map::{height_to_normal_xy, compress_normal_map};
 
 pub trait CompressionAlgorithm
 {
-	fn compress(source: &[u8], size: (usize, usize)) -> Vec<u8>;
+	/// Number of interleaved 8-bit channels per texel in the source
+	fn source_channels() -> usize;
+	/// Format of the compressed blocks
+	fn format() -> TextureFormat;
+	/// Encoder revision: must be bumped whenever the output for the same input changes(invalidates cached blocks)
+	fn version() -> u32;
+	/// Compresses the image: `options.swizzle` picks the `source_channels()` channels the encoder reads
+	fn compress_image(image: &ImageView, options: &CompressionOptions) -> Vec<u8>;
+	/// Decodes compressed blocks back into the source layout(as a GPU would sample them)
+	fn decompress(compressed: &[u8], size: (usize, usize)) -> Vec<u8>;
+	/// Whether the encoder reads float texels(mipmap levels are then downsampled in float, keeping the HDR range)
+	fn float_source() -> bool { false }
+
+	/// Compresses a tightly packed 8-bit source of `source_channels()` channels with the default options
+	fn compress(source: &[u8], size: (usize, usize)) -> Vec<u8>
+	{
+		Self::compress_image(&ImageView::new(source, size, Self::source_channels()), &CompressionOptions::default())
+	}
+	/// Same output as `compress`, but encodes block rows on the rayon thread pool
+	fn compress_parallel(source: &[u8], size: (usize, usize)) -> Vec<u8>
+	{
+		let options = CompressionOptions { parallel: true, .. CompressionOptions::default() };
+		Self::compress_image(&ImageView::new(source, size, Self::source_channels()), &options)
+	}
 }
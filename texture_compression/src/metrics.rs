@@ -0,0 +1,179 @@
+// Error Metrics: measures how lossy the compression is
+
+use std;
+use super::CompressionAlgorithm;
+
+/// Error metrics of a channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelMetrics
+{
+	/// Root mean squared error, in 8-bit levels
+	pub rmse: f64,
+	/// Peak signal-to-noise ratio in dB(infinite if lossless)
+	pub psnr: f64,
+	/// Structural similarity index(1.0 if identical)
+	pub ssim: f64
+}
+
+fn check_extent(a: &[u8], b: &[u8], size: (usize, usize), channels: usize)
+{
+	assert_eq!(a.len(), size.0 * size.1 * channels);
+	assert_eq!(a.len(), b.len());
+}
+fn channel_values<'a>(image: &'a [u8], channels: usize, channel: usize) -> std::iter::StepBy<std::iter::Skip<std::slice::Iter<'a, u8>>>
+{
+	image.iter().skip(channel).step_by(channels)
+}
+
+/// Per-channel root mean squared error
+pub fn rmse(a: &[u8], b: &[u8], size: (usize, usize), channels: usize) -> Vec<f64>
+{
+	check_extent(a, b, size, channels);
+	(0 .. channels).map(|c|
+	{
+		let sum = channel_values(a, channels, c).zip(channel_values(b, channels, c))
+			.fold(0.0, |acc, (&x, &y)| acc + (x as f64 - y as f64) * (x as f64 - y as f64));
+		(sum / (size.0 * size.1) as f64).sqrt()
+	}).collect()
+}
+/// Per-channel peak signal-to-noise ratio in dB
+pub fn psnr(a: &[u8], b: &[u8], size: (usize, usize), channels: usize) -> Vec<f64>
+{
+	rmse(a, b, size, channels).into_iter().map(rmse_to_psnr).collect()
+}
+fn rmse_to_psnr(rmse: f64) -> f64
+{
+	if rmse == 0.0 { f64::INFINITY } else { 20.0 * (255.0 / rmse).log10() }
+}
+
+/// Per-channel mean SSIM, over 8x8 windows placed every 4 texels
+pub fn ssim(a: &[u8], b: &[u8], size: (usize, usize), channels: usize) -> Vec<f64>
+{
+	const WINDOW: usize = 8;
+	const STEP: usize = 4;
+	const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+	const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+	check_extent(a, b, size, channels);
+
+	// windows covering the image(a smaller image is a single window)
+	fn window_origins(len: usize) -> Vec<usize>
+	{
+		if len <= WINDOW { vec![0] } else { (0 .. (len - WINDOW) / STEP + 1).map(|n| n * STEP).collect() }
+	}
+	let (wxs, wys) = (window_origins(size.0), window_origins(size.1));
+	let (ww, wh) = (std::cmp::min(WINDOW, size.0), std::cmp::min(WINDOW, size.1));
+	(0 .. channels).map(|c|
+	{
+		let total = wys.iter().flat_map(|&y| wxs.iter().map(move |&x| (x, y))).map(|(wx, wy)|
+		{
+			let texels = (wy .. wy + wh).flat_map(|y| (wx .. wx + ww).map(move |x| (x + y * size.0) * channels + c))
+				.map(|i| (a[i] as f64, b[i] as f64)).collect::<Vec<_>>();
+			let n = texels.len() as f64;
+			let (mean_a, mean_b) = texels.iter().fold((0.0, 0.0), |(sa, sb), &(x, y)| (sa + x, sb + y));
+			let (mean_a, mean_b) = (mean_a / n, mean_b / n);
+			let (var_a, var_b, cov) = texels.iter().fold((0.0, 0.0, 0.0), |(va, vb, cv), &(x, y)|
+				(va + (x - mean_a) * (x - mean_a), vb + (y - mean_b) * (y - mean_b), cv + (x - mean_a) * (y - mean_b)));
+			let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+			((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2)) / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+		}).fold(0.0, |acc, s| acc + s);
+		total / (wxs.len() * wys.len()) as f64
+	}).collect()
+}
+
+/// Worst absolute error(over all channels) of each block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockErrorMap
+{
+	/// Number of blocks in each direction
+	pub blocks: (usize, usize),
+	/// Texel extent of a block
+	pub block_extent: (usize, usize),
+	/// Worst errors, row-major
+	pub worst: Vec<u8>
+}
+impl BlockErrorMap
+{
+	pub fn new(a: &[u8], b: &[u8], size: (usize, usize), channels: usize, block_extent: (usize, usize)) -> Self
+	{
+		check_extent(a, b, size, channels);
+		let blocks = (size.0.div_ceil(block_extent.0), size.1.div_ceil(block_extent.1));
+		let mut worst = vec![0u8; blocks.0 * blocks.1];
+		for y in 0 .. size.1
+		{
+			for x in 0 .. size.0
+			{
+				let block = &mut worst[x / block_extent.0 + (y / block_extent.1) * blocks.0];
+				for c in 0 .. channels
+				{
+					let i = (x + y * size.0) * channels + c;
+					*block = std::cmp::max(*block, a[i].abs_diff(b[i]));
+				}
+			}
+		}
+		BlockErrorMap { blocks, block_extent, worst }
+	}
+	/// Expands the map to a grayscale image of the texture's extent(brighter is worse), for viewing in an image editor
+	pub fn to_image(&self, size: (usize, usize)) -> Vec<u8>
+	{
+		let peak = std::cmp::max(self.worst.iter().cloned().max().unwrap_or(0), 1) as u32;
+		(0 .. size.1).flat_map(|y| (0 .. size.0).map(move |x| (x, y)))
+			.map(|(x, y)| (self.worst[x / self.block_extent.0 + (y / self.block_extent.1) * self.blocks.0] as u32 * 255 / peak) as u8)
+			.collect()
+	}
+}
+
+/// Quality report of an algorithm for a source image
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualityReport
+{
+	pub channels: Vec<ChannelMetrics>,
+	pub heatmap: BlockErrorMap
+}
+/// Compresses the source with the algorithm and measures the decoded result against the source
+pub fn quality_report<Algorithm: CompressionAlgorithm>(source: &[u8], size: (usize, usize)) -> QualityReport
+{
+	let channels = Algorithm::source_channels();
+	let decoded = Algorithm::decompress(&Algorithm::compress(source, size), size);
+	let metrics = rmse(source, &decoded, size, channels).into_iter().zip(ssim(source, &decoded, size, channels))
+		.map(|(e, s)| ChannelMetrics { rmse: e, psnr: rmse_to_psnr(e), ssim: s }).collect();
+	QualityReport
+	{
+		channels: metrics,
+		heatmap: BlockErrorMap::new(source, &decoded, size, channels, Algorithm::format().block_extent())
+	}
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use {BC4, BC5};
+
+	#[test] fn identical_images()
+	{
+		let image = (0 .. 16 * 16).map(|n| n as u8).collect::<Vec<_>>();
+		assert_eq!(rmse(&image, &image, (16, 16), 1), vec![0.0]);
+		assert!(psnr(&image, &image, (16, 16), 1)[0].is_infinite());
+		assert!((ssim(&image, &image, (16, 16), 1)[0] - 1.0).abs() < 1.0e-9);
+	}
+	#[test] fn known_errors()
+	{
+		let (a, b) = ([10u8, 0, 10, 0], [13u8, 0, 6, 0]);
+		let e = rmse(&a, &b, (2, 1), 2);
+		assert!((e[0] - 12.5f64.sqrt()).abs() < 1.0e-9);
+		assert_eq!(e[1], 0.0);
+		let map = BlockErrorMap::new(&a, &b, (2, 1), 2, (1, 1));
+		assert_eq!(map.worst, vec![3, 4]);
+		assert_eq!(map.to_image((2, 1)), vec![191, 255]);
+	}
+	#[test] fn reports_bc4_bc5()
+	{
+		let gradient = (0 .. 32 * 32).map(|n| ((n % 32) * 8) as u8).collect::<Vec<_>>();
+		let report = quality_report::<BC4>(&gradient, (32, 32));
+		assert_eq!(report.channels.len(), 1);
+		assert!(report.channels[0].psnr > 35.0 && report.channels[0].ssim > 0.95);
+		assert_eq!(report.heatmap.blocks, (8, 8));
+		let rg = gradient.iter().flat_map(|&v| vec![v, 255 - v]).collect::<Vec<_>>();
+		assert_eq!(quality_report::<BC5>(&rg, (32, 32)).channels.len(), 2);
+	}
+}
@@ -0,0 +1,227 @@
+// Mipmap Chain Generation
+
+use std;
+use super::{CompressionAlgorithm, ImageView, CompressionOptions};
+
+/// Downsampling filter used for generating lower levels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipFilter
+{
+	/// Area average of covered texels
+	Box,
+	/// Kaiser-windowed sinc(sharper, may ring slightly)
+	Kaiser
+}
+impl MipFilter
+{
+	/// Filter radius, in destination texels
+	fn support(&self) -> f32 { match *self { MipFilter::Box => 0.5, MipFilter::Kaiser => 3.0 } }
+	fn weight(&self, t: f32) -> f32
+	{
+		match *self
+		{
+			MipFilter::Box => if t.abs() <= 0.5 { 1.0 } else { 0.0 },
+			MipFilter::Kaiser =>
+			{
+				const ALPHA: f32 = 4.0;
+				let width = self.support();
+				if t.abs() >= width { return 0.0; }
+				let sinc = if t == 0.0 { 1.0 } else { (std::f32::consts::PI * t).sin() / (std::f32::consts::PI * t) };
+				let r = t / width;
+				sinc * bessel_i0(ALPHA * (1.0 - r * r).sqrt()) / bessel_i0(ALPHA)
+			}
+		}
+	}
+}
+/// Modified Bessel function of the first kind, order 0(power series)
+fn bessel_i0(x: f32) -> f32
+{
+	let (mut sum, mut term) = (1.0f32, 1.0f32);
+	for k in 1 .. 32
+	{
+		let h = x / (2 * k) as f32;
+		term *= h * h;
+		sum += term;
+		if term < sum * 1.0e-7 { break; }
+	}
+	sum
+}
+
+/// A level in the compressed mipmap chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MipLevel
+{
+	/// Extent of the level, in texels
+	pub size: (usize, usize),
+	/// Byte offset of the level in the compressed chain
+	pub offset: usize,
+	/// Byte length of the level
+	pub length: usize
+}
+
+/// Number of levels down to 1x1(including the base level)
+pub fn level_count(size: (usize, usize)) -> usize
+{
+	let mut n = 1;
+	let mut s = std::cmp::max(size.0, size.1);
+	while s > 1 { s >>= 1; n += 1; }
+	n
+}
+fn next_level_size(size: (usize, usize)) -> (usize, usize)
+{
+	(std::cmp::max(size.0 >> 1, 1), std::cmp::max(size.1 >> 1, 1))
+}
+
+// Separable resampling pass along a line: returns [(source index, weight)] for each destination texel
+fn resample_weights(filter: MipFilter, src_len: usize, dst_len: usize) -> Vec<Vec<(usize, f32)>>
+{
+	let scale = src_len as f32 / dst_len as f32;
+	(0 .. dst_len).map(|x|
+	{
+		let center = (x as f32 + 0.5) * scale;
+		let radius = filter.support() * scale;
+		let (first, last) = ((center - radius).floor() as isize, (center + radius).ceil() as isize);
+		let mut taps = (first .. last + 1).map(|i|
+		{
+			let t = (i as f32 + 0.5 - center) / scale;
+			let clamped = std::cmp::min(std::cmp::max(i, 0), src_len as isize - 1) as usize;
+			(clamped, filter.weight(t))
+		}).filter(|&(_, w)| w != 0.0).collect::<Vec<_>>();
+		let total = taps.iter().fold(0.0, |a, &(_, w)| a + w);
+		for t in &mut taps { t.1 /= total; }
+		taps
+	}).collect()
+}
+/// Downsamples an interleaved 8-bit image to the next mip level
+pub fn downsample(source: &[u8], size: (usize, usize), channels: usize, filter: MipFilter) -> (Vec<u8>, (usize, usize))
+{
+	// resampled in float to avoid double rounding
+	let (dest, dsize) = downsample_f32(&source.iter().map(|&v| v as f32).collect::<Vec<_>>(), size, channels, filter);
+	(dest.into_iter().map(|v| (v + 0.5).clamp(0.0, 255.0) as u8).collect(), dsize)
+}
+/// Downsamples an interleaved float image to the next mip level(values out of 0.0 ..= 1.0 are kept)
+pub fn downsample_f32(source: &[f32], size: (usize, usize), channels: usize, filter: MipFilter) -> (Vec<f32>, (usize, usize))
+{
+	assert_eq!(size.0 * size.1 * channels, source.len());
+	let dsize = next_level_size(size);
+	let (hw, vw) = (resample_weights(filter, size.0, dsize.0), resample_weights(filter, size.1, dsize.1));
+
+	// horizontal pass
+	let mut horz = vec![0.0f32; dsize.0 * size.1 * channels];
+	for y in 0 .. size.1
+	{
+		for (x, taps) in hw.iter().enumerate()
+		{
+			for c in 0 .. channels
+			{
+				horz[(x + y * dsize.0) * channels + c] = taps.iter().fold(0.0, |a, &(sx, w)| a + source[(sx + y * size.0) * channels + c] * w);
+			}
+		}
+	}
+	// vertical pass
+	let mut dest = vec![0.0f32; dsize.0 * dsize.1 * channels];
+	for (y, taps) in vw.iter().enumerate()
+	{
+		for x in 0 .. dsize.0
+		{
+			for c in 0 .. channels
+			{
+				dest[(x + y * dsize.0) * channels + c] = taps.iter().fold(0.0, |a, &(sy, w)| a + horz[(x + sy * dsize.0) * channels + c] * w);
+			}
+		}
+	}
+	(dest, dsize)
+}
+
+/// Compresses the full mipmap chain(down to 1x1), returns concatenated levels and the layout of each level
+pub fn compress_mipmapped<Algorithm: CompressionAlgorithm>(source: &[u8], size: (usize, usize), filter: MipFilter) -> (Vec<u8>, Vec<MipLevel>)
+{
+	compress_mipmapped_image::<Algorithm>(&ImageView::new(source, size, Algorithm::source_channels()), &CompressionOptions::default(), filter)
+}
+/// `compress_mipmapped` with encoder options: the swizzled channels are downsampled, so each level is encoded alike
+pub fn compress_mipmapped_image<Algorithm: CompressionAlgorithm>(image: &ImageView, options: &CompressionOptions, filter: MipFilter) -> (Vec<u8>, Vec<MipLevel>)
+{
+	let channels = Algorithm::source_channels();
+	let level_options = CompressionOptions { swizzle: [0, 1, 2, 3], .. options.clone() };
+	let mut levels = Vec::with_capacity(level_count(image.size));
+	let mut compressed = Vec::new();
+	let mut push_level = |level: &ImageView|
+	{
+		let level_bytes = Algorithm::compress_image(level, &level_options);
+		levels.push(MipLevel { size: level.size, offset: compressed.len(), length: level_bytes.len() });
+		compressed.extend_from_slice(&level_bytes);
+	};
+	// float-source encoders get float levels, so that the HDR range survives downsampling
+	if Algorithm::float_source()
+	{
+		let mut current = (image.pack_f32(&options.swizzle[.. channels]), image.size);
+		loop
+		{
+			push_level(&ImageView::new(&current.0[..], current.1, channels));
+			if current.1 == (1, 1) { break; }
+			current = downsample_f32(&current.0, current.1, channels, filter);
+		}
+	}
+	else
+	{
+		let mut current = (image.pack(&options.swizzle[.. channels]), image.size);
+		loop
+		{
+			push_level(&ImageView::new(&current.0[..], current.1, channels));
+			if current.1 == (1, 1) { break; }
+			current = downsample(&current.0, current.1, channels, filter);
+		}
+	}
+	(compressed, levels)
+}
+
+#[cfg(test)]
+mod testings
+{
+	use super::*;
+	use BC4;
+
+	#[test] fn level_counts()
+	{
+		assert_eq!(level_count((1, 1)), 1);
+		assert_eq!(level_count((16, 16)), 5);
+		assert_eq!(level_count((160, 560)), 10);
+	}
+	#[test] fn box_filter_averages_2x2()
+	{
+		let (d, s) = downsample(&[0, 255, 255, 255, 10, 20, 30, 40], (4, 2), 1, MipFilter::Box);
+		assert_eq!(s, (2, 1));
+		assert_eq!(d, vec![71, 145]);
+	}
+	#[test] fn chain_offsets_are_contiguous()
+	{
+		let (data, levels) = compress_mipmapped::<BC4>(&[200; 13 * 7], (13, 7), MipFilter::Kaiser);
+		assert_eq!(levels.iter().map(|l| l.size).collect::<Vec<_>>(), vec![(13, 7), (6, 3), (3, 1), (1, 1)]);
+		assert_eq!(levels.iter().map(|l| l.length).collect::<Vec<_>>(), vec![4 * 2 * 8, 2 * 8, 8, 8]);
+		for w in levels.windows(2) { assert_eq!(w[0].offset + w[0].length, w[1].offset); }
+		assert_eq!(data.len(), levels.last().map(|l| l.offset + l.length).unwrap());
+	}
+	#[test] fn swizzled_chain_matches_packed_source()
+	{
+		use {ImageView, CompressionOptions, BC5};
+
+		let rgba = (0 .. 12 * 10).flat_map(|n| vec![(n * 7) as u8, 0, 0, (n * 3) as u8]).collect::<Vec<_>>();
+		let ra = ImageView::new(&rgba[..], (12, 10), 4).pack(&[0, 3]);
+		let options = CompressionOptions { swizzle: [0, 3, 0, 0], .. CompressionOptions::default() };
+		let swizzled = compress_mipmapped_image::<BC5>(&ImageView::new(&rgba[..], (12, 10), 4), &options, MipFilter::Box);
+		assert_eq!(swizzled, compress_mipmapped::<BC5>(&ra, (12, 10), MipFilter::Box));
+	}
+	#[test] fn float_chain_keeps_hdr_range()
+	{
+		use {ImageView, CompressionOptions, BC6HUF16};
+
+		let hdr = (0 .. 16 * 16).flat_map(|n| vec![4.0 + (n % 16) as f32 * 0.25, 2.0, 1.5]).collect::<Vec<f32>>();
+		let (data, levels) = compress_mipmapped_image::<BC6HUF16>(&ImageView::new(&hdr[..], (16, 16), 3), &CompressionOptions::default(), MipFilter::Kaiser);
+		assert_eq!(levels.len(), 5);
+		for l in &levels
+		{
+			let decoded = BC6HUF16::decompress_f16(&data[l.offset .. l.offset + l.length], l.size);
+			assert!(decoded.chunks(3).all(|t| f32::from(t[0]) > 3.5 && (f32::from(t[1]) - 2.0).abs() < 0.1), "level {:?} lost the HDR range", l.size);
+		}
+	}
+}
@@ -0,0 +1,76 @@
+// Normal Maps from Height Maps: tangent-space X/Y packed for BC5(the shader reconstructs Z)
+
+use super::{CompressionAlgorithm, BC5, ImageView};
+
+/// Converts a channel of the height map into tangent-space normal X/Y(2 interleaved 8-bit channels, 128 is zero).
+/// Slopes are taken with the Sobel operator over heights normalized to 0.0 ..= 1.0 and scaled by `strength`;
+/// texels outside the image repeat the edge. X points right and Y down the image(DirectX convention)
+pub fn height_to_normal_xy(height: &ImageView, channel: usize, strength: f32) -> Vec<u8>
+{
+	let (w, h) = height.size;
+	let at = |x: isize, y: isize|
+	{
+		let (cx, cy) = (x.max(0).min(w as isize - 1) as usize, y.max(0).min(h as isize - 1) as usize);
+		height.texel(cx, cy, channel) as f32 / 255.0
+	};
+	let mut normals = Vec::with_capacity(w * h * 2);
+	for y in 0 .. h as isize
+	{
+		for x in 0 .. w as isize
+		{
+			let dx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+			let dy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+			let (nx, ny) = (-dx * strength, -dy * strength);
+			let length = (nx * nx + ny * ny + 1.0).sqrt();
+			normals.push(encode_unit(nx / length));
+			normals.push(encode_unit(ny / length));
+		}
+	}
+	normals
+}
+fn encode_unit(v: f32) -> u8 { ((v * 0.5 + 0.5) * 255.0).round().clamp(0.0, 255.0) as u8 }
+
+/// Height map channel to a BC5 normal map(X in red, Y in green)
+pub fn compress_normal_map(height: &ImageView, channel: usize, strength: f32) -> Vec<u8>
+{
+	BC5::compress(&height_to_normal_xy(height, channel, strength), height.size)
+}
+
+/// Unit normal from the stored X/Y, as the shader reconstructs it: z = sqrt(1 - x^2 - y^2)
+pub fn reconstruct_normal(x: u8, y: u8) -> [f32; 3]
+{
+	let (nx, ny) = (x as f32 / 255.0 * 2.0 - 1.0, y as f32 / 255.0 * 2.0 - 1.0);
+	[nx, ny, (1.0 - nx * nx - ny * ny).max(0.0).sqrt()]
+}
+
+#[cfg(test)]
+mod testings
+{
+	use {CompressionAlgorithm, BC5, ImageView};
+
+	#[test] fn flat_height_is_straight_up()
+	{
+		let height = [77u8; 6 * 5];
+		let compressed = super::compress_normal_map(&ImageView::new(&height[..], (6, 5), 1), 0, 4.0);
+		let xy = BC5::decompress(&compressed, (6, 5));
+		assert!(xy.iter().all(|&v| v == 128), "{:?}", xy);
+		assert!(super::reconstruct_normal(128, 128)[2] > 0.9999);
+	}
+	#[test] fn slopes_tilt_against_the_gradient()
+	{
+		// height rises to the right(red channel of an RGBA layer): normals lean left, with no Y component
+		let rgba = (0 .. 8 * 4).flat_map(|n| vec![((n % 8) * 16) as u8, 0, 0, 255]).collect::<Vec<_>>();
+		let xy = super::height_to_normal_xy(&ImageView::new(&rgba[..], (8, 4), 4), 0, 2.0);
+		let slope = -8.0 * 16.0 / 255.0 * 2.0;
+		let expected = super::encode_unit(slope / (slope * slope + 1.0f32).sqrt());
+		for t in xy.chunks(2).enumerate().filter(|&(n, _)| n % 8 != 0 && n % 8 != 7).map(|(_, t)| t)
+		{
+			assert_eq!(t, &[expected, 128]);
+		}
+		let n = super::reconstruct_normal(expected, 128);
+		assert!(n[0] < 0.0 && (n[0] * n[0] + n[2] * n[2] - 1.0).abs() < 0.02);
+		// stronger bumps lean further
+		let steep = super::height_to_normal_xy(&ImageView::new(&rgba[..], (8, 4), 4), 0, 8.0);
+		assert!(steep[2] < xy[2]);
+	}
+}